@@ -0,0 +1,313 @@
+use std::cell::Cell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::source_map::Span;
+use crate::symbol::Symbol;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LiteralValue {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Symbol),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnaryOp {
+    Bang,
+    Minus,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BinaryOp {
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    EqualEqual,
+    BangEqual,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LogicalOp {
+    And,
+    Or,
+}
+
+/// An operator along with where it was written, so errors can point at it.
+#[derive(Debug, Copy, Clone)]
+pub struct Operator<T> {
+    pub kind: T,
+    pub line: usize,
+    pub span: Span,
+}
+
+/// An identifier along with where it was written.
+#[derive(Debug, Copy, Clone)]
+pub struct Name {
+    pub symbol: Symbol,
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
+}
+
+/// A use of a variable. The resolver fills in `depth` with how many scopes
+/// out from the use the variable was declared, leaving it `None` for globals.
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub name: Name,
+    pub depth: Cell<Option<usize>>,
+}
+
+impl Variable {
+    pub fn new(name: Name) -> Self {
+        Variable {
+            name,
+            depth: Cell::new(None),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Literal(LiteralValue),
+    Grouping(Box<Expr>),
+    Unary {
+        operator: Operator<UnaryOp>,
+        right: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        operator: Operator<BinaryOp>,
+        right: Box<Expr>,
+    },
+    Logical {
+        left: Box<Expr>,
+        operator: Operator<LogicalOp>,
+        right: Box<Expr>,
+    },
+    Variable(Variable),
+    Assign {
+        variable: Variable,
+        value: Box<Expr>,
+    },
+    /// `line` is the line of the closing parenthesis.
+    Call {
+        callee: Box<Expr>,
+        arguments: Vec<Expr>,
+        line: usize,
+    },
+    Get {
+        object: Box<Expr>,
+        name: Name,
+    },
+    Set {
+        object: Box<Expr>,
+        name: Name,
+        value: Box<Expr>,
+    },
+    /// A list literal, `[a, b, c]`.
+    List(Vec<Expr>),
+    /// A map literal, `{key: value, ...}`, with its entries in order.
+    /// `line` is the line of the closing brace.
+    Map {
+        entries: Vec<(Expr, Expr)>,
+        line: usize,
+    },
+    /// `object[index]`. `line` is the line of the closing bracket.
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        line: usize,
+    },
+    SetIndex {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        value: Box<Expr>,
+        line: usize,
+    },
+    This(Variable),
+    Super {
+        keyword: Variable,
+        method: Name,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Expression(Expr),
+    Print(Expr),
+    Var {
+        name: Name,
+        initializer: Option<Expr>,
+        doc: Option<String>,
+    },
+    Block(Vec<Stmt>),
+    If {
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+    },
+    /// `increment` is only set for a desugared `for` loop. It runs after
+    /// each pass of the body, even one cut short by `continue`.
+    While {
+        condition: Expr,
+        body: Box<Stmt>,
+        increment: Option<Expr>,
+    },
+    /// `for (variable in iterable) body`, with a fresh `variable` for each
+    /// pass. `keyword` is the `in` token, where iteration errors point.
+    ForIn {
+        variable: Name,
+        keyword: Name,
+        iterable: Expr,
+        body: Box<Stmt>,
+    },
+    Break {
+        keyword: Name,
+    },
+    Continue {
+        keyword: Name,
+    },
+    Function(Rc<FunctionDecl>),
+    Return {
+        keyword: Name,
+        value: Option<Expr>,
+    },
+    Class(ClassDecl),
+}
+
+/// `doc` holds the text of any `///` comments written just before the
+/// declaration.
+#[derive(Debug)]
+pub struct FunctionDecl {
+    pub name: Name,
+    pub params: Vec<Name>,
+    pub body: Vec<Stmt>,
+    pub doc: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassDecl {
+    pub name: Name,
+    pub superclass: Option<Variable>,
+    pub methods: Vec<Rc<FunctionDecl>>,
+    pub doc: Option<String>,
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UnaryOp::Bang => "!",
+            UnaryOp::Minus => "-",
+        })
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Star => "*",
+            BinaryOp::Slash => "/",
+            BinaryOp::Percent => "%",
+            BinaryOp::Greater => ">",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::Less => "<",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::EqualEqual => "==",
+            BinaryOp::BangEqual => "!=",
+        })
+    }
+}
+
+impl fmt::Display for LogicalOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogicalOp::And => "and",
+            LogicalOp::Or => "or",
+        })
+    }
+}
+
+impl fmt::Display for LiteralValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LiteralValue::Nil => write!(f, "nil"),
+            LiteralValue::Bool(value) => write!(f, "{}", value),
+            LiteralValue::Number(value) => write!(f, "{}", value),
+            LiteralValue::String(value) => write!(f, "{:?}", value.as_str()),
+        }
+    }
+}
+
+/// Prints the expression fully parenthesized in prefix form, e.g.
+/// `(* (- 123) (group 45.67))`, which makes precedence easy to check.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Grouping(expr) => write!(f, "(group {})", expr),
+            Expr::Unary { operator, right } => write!(f, "({} {})", operator.kind, right),
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => write!(f, "({} {} {})", operator.kind, left, right),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => write!(f, "({} {} {})", operator.kind, left, right),
+            Expr::Variable(variable) => write!(f, "{}", variable.name.symbol),
+            Expr::Assign { variable, value } => {
+                write!(f, "(= {} {})", variable.name.symbol, value)
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                write!(f, "(call {}", callee)?;
+                for argument in arguments {
+                    write!(f, " {}", argument)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Get { object, name } => write!(f, "(. {} {})", object, name.symbol),
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => write!(f, "(= (. {} {}) {})", object, name.symbol, value),
+            Expr::List(elements) => {
+                write!(f, "(list")?;
+                for element in elements {
+                    write!(f, " {}", element)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Map { entries, .. } => {
+                write!(f, "(map")?;
+                for (key, value) in entries {
+                    write!(f, " {} {}", key, value)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Index { object, index, .. } => write!(f, "([] {} {})", object, index),
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => write!(f, "(= ([] {} {}) {})", object, index, value),
+            Expr::This(_) => write!(f, "this"),
+            Expr::Super { method, .. } => write!(f, "(super {})", method.symbol),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use crate::bytecode::object::ObjRef;
+use crate::bytecode::value::Value;
+
+/// How many constants a chunk can hold, the most a 24-bit operand can index.
+pub const CONSTANTS_MAX: usize = 1 << 24;
+
+/// Declares `OpCode` along with its conversion from a byte, so the two can't
+/// get out of sync when instructions are added.
+macro_rules! opcodes {
+    ($($(#[$doc:meta])* $name:ident,)*) => {
+        /// One-byte instruction codes. Operands, where an instruction has
+        /// any, follow it in the code stream.
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        #[repr(u8)]
+        pub enum OpCode {
+            $($(#[$doc])* $name,)*
+        }
+
+        impl TryFrom<u8> for OpCode {
+            type Error = u8;
+
+            fn try_from(byte: u8) -> Result<Self, Self::Error> {
+                const ALL: &[OpCode] = &[$(OpCode::$name,)*];
+                ALL.get(byte as usize).copied().ok_or(byte)
+            }
+        }
+    };
+}
+
+opcodes! {
+    /// Operand: constant index. Every instruction taking a constant index
+    /// has a `Long` form that takes a 24-bit one instead, for chunks with
+    /// more than 256 constants.
+    Constant,
+    ConstantLong,
+    Nil,
+    True,
+    False,
+    Pop,
+    /// Operand: stack slot relative to the frame.
+    GetLocal,
+    SetLocal,
+    /// Operand: constant index of the name.
+    GetGlobal,
+    GetGlobalLong,
+    DefineGlobal,
+    DefineGlobalLong,
+    SetGlobal,
+    SetGlobalLong,
+    /// Operand: upvalue index in the running closure.
+    GetUpvalue,
+    SetUpvalue,
+    /// Operand: constant index of the name.
+    GetProperty,
+    GetPropertyLong,
+    SetProperty,
+    SetPropertyLong,
+    GetSuper,
+    GetSuperLong,
+    Equal,
+    Greater,
+    Less,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Not,
+    Negate,
+    Print,
+    /// Operand: 16-bit forward offset.
+    Jump,
+    JumpIfFalse,
+    /// Operand: 16-bit backward offset.
+    Loop,
+    /// Operand: argument count.
+    Call,
+    /// Operands: constant index of the method name, argument count.
+    Invoke,
+    InvokeLong,
+    SuperInvoke,
+    SuperInvokeLong,
+    /// Operands: constant index of the function, then an `is_local`/index
+    /// byte pair for each upvalue it captures.
+    Closure,
+    ClosureLong,
+    CloseUpvalue,
+    Return,
+    /// Operand: constant index of the name.
+    Class,
+    ClassLong,
+    Inherit,
+    /// Operand: constant index of the name.
+    Method,
+    MethodLong,
+    /// Operand: element count. The elements are on the stack, first
+    /// element deepest.
+    BuildList,
+    /// Operand: entry count. Each key is on the stack just below its value.
+    BuildMap,
+    GetIndex,
+    SetIndex,
+    /// Replaces the value on top of the stack with something a `for` loop
+    /// can call `done()` and `next()` on: an `Iteration` for lists, maps and
+    /// strings, or whatever an instance's `iter()` method returns.
+    Iter,
+}
+
+impl OpCode {
+    /// The wide form of an instruction taking a constant index, or `None`
+    /// for instructions without a constant operand.
+    pub fn long_form(self) -> Option<OpCode> {
+        match self {
+            OpCode::Constant => Some(OpCode::ConstantLong),
+            OpCode::GetGlobal => Some(OpCode::GetGlobalLong),
+            OpCode::DefineGlobal => Some(OpCode::DefineGlobalLong),
+            OpCode::SetGlobal => Some(OpCode::SetGlobalLong),
+            OpCode::GetProperty => Some(OpCode::GetPropertyLong),
+            OpCode::SetProperty => Some(OpCode::SetPropertyLong),
+            OpCode::GetSuper => Some(OpCode::GetSuperLong),
+            OpCode::Invoke => Some(OpCode::InvokeLong),
+            OpCode::SuperInvoke => Some(OpCode::SuperInvokeLong),
+            OpCode::Closure => Some(OpCode::ClosureLong),
+            OpCode::Class => Some(OpCode::ClassLong),
+            OpCode::Method => Some(OpCode::MethodLong),
+            _ => None,
+        }
+    }
+
+    /// Whether this is the wide form of an instruction, with a 24-bit
+    /// constant index.
+    pub fn is_long(self) -> bool {
+        matches!(
+            self,
+            OpCode::ConstantLong
+                | OpCode::GetGlobalLong
+                | OpCode::DefineGlobalLong
+                | OpCode::SetGlobalLong
+                | OpCode::GetPropertyLong
+                | OpCode::SetPropertyLong
+                | OpCode::GetSuperLong
+                | OpCode::InvokeLong
+                | OpCode::SuperInvokeLong
+                | OpCode::ClosureLong
+                | OpCode::ClassLong
+                | OpCode::MethodLong
+        )
+    }
+}
+
+/// A constant that can be shared by every instruction using an equal one.
+/// Numbers compare by their bits, so `0` and `-0` stay apart, and strings
+/// are interned, so equal strings share an `ObjRef`.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum ConstantKey {
+    Number(u64),
+    Obj(ObjRef),
+}
+
+/// A sequence of bytecode along with the constants it refers to and the
+/// source line each byte came from.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Value>,
+    // Where each constant already in `constants` is.
+    constant_indices: HashMap<ConstantKey, usize>,
+    // Run-length encoded: each entry is a line and how many consecutive
+    // bytes came from it.
+    lines: Vec<(usize, usize)>,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Chunk::default()
+    }
+
+    pub fn write(&mut self, byte: u8, line: usize) {
+        self.code.push(byte);
+        match self.lines.last_mut() {
+            Some((last_line, count)) if *last_line == line => *count += 1,
+            _ => self.lines.push((line, 1)),
+        }
+    }
+
+    /// Adds a constant, returning its index. A number or object that's
+    /// already in the chunk is reused rather than added again.
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        let key = match value {
+            Value::Number(number) => ConstantKey::Number(number.to_bits()),
+            Value::Obj(reference) => ConstantKey::Obj(reference),
+            Value::Nil | Value::Bool(_) => {
+                self.constants.push(value);
+                return self.constants.len() - 1;
+            }
+        };
+        if let Some(index) = self.constant_indices.get(&key) {
+            return *index;
+        }
+        self.constants.push(value);
+        let index = self.constants.len() - 1;
+        self.constant_indices.insert(key, index);
+        index
+    }
+
+    /// Source line of the byte at `offset`.
+    pub fn line(&self, offset: usize) -> usize {
+        let mut start = 0;
+        for (line, count) in &self.lines {
+            start += count;
+            if offset < start {
+                return *line;
+            }
+        }
+        self.lines.last().map_or(0, |(line, _)| *line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_numbers_and_objects() {
+        let mut chunk = Chunk::new();
+        let one = chunk.add_constant(Value::Number(1.0));
+        let name = chunk.add_constant(Value::Obj(ObjRef(3)));
+        assert_eq!(chunk.add_constant(Value::Number(1.0)), one);
+        assert_eq!(chunk.add_constant(Value::Obj(ObjRef(3))), name);
+        assert_ne!(chunk.add_constant(Value::Obj(ObjRef(4))), name);
+        assert_eq!(chunk.constants.len(), 3);
+    }
+
+    #[test]
+    fn keeps_numbers_with_different_bits_apart() {
+        // 0 and -0 compare equal but print differently.
+        let mut chunk = Chunk::new();
+        let zero = chunk.add_constant(Value::Number(0.0));
+        assert_ne!(chunk.add_constant(Value::Number(-0.0)), zero);
+    }
+
+    #[test]
+    fn only_indexed_instructions_have_long_forms() {
+        assert_eq!(OpCode::Constant.long_form(), Some(OpCode::ConstantLong));
+        assert_eq!(OpCode::Closure.long_form(), Some(OpCode::ClosureLong));
+        assert_eq!(OpCode::Add.long_form(), None);
+        assert!(OpCode::SuperInvokeLong.is_long());
+        assert!(!OpCode::SuperInvoke.is_long());
+    }
+}
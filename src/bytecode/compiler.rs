@@ -0,0 +1,1322 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::bytecode::chunk::{Chunk, OpCode, CONSTANTS_MAX};
+use crate::bytecode::heap::Heap;
+use crate::bytecode::object::{Function, Obj, ObjRef};
+use crate::bytecode::value::Value;
+use crate::parser::ParseError;
+use crate::scanner::{LexError, Literal, Scanner, Token, TokenType};
+use crate::source_map::{Position, Span};
+
+/// Functions can't take more arguments than this, since the count is a
+/// one-byte operand.
+const MAX_ARGUMENTS: usize = 255;
+/// The element count of a list or map literal is a one-byte operand too.
+const MAX_ELEMENTS: usize = 255;
+/// Locals and upvalues are addressed by a one-byte operand.
+const MAX_LOCALS: usize = 256;
+const MAX_UPVALUES: usize = 256;
+
+#[derive(Debug, Clone)]
+pub enum CompileError {
+    Lex(LexError),
+    Syntax(ParseError),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Lex(error) => write!(f, "{}", error),
+            CompileError::Syntax(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Compiles a whole script into a function taking no arguments, returning
+/// every error found if there are any.
+pub fn compile(source: &str, heap: &mut Heap) -> Result<ObjRef, Vec<CompileError>> {
+    let mut compiler = Compiler::new(Scanner::new(source), heap);
+    compiler.advance();
+    while !compiler.r#match(TokenType::Eof) {
+        compiler.declaration();
+    }
+    let (function, _) = compiler.end_function();
+
+    if compiler.errors.is_empty() {
+        Ok(function)
+    } else {
+        Err(compiler.errors)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    None,
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::None => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+/// Parses part of an expression. The flag says whether an assignment may
+/// follow.
+type ParseFn<'src, 'h> = fn(&mut Compiler<'src, 'h>, bool);
+
+struct ParseRule<'src, 'h> {
+    prefix: Option<ParseFn<'src, 'h>>,
+    infix: Option<ParseFn<'src, 'h>>,
+    precedence: Precedence,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local<'src> {
+    name: &'src str,
+    // `None` until the variable's initializer has been compiled.
+    depth: Option<usize>,
+    is_captured: bool,
+}
+
+/// Where a closure finds a captured variable: a local slot of the enclosing
+/// function, or one of the enclosing function's own upvalues.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct UpvalueRef {
+    pub index: u8,
+    pub is_local: bool,
+}
+
+/// A loop being compiled, for `break` and `continue` in its body.
+struct LoopState {
+    // Where `continue` jumps back to.
+    start: usize,
+    // The scope depth outside the body. Jumping out of the body pops the
+    // locals deeper than this.
+    scope_depth: usize,
+    // Jumps from `break`s, patched once the end of the loop is known.
+    breaks: Vec<usize>,
+}
+
+/// A function being compiled. They nest, with the innermost last.
+struct FunctionState<'src> {
+    kind: FunctionKind,
+    name: Option<ObjRef>,
+    arity: usize,
+    chunk: Chunk,
+    locals: Vec<Local<'src>>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+    // Innermost last. A function's body starts outside of any loop.
+    loops: Vec<LoopState>,
+}
+
+impl<'src> FunctionState<'src> {
+    fn new(kind: FunctionKind, name: Option<ObjRef>) -> Self {
+        // Slot zero holds the function being called, or `this` in methods.
+        let slot_zero = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            FunctionKind::Script | FunctionKind::Function => "",
+        };
+        FunctionState {
+            kind,
+            name,
+            arity: 0,
+            chunk: Chunk::new(),
+            locals: vec![Local {
+                name: slot_zero,
+                depth: Some(0),
+                is_captured: false,
+            }],
+            upvalues: vec![],
+            scope_depth: 0,
+            loops: vec![],
+        }
+    }
+}
+
+struct ClassState {
+    has_superclass: bool,
+}
+
+/// Single-pass compiler from tokens straight to bytecode. Statements are
+/// parsed by recursive descent and expressions by a Pratt parser, emitting
+/// code as each construct is recognized rather than building a tree.
+struct Compiler<'src, 'h> {
+    scanner: Scanner<'src>,
+    // Scanned past `current` by `check_next`, not yet handed out.
+    lookahead: VecDeque<Result<Token<'src>, LexError>>,
+    current: Token<'src>,
+    previous: Token<'src>,
+    heap: &'h mut Heap,
+    errors: Vec<CompileError>,
+    // Set after an error to suppress the cascade that usually follows, until
+    // the next statement boundary.
+    panic_mode: bool,
+    functions: Vec<FunctionState<'src>>,
+    classes: Vec<ClassState>,
+}
+
+impl<'src, 'h> Compiler<'src, 'h> {
+    fn new(scanner: Scanner<'src>, heap: &'h mut Heap) -> Self {
+        Compiler {
+            scanner,
+            lookahead: VecDeque::new(),
+            current: placeholder_token(),
+            previous: placeholder_token(),
+            heap,
+            errors: vec![],
+            panic_mode: false,
+            functions: vec![FunctionState::new(FunctionKind::Script, None)],
+            classes: vec![],
+        }
+    }
+
+    // Declarations and statements
+
+    fn declaration(&mut self) {
+        if self.r#match(TokenType::Class) {
+            self.class_declaration();
+        } else if self.r#match(TokenType::Fun) {
+            self.fun_declaration();
+        } else if self.r#match(TokenType::Var) {
+            self.var_declaration();
+        } else {
+            self.statement();
+        }
+
+        if self.panic_mode {
+            self.synchronize();
+        }
+    }
+
+    fn class_declaration(&mut self) {
+        self.consume(TokenType::Identifier, "Expect class name.");
+        let class_name = self.previous.clone();
+        let name_constant = self.identifier_constant(class_name.source);
+        self.declare_variable();
+
+        let line = self.previous.line;
+        self.emit_op_with_index(line, OpCode::Class, name_constant, &[]);
+        self.define_variable(name_constant);
+
+        self.classes.push(ClassState {
+            has_superclass: false,
+        });
+
+        if self.r#match(TokenType::Less) {
+            self.consume(TokenType::Identifier, "Expect superclass name.");
+            self.variable(false);
+
+            if class_name.source == self.previous.source {
+                self.error("A class can't inherit from itself.");
+            }
+
+            // `super` lives in its own scope so each subclass gets its own.
+            self.begin_scope();
+            self.add_local("super");
+            self.define_variable(0);
+
+            self.named_variable(class_name.source, false);
+            self.emit_op(OpCode::Inherit);
+            self.classes.last_mut().unwrap().has_superclass = true;
+        }
+
+        // Keep the class on the stack while its methods are attached.
+        self.named_variable(class_name.source, false);
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.");
+        while !self.check(TokenType::RightBrace) && !self.check(TokenType::Eof) {
+            self.method();
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.");
+        self.emit_op(OpCode::Pop);
+
+        if self.classes.pop().unwrap().has_superclass {
+            self.end_scope();
+        }
+    }
+
+    fn method(&mut self) {
+        self.consume(TokenType::Identifier, "Expect method name.");
+        let name = self.previous.source;
+        let constant = self.identifier_constant(name);
+
+        let kind = if name == "init" {
+            FunctionKind::Initializer
+        } else {
+            FunctionKind::Method
+        };
+        self.function(kind);
+
+        let line = self.previous.line;
+        self.emit_op_with_index(line, OpCode::Method, constant, &[]);
+    }
+
+    fn fun_declaration(&mut self) {
+        let global = self.parse_variable("Expect function name.");
+        // A function can refer to itself, so it's usable before its body is
+        // compiled.
+        self.mark_initialized();
+        self.function(FunctionKind::Function);
+        self.define_variable(global);
+    }
+
+    /// Compiles a function's parameters and body, whose name has just been
+    /// consumed, and emits the code creating a closure for it.
+    fn function(&mut self, kind: FunctionKind) {
+        let kind_name = match kind {
+            FunctionKind::Function => "function",
+            _ => "method",
+        };
+        let name = self.heap.intern(self.previous.source);
+        self.functions.push(FunctionState::new(kind, Some(name)));
+        self.begin_scope();
+
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expect '(' after {} name.", kind_name),
+        );
+        if !self.check(TokenType::RightParen) {
+            loop {
+                let state = self.functions.last_mut().unwrap();
+                state.arity += 1;
+                if state.arity > MAX_ARGUMENTS {
+                    self.error_at_current(&format!(
+                        "Can't have more than {} parameters.",
+                        MAX_ARGUMENTS
+                    ));
+                }
+                let constant = self.parse_variable("Expect parameter name.");
+                self.define_variable(constant);
+                if !self.r#match(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.");
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind_name),
+        );
+        self.block();
+
+        let (function, upvalues) = self.end_function();
+        let constant = self.make_constant(Value::Obj(function));
+        let line = self.previous.line;
+        self.emit_op_with_index(line, OpCode::Closure, constant, &[]);
+        for upvalue in upvalues {
+            self.emit_byte(upvalue.is_local as u8);
+            self.emit_byte(upvalue.index);
+        }
+    }
+
+    fn var_declaration(&mut self) {
+        let global = self.parse_variable("Expect variable name.");
+
+        if self.r#match(TokenType::Equal) {
+            self.expression();
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        );
+
+        self.define_variable(global);
+    }
+
+    fn statement(&mut self) {
+        if self.r#match(TokenType::Print) {
+            self.print_statement();
+        } else if self.r#match(TokenType::For) {
+            self.for_statement();
+        } else if self.r#match(TokenType::If) {
+            self.if_statement();
+        } else if self.r#match(TokenType::Return) {
+            self.return_statement();
+        } else if self.r#match(TokenType::While) {
+            self.while_statement();
+        } else if self.r#match(TokenType::Break) {
+            self.break_statement();
+        } else if self.r#match(TokenType::Continue) {
+            self.continue_statement();
+        } else if self.r#match(TokenType::LeftBrace) {
+            self.begin_scope();
+            self.block();
+            self.end_scope();
+        } else {
+            self.expression_statement();
+        }
+    }
+
+    fn print_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::Semicolon, "Expect ';' after value.");
+        self.emit_op(OpCode::Print);
+    }
+
+    fn for_statement(&mut self) {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.");
+        if self.check(TokenType::Identifier) && self.check_next(TokenType::In) {
+            self.for_in_statement();
+            return;
+        }
+
+        self.begin_scope();
+        if self.r#match(TokenType::Semicolon) {
+            // No initializer.
+        } else if self.r#match(TokenType::Var) {
+            self.var_declaration();
+        } else {
+            self.expression_statement();
+        }
+
+        let mut loop_start = self.chunk().code.len();
+        let mut exit_jump = None;
+        if !self.r#match(TokenType::Semicolon) {
+            self.expression();
+            self.consume(TokenType::Semicolon, "Expect ';' after loop condition.");
+            exit_jump = Some(self.emit_jump(OpCode::JumpIfFalse));
+            self.emit_op(OpCode::Pop);
+        }
+
+        // The increment is compiled before the body but runs after it, so
+        // jump over it now and loop back to it from the end of the body.
+        if !self.r#match(TokenType::RightParen) {
+            let body_jump = self.emit_jump(OpCode::Jump);
+            let increment_start = self.chunk().code.len();
+            self.expression();
+            self.emit_op(OpCode::Pop);
+            self.consume(TokenType::RightParen, "Expect ')' after for clauses.");
+
+            self.emit_loop(loop_start);
+            loop_start = increment_start;
+            self.patch_jump(body_jump);
+        }
+
+        self.begin_loop(loop_start);
+        self.statement();
+        self.emit_loop(loop_start);
+
+        if let Some(exit_jump) = exit_jump {
+            self.patch_jump(exit_jump);
+            self.emit_op(OpCode::Pop);
+        }
+        self.end_loop();
+        self.end_scope();
+    }
+
+    /// `for (x in xs)`, once the `(` has been consumed. The iterator lives
+    /// in a hidden local for the whole loop, and each pass gets a fresh `x`
+    /// so closures capture the value from their own pass.
+    fn for_in_statement(&mut self) {
+        self.begin_scope();
+        self.consume(TokenType::Identifier, "Expect variable name.");
+        let variable = self.previous.source;
+        self.consume(TokenType::In, "Expect 'in' after variable name.");
+        // Iteration errors point at the `in`.
+        let line = self.previous.line;
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.");
+
+        self.emit_op_at(line, OpCode::Iter, &[]);
+        // A name no variable can have, so the body can't see it.
+        self.add_local("");
+        self.mark_initialized();
+        let iterator = (self.state().locals.len() - 1) as u8;
+        let done = self.identifier_constant("done");
+        let next = self.identifier_constant("next");
+
+        let loop_start = self.chunk().code.len();
+        self.emit_op_at(line, OpCode::GetLocal, &[iterator]);
+        self.emit_op_with_index(line, OpCode::Invoke, done, &[0]);
+        self.emit_op_at(line, OpCode::Not, &[]);
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+
+        self.begin_loop(loop_start);
+        self.begin_scope();
+        self.emit_op_at(line, OpCode::GetLocal, &[iterator]);
+        self.emit_op_with_index(line, OpCode::Invoke, next, &[0]);
+        self.add_local(variable);
+        self.mark_initialized();
+        self.statement();
+        self.end_scope();
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit_op(OpCode::Pop);
+        self.end_loop();
+        self.end_scope();
+    }
+
+    fn if_statement(&mut self) {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.");
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.");
+
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.statement();
+        let else_jump = self.emit_jump(OpCode::Jump);
+
+        self.patch_jump(then_jump);
+        self.emit_op(OpCode::Pop);
+        if self.r#match(TokenType::Else) {
+            self.statement();
+        }
+        self.patch_jump(else_jump);
+    }
+
+    fn return_statement(&mut self) {
+        let kind = self.functions.last().unwrap().kind;
+        if kind == FunctionKind::Script {
+            self.error("Can't return from top-level code.");
+        }
+
+        if self.r#match(TokenType::Semicolon) {
+            self.emit_return();
+        } else {
+            if kind == FunctionKind::Initializer {
+                self.error("Can't return a value from an initializer.");
+            }
+            self.expression();
+            self.consume(TokenType::Semicolon, "Expect ';' after return value.");
+            self.emit_op(OpCode::Return);
+        }
+    }
+
+    fn while_statement(&mut self) {
+        let loop_start = self.chunk().code.len();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.");
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after condition.");
+
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.begin_loop(loop_start);
+        self.statement();
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit_op(OpCode::Pop);
+        self.end_loop();
+    }
+
+    fn break_statement(&mut self) {
+        let keyword = self.previous.clone();
+        self.consume(TokenType::Semicolon, "Expect ';' after 'break'.");
+        if self.state().loops.is_empty() {
+            self.error_at(&keyword, "Can't use 'break' outside of a loop.");
+            return;
+        }
+        self.discard_loop_locals();
+        let jump = self.emit_jump(OpCode::Jump);
+        let state = self.functions.last_mut().unwrap();
+        state.loops.last_mut().unwrap().breaks.push(jump);
+    }
+
+    fn continue_statement(&mut self) {
+        let keyword = self.previous.clone();
+        self.consume(TokenType::Semicolon, "Expect ';' after 'continue'.");
+        let start = match self.state().loops.last() {
+            Some(innermost) => innermost.start,
+            None => {
+                self.error_at(&keyword, "Can't use 'continue' outside of a loop.");
+                return;
+            }
+        };
+        self.discard_loop_locals();
+        self.emit_loop(start);
+    }
+
+    /// Starts a loop's body, which `continue` leaves by jumping to `start`.
+    fn begin_loop(&mut self, start: usize) {
+        let state = self.functions.last_mut().unwrap();
+        let scope_depth = state.scope_depth;
+        state.loops.push(LoopState {
+            start,
+            scope_depth,
+            breaks: vec![],
+        });
+    }
+
+    /// Ends the innermost loop, sending its `break`s to the code after it.
+    fn end_loop(&mut self) {
+        let innermost = self.functions.last_mut().unwrap().loops.pop().unwrap();
+        for jump in innermost.breaks {
+            self.patch_jump(jump);
+        }
+    }
+
+    /// Emits code popping the locals of the innermost loop's body, for a jump
+    /// out of it. Unlike `end_scope`, the compiler keeps them, since the code
+    /// after the jump is still in their scope.
+    fn discard_loop_locals(&mut self) {
+        let state = self.state();
+        let scope_depth = state.loops.last().unwrap().scope_depth;
+        let ops: Vec<_> = state
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth.is_none_or(|depth| depth > scope_depth))
+            .map(|local| {
+                if local.is_captured {
+                    OpCode::CloseUpvalue
+                } else {
+                    OpCode::Pop
+                }
+            })
+            .collect();
+        for op in ops {
+            self.emit_op(op);
+        }
+    }
+
+    fn block(&mut self) {
+        while !self.check(TokenType::RightBrace) && !self.check(TokenType::Eof) {
+            self.declaration();
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after block.");
+    }
+
+    fn expression_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.");
+        self.emit_op(OpCode::Pop);
+    }
+
+    // Expressions
+
+    fn expression(&mut self) {
+        self.parse_precedence(Precedence::Assignment);
+    }
+
+    /// Parses an expression made of operators at `precedence` or tighter.
+    fn parse_precedence(&mut self, precedence: Precedence) {
+        let prefix = match Self::rule(self.current.r#type).prefix {
+            Some(prefix) => prefix,
+            None => {
+                self.error_at_current("Expect expression.");
+                return;
+            }
+        };
+        self.advance();
+
+        let can_assign = precedence <= Precedence::Assignment;
+        prefix(self, can_assign);
+
+        while precedence <= Self::rule(self.current.r#type).precedence {
+            self.advance();
+            let infix = Self::rule(self.previous.r#type)
+                .infix
+                .expect("tokens with a precedence have an infix rule");
+            infix(self, can_assign);
+        }
+
+        if can_assign && self.check(TokenType::Equal) {
+            self.error_at_current("Invalid assignment target.");
+            self.advance();
+        }
+    }
+
+    fn rule(r#type: TokenType) -> ParseRule<'src, 'h> {
+        let (prefix, infix, precedence): (
+            Option<ParseFn<'src, 'h>>,
+            Option<ParseFn<'src, 'h>>,
+            Precedence,
+        ) = match r#type {
+            TokenType::LeftParen => (Some(Self::grouping), Some(Self::call), Precedence::Call),
+            TokenType::Dot => (None, Some(Self::dot), Precedence::Call),
+            TokenType::LeftBracket => (Some(Self::list), Some(Self::subscript), Precedence::Call),
+            TokenType::LeftBrace => (Some(Self::map), None, Precedence::None),
+            TokenType::Minus => (Some(Self::unary), Some(Self::binary), Precedence::Term),
+            TokenType::Plus => (None, Some(Self::binary), Precedence::Term),
+            TokenType::Slash | TokenType::Star | TokenType::Percent => {
+                (None, Some(Self::binary), Precedence::Factor)
+            }
+            TokenType::Bang => (Some(Self::unary), None, Precedence::None),
+            TokenType::BangEqual | TokenType::EqualEqual => {
+                (None, Some(Self::binary), Precedence::Equality)
+            }
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => (None, Some(Self::binary), Precedence::Comparison),
+            TokenType::Identifier => (Some(Self::variable), None, Precedence::None),
+            TokenType::String => (Some(Self::string), None, Precedence::None),
+            TokenType::Number => (Some(Self::number), None, Precedence::None),
+            TokenType::And => (None, Some(Self::and), Precedence::And),
+            TokenType::Or => (None, Some(Self::or), Precedence::Or),
+            TokenType::False | TokenType::True | TokenType::Nil => {
+                (Some(Self::literal), None, Precedence::None)
+            }
+            TokenType::Super => (Some(Self::super_), None, Precedence::None),
+            TokenType::This => (Some(Self::this), None, Precedence::None),
+            _ => (None, None, Precedence::None),
+        };
+        ParseRule {
+            prefix,
+            infix,
+            precedence,
+        }
+    }
+
+    fn grouping(&mut self, _can_assign: bool) {
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after expression.");
+    }
+
+    fn number(&mut self, _can_assign: bool) {
+        if let Literal::Float(value) = self.previous.literal {
+            self.emit_constant(Value::Number(value));
+        }
+    }
+
+    fn string(&mut self, _can_assign: bool) {
+        if let Literal::String(symbol) = self.previous.literal {
+            let string = self.heap.intern(symbol.as_str());
+            self.emit_constant(Value::Obj(string));
+        }
+    }
+
+    fn literal(&mut self, _can_assign: bool) {
+        match self.previous.r#type {
+            TokenType::False => self.emit_op(OpCode::False),
+            TokenType::True => self.emit_op(OpCode::True),
+            TokenType::Nil => self.emit_op(OpCode::Nil),
+            _ => unreachable!("not a literal token"),
+        }
+    }
+
+    fn unary(&mut self, _can_assign: bool) {
+        let operator = self.previous.r#type;
+        let line = self.previous.line;
+        self.parse_precedence(Precedence::Unary);
+
+        match operator {
+            TokenType::Bang => self.emit_op_at(line, OpCode::Not, &[]),
+            TokenType::Minus => self.emit_op_at(line, OpCode::Negate, &[]),
+            _ => unreachable!("not a unary operator"),
+        }
+    }
+
+    fn binary(&mut self, _can_assign: bool) {
+        let operator = self.previous.r#type;
+        let line = self.previous.line;
+        // Binary operators are left-associative, so the right operand only
+        // takes operators that bind tighter.
+        self.parse_precedence(Self::rule(operator).precedence.next());
+
+        match operator {
+            TokenType::BangEqual => {
+                self.emit_op_at(line, OpCode::Equal, &[]);
+                self.emit_op_at(line, OpCode::Not, &[]);
+            }
+            TokenType::EqualEqual => self.emit_op_at(line, OpCode::Equal, &[]),
+            TokenType::Greater => self.emit_op_at(line, OpCode::Greater, &[]),
+            TokenType::GreaterEqual => {
+                self.emit_op_at(line, OpCode::Less, &[]);
+                self.emit_op_at(line, OpCode::Not, &[]);
+            }
+            TokenType::Less => self.emit_op_at(line, OpCode::Less, &[]),
+            TokenType::LessEqual => {
+                self.emit_op_at(line, OpCode::Greater, &[]);
+                self.emit_op_at(line, OpCode::Not, &[]);
+            }
+            TokenType::Plus => self.emit_op_at(line, OpCode::Add, &[]),
+            TokenType::Minus => self.emit_op_at(line, OpCode::Subtract, &[]),
+            TokenType::Star => self.emit_op_at(line, OpCode::Multiply, &[]),
+            TokenType::Slash => self.emit_op_at(line, OpCode::Divide, &[]),
+            TokenType::Percent => self.emit_op_at(line, OpCode::Modulo, &[]),
+            _ => unreachable!("not a binary operator"),
+        }
+    }
+
+    fn and(&mut self, _can_assign: bool) {
+        let end_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.parse_precedence(Precedence::And);
+        self.patch_jump(end_jump);
+    }
+
+    fn or(&mut self, _can_assign: bool) {
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+        let end_jump = self.emit_jump(OpCode::Jump);
+        self.patch_jump(else_jump);
+        self.emit_op(OpCode::Pop);
+        self.parse_precedence(Precedence::Or);
+        self.patch_jump(end_jump);
+    }
+
+    fn call(&mut self, _can_assign: bool) {
+        let arg_count = self.argument_list();
+        self.emit_op(OpCode::Call);
+        self.emit_byte(arg_count);
+    }
+
+    fn argument_list(&mut self) -> u8 {
+        let mut arg_count = 0;
+        if !self.check(TokenType::RightParen) {
+            loop {
+                self.expression();
+                if arg_count == MAX_ARGUMENTS {
+                    self.error(&format!(
+                        "Can't have more than {} arguments.",
+                        MAX_ARGUMENTS
+                    ));
+                }
+                arg_count += 1;
+                if !self.r#match(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after arguments.");
+        arg_count.min(MAX_ARGUMENTS) as u8
+    }
+
+    fn dot(&mut self, can_assign: bool) {
+        self.consume(TokenType::Identifier, "Expect property name after '.'.");
+        let line = self.previous.line;
+        let name = self.identifier_constant(self.previous.source);
+
+        if can_assign && self.r#match(TokenType::Equal) {
+            self.expression();
+            self.emit_op_with_index(line, OpCode::SetProperty, name, &[]);
+        } else if self.r#match(TokenType::LeftParen) {
+            // Calling a method straight away skips creating a bound method.
+            let arg_count = self.argument_list();
+            self.emit_op_with_index(line, OpCode::Invoke, name, &[arg_count]);
+        } else {
+            self.emit_op_with_index(line, OpCode::GetProperty, name, &[]);
+        }
+    }
+
+    /// A list literal. A trailing comma is allowed after the last element.
+    fn list(&mut self, _can_assign: bool) {
+        let mut count = 0;
+        while !self.check(TokenType::RightBracket) {
+            if count == MAX_ELEMENTS {
+                self.error_at_current(&format!(
+                    "Can't have more than {} elements in a list.",
+                    MAX_ELEMENTS
+                ));
+            }
+            self.expression();
+            count += 1;
+            if !self.r#match(TokenType::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBracket, "Expect ']' after list elements.");
+        self.emit_op(OpCode::BuildList);
+        self.emit_byte(count.min(MAX_ELEMENTS) as u8);
+    }
+
+    /// A map literal, which may also end with a trailing comma.
+    fn map(&mut self, _can_assign: bool) {
+        let mut count = 0;
+        while !self.check(TokenType::RightBrace) {
+            if count == MAX_ELEMENTS {
+                self.error_at_current(&format!(
+                    "Can't have more than {} entries in a map.",
+                    MAX_ELEMENTS
+                ));
+            }
+            self.expression();
+            self.consume(TokenType::Colon, "Expect ':' after map key.");
+            self.expression();
+            count += 1;
+            if !self.r#match(TokenType::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after map entries.");
+        self.emit_op(OpCode::BuildMap);
+        self.emit_byte(count.min(MAX_ELEMENTS) as u8);
+    }
+
+    fn subscript(&mut self, can_assign: bool) {
+        self.expression();
+        self.consume(TokenType::RightBracket, "Expect ']' after index.");
+        let line = self.previous.line;
+
+        if can_assign && self.r#match(TokenType::Equal) {
+            self.expression();
+            self.emit_op_at(line, OpCode::SetIndex, &[]);
+        } else {
+            self.emit_op_at(line, OpCode::GetIndex, &[]);
+        }
+    }
+
+    fn variable(&mut self, can_assign: bool) {
+        self.named_variable(self.previous.source, can_assign);
+    }
+
+    fn this(&mut self, _can_assign: bool) {
+        if self.classes.is_empty() {
+            self.error("Can't use 'this' outside of a class.");
+            return;
+        }
+        self.variable(false);
+    }
+
+    fn super_(&mut self, _can_assign: bool) {
+        match self.classes.last() {
+            None => self.error("Can't use 'super' outside of a class."),
+            Some(class) if !class.has_superclass => {
+                self.error("Can't use 'super' in a class with no superclass.")
+            }
+            Some(_) => {}
+        }
+
+        self.consume(TokenType::Dot, "Expect '.' after 'super'.");
+        self.consume(TokenType::Identifier, "Expect superclass method name.");
+        let line = self.previous.line;
+        let name = self.identifier_constant(self.previous.source);
+
+        self.named_variable("this", false);
+        if self.r#match(TokenType::LeftParen) {
+            let arg_count = self.argument_list();
+            self.named_variable("super", false);
+            self.emit_op_with_index(line, OpCode::SuperInvoke, name, &[arg_count]);
+        } else {
+            self.named_variable("super", false);
+            self.emit_op_with_index(line, OpCode::GetSuper, name, &[]);
+        }
+    }
+
+    // Variables
+
+    /// Emits a read of the variable `name`, or an assignment to it if one
+    /// follows and is allowed here.
+    fn named_variable(&mut self, name: &'src str, can_assign: bool) {
+        let line = self.previous.line;
+        let current = self.functions.len() - 1;
+        let (get_op, set_op, arg) = if let Some(slot) = self.resolve_local(current, name) {
+            (OpCode::GetLocal, OpCode::SetLocal, slot as usize)
+        } else if let Some(index) = self.resolve_upvalue(current, name) {
+            (OpCode::GetUpvalue, OpCode::SetUpvalue, index as usize)
+        } else {
+            let constant = self.identifier_constant(name);
+            (OpCode::GetGlobal, OpCode::SetGlobal, constant)
+        };
+
+        if can_assign && self.r#match(TokenType::Equal) {
+            self.expression();
+            self.emit_op_with_index(line, set_op, arg, &[]);
+        } else {
+            self.emit_op_with_index(line, get_op, arg, &[]);
+        }
+    }
+
+    /// Finds `name` among the locals of the function at `function`, returning
+    /// its slot.
+    fn resolve_local(&mut self, function: usize, name: &str) -> Option<u8> {
+        let locals = &self.functions[function].locals;
+        let (slot, local) = locals
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, local)| local.name == name)?;
+        if local.depth.is_none() {
+            self.error("Can't read local variable in its own initializer.");
+        }
+        Some(slot as u8)
+    }
+
+    /// Finds `name` in the functions enclosing the one at `function`, adding
+    /// upvalues along the way so each closure can pass it inwards.
+    fn resolve_upvalue(&mut self, function: usize, name: &str) -> Option<u8> {
+        if function == 0 {
+            return None;
+        }
+        let enclosing = function - 1;
+
+        if let Some(slot) = self.resolve_local(enclosing, name) {
+            self.functions[enclosing].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(function, slot, true));
+        }
+        if let Some(index) = self.resolve_upvalue(enclosing, name) {
+            return Some(self.add_upvalue(function, index, false));
+        }
+        None
+    }
+
+    fn add_upvalue(&mut self, function: usize, index: u8, is_local: bool) -> u8 {
+        let upvalue = UpvalueRef { index, is_local };
+        let upvalues = &self.functions[function].upvalues;
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return existing as u8;
+        }
+        if upvalues.len() == MAX_UPVALUES {
+            self.error("Too many closure variables in function.");
+            return 0;
+        }
+        let upvalues = &mut self.functions[function].upvalues;
+        upvalues.push(upvalue);
+        (upvalues.len() - 1) as u8
+    }
+
+    /// Consumes a variable name and declares it. Returns the constant index
+    /// of the name for globals, which are looked up by name at runtime.
+    fn parse_variable(&mut self, message: &str) -> usize {
+        self.consume(TokenType::Identifier, message);
+
+        self.declare_variable();
+        if self.state().scope_depth > 0 {
+            return 0;
+        }
+        self.identifier_constant(self.previous.source)
+    }
+
+    /// Adds the variable just named to the current scope, if it's local.
+    fn declare_variable(&mut self) {
+        let state = self.state();
+        if state.scope_depth == 0 {
+            return;
+        }
+
+        let name = self.previous.source;
+        let scope_depth = state.scope_depth;
+        let already_declared = state
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth.is_none_or(|depth| depth >= scope_depth))
+            .any(|local| local.name == name);
+        if already_declared {
+            self.error("Already a variable with this name in this scope.");
+        }
+
+        self.add_local(name);
+    }
+
+    fn add_local(&mut self, name: &'src str) {
+        if self.state().locals.len() == MAX_LOCALS {
+            self.error("Too many local variables in function.");
+            return;
+        }
+        self.functions.last_mut().unwrap().locals.push(Local {
+            name,
+            depth: None,
+            is_captured: false,
+        });
+    }
+
+    fn define_variable(&mut self, global: usize) {
+        if self.state().scope_depth > 0 {
+            self.mark_initialized();
+            return;
+        }
+        let line = self.previous.line;
+        self.emit_op_with_index(line, OpCode::DefineGlobal, global, &[]);
+    }
+
+    fn mark_initialized(&mut self) {
+        let state = self.functions.last_mut().unwrap();
+        if state.scope_depth == 0 {
+            return;
+        }
+        if let Some(local) = state.locals.last_mut() {
+            local.depth = Some(state.scope_depth);
+        }
+    }
+
+    fn identifier_constant(&mut self, name: &str) -> usize {
+        let string = self.heap.intern(name);
+        self.make_constant(Value::Obj(string))
+    }
+
+    fn begin_scope(&mut self) {
+        self.functions.last_mut().unwrap().scope_depth += 1;
+    }
+
+    /// Pops the locals of the scope being closed, moving any that closures
+    /// captured onto the heap.
+    fn end_scope(&mut self) {
+        let state = self.functions.last_mut().unwrap();
+        state.scope_depth -= 1;
+        let scope_depth = state.scope_depth;
+
+        let mut ops = vec![];
+        while let Some(local) = state.locals.last() {
+            if local.depth.is_none_or(|depth| depth <= scope_depth) {
+                break;
+            }
+            ops.push(if local.is_captured {
+                OpCode::CloseUpvalue
+            } else {
+                OpCode::Pop
+            });
+            state.locals.pop();
+        }
+        for op in ops {
+            self.emit_op(op);
+        }
+    }
+
+    /// Finishes the innermost function, returning it along with the
+    /// upvalues its closure needs to capture.
+    fn end_function(&mut self) -> (ObjRef, Vec<UpvalueRef>) {
+        self.emit_return();
+        let state = self.functions.pop().unwrap();
+        let function = Function {
+            arity: state.arity,
+            upvalue_count: state.upvalues.len(),
+            chunk: Rc::new(state.chunk),
+            name: state.name,
+        };
+        (self.heap.alloc(Obj::Function(function)), state.upvalues)
+    }
+
+    // Emitting code
+
+    fn state(&self) -> &FunctionState<'src> {
+        self.functions.last().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.functions.last_mut().unwrap().chunk
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let line = self.previous.line;
+        self.chunk().write(byte, line);
+    }
+
+    fn emit_op(&mut self, op: OpCode) {
+        self.emit_byte(op as u8);
+    }
+
+    /// Emits an instruction and its operands attributed to `line`, for
+    /// operators whose errors should point at the operator rather than the
+    /// end of its operands.
+    fn emit_op_at(&mut self, line: usize, op: OpCode, operands: &[u8]) {
+        self.chunk().write(op as u8, line);
+        for operand in operands {
+            self.chunk().write(*operand, line);
+        }
+    }
+
+    fn emit_return(&mut self) {
+        if self.state().kind == FunctionKind::Initializer {
+            self.emit_op(OpCode::GetLocal);
+            self.emit_byte(0);
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+        self.emit_op(OpCode::Return);
+    }
+
+    fn make_constant(&mut self, value: Value) -> usize {
+        let index = self.chunk().add_constant(value);
+        if index >= CONSTANTS_MAX {
+            self.error("Too many constants in one chunk.");
+            return 0;
+        }
+        index
+    }
+
+    fn emit_constant(&mut self, value: Value) {
+        let constant = self.make_constant(value);
+        let line = self.previous.line;
+        self.emit_op_with_index(line, OpCode::Constant, constant, &[]);
+    }
+
+    /// Emits `op` with a slot or constant index followed by `operands`. The
+    /// index takes one byte, unless it's a constant index past 255, which
+    /// switches to the wide form of the instruction. Slots always fit.
+    fn emit_op_with_index(&mut self, line: usize, op: OpCode, index: usize, operands: &[u8]) {
+        match op.long_form() {
+            Some(long_op) if index > u8::MAX as usize => {
+                let index = [(index >> 16) as u8, (index >> 8) as u8, index as u8];
+                self.emit_op_at(line, long_op, &[&index, operands].concat());
+            }
+            _ => self.emit_op_at(line, op, &[&[index as u8], operands].concat()),
+        }
+    }
+
+    /// Emits a jump with a placeholder offset, returning where the offset is
+    /// so it can be patched once the target is known.
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_op(op);
+        self.emit_byte(0xff);
+        self.emit_byte(0xff);
+        self.chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize) {
+        // Jump past the offset itself.
+        let jump = self.chunk().code.len() - offset - 2;
+        if jump > u16::MAX as usize {
+            self.error("Too much code to jump over.");
+        }
+        let code = &mut self.chunk().code;
+        code[offset] = ((jump >> 8) & 0xff) as u8;
+        code[offset + 1] = (jump & 0xff) as u8;
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) {
+        self.emit_op(OpCode::Loop);
+        let offset = self.chunk().code.len() - loop_start + 2;
+        if offset > u16::MAX as usize {
+            self.error("Loop body too large.");
+        }
+        self.emit_byte(((offset >> 8) & 0xff) as u8);
+        self.emit_byte((offset & 0xff) as u8);
+    }
+
+    // Tokens
+
+    fn advance(&mut self) {
+        let next = loop {
+            match self.lookahead.pop_front().or_else(|| self.scanner.next()) {
+                Some(Ok(token)) if token.r#type == TokenType::DocComment => {}
+                Some(Ok(token)) => break token,
+                Some(Err(error)) => {
+                    self.errors.push(CompileError::Lex(error));
+                    self.panic_mode = true;
+                }
+                // Stay on `Eof` once the scanner has run out.
+                None => break self.current.clone(),
+            }
+        };
+        self.previous = std::mem::replace(&mut self.current, next);
+    }
+
+    fn consume(&mut self, r#type: TokenType, message: &str) {
+        if self.current.r#type == r#type {
+            self.advance();
+            return;
+        }
+        self.error_at_current(message);
+    }
+
+    fn check(&self, r#type: TokenType) -> bool {
+        self.current.r#type == r#type
+    }
+
+    /// Like `check`, but for the token after the current one, scanning it
+    /// early. Any errors scanned along the way wait in `lookahead` too, to be
+    /// reported in order.
+    fn check_next(&mut self, r#type: TokenType) -> bool {
+        loop {
+            let next = self.lookahead.iter().find_map(|item| match item {
+                Ok(token) if token.r#type != TokenType::DocComment => Some(token.r#type),
+                _ => None,
+            });
+            if let Some(next) = next {
+                return next == r#type;
+            }
+            match self.scanner.next() {
+                Some(item) => self.lookahead.push_back(item),
+                None => return false,
+            }
+        }
+    }
+
+    fn r#match(&mut self, r#type: TokenType) -> bool {
+        if !self.check(r#type) {
+            return false;
+        }
+        self.advance();
+        true
+    }
+
+    /// Skips tokens until what looks like the start of the next statement.
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+
+        while self.current.r#type != TokenType::Eof {
+            if self.previous.r#type == TokenType::Semicolon {
+                return;
+            }
+            match self.current.r#type {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return
+                | TokenType::Break
+                | TokenType::Continue => return,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    fn error(&mut self, message: &str) {
+        let token = self.previous.clone();
+        self.error_at(&token, message);
+    }
+
+    fn error_at_current(&mut self, message: &str) {
+        let token = self.current.clone();
+        self.error_at(&token, message);
+    }
+
+    fn error_at(&mut self, token: &Token<'src>, message: &str) {
+        if self.panic_mode {
+            return;
+        }
+        self.panic_mode = true;
+        self.errors.push(CompileError::Syntax(ParseError {
+            message: String::from(message),
+            lexeme: String::from(token.source),
+            line: token.line,
+            column: token.start.column,
+            span: token.span,
+            at_end: token.r#type == TokenType::Eof,
+        }));
+    }
+}
+
+/// Stands in for `current` and `previous` before the first token is read.
+fn placeholder_token() -> Token<'static> {
+    Token {
+        r#type: TokenType::Eof,
+        source: "",
+        literal: Literal::Empty,
+        line: 1,
+        span: Span::default(),
+        start: Position::default(),
+        end: Position::default(),
+        leading_trivia: vec![],
+        trailing_trivia: vec![],
+    }
+}
@@ -0,0 +1,323 @@
+use std::fmt::Write;
+
+use crate::bytecode::chunk::{Chunk, OpCode};
+use crate::bytecode::heap::Heap;
+use crate::bytecode::object::ObjRef;
+use crate::bytecode::value::Value;
+
+/// Disassembles a compiled function followed by every function nested in
+/// it, each under a header with its name.
+pub fn disassemble_function(heap: &Heap, function: ObjRef) -> String {
+    let mut out = String::new();
+    let mut pending = vec![function];
+    while let Some(function) = pending.pop() {
+        let object = heap.function(function);
+        let name = match object.name {
+            Some(name) => heap.string(name),
+            None => "<script>",
+        };
+        out.push_str(&disassemble_chunk(&object.chunk, name, heap));
+
+        // Push in reverse so nested functions come out in source order.
+        for constant in object.chunk.constants.iter().rev() {
+            if let Value::Obj(reference) = constant {
+                if heap.is_function(*reference) {
+                    pending.push(*reference);
+                }
+            }
+        }
+    }
+    out
+}
+
+pub fn disassemble_chunk(chunk: &Chunk, name: &str, heap: &Heap) -> String {
+    let mut out = format!("== {} ==\n", name);
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = disassemble_instruction(chunk, offset, heap, &mut out);
+    }
+    out
+}
+
+/// Writes one line for the instruction at `offset`: the offset, its source
+/// line, the opcode and its decoded operands. Returns the offset of the next
+/// instruction.
+pub fn disassemble_instruction(
+    chunk: &Chunk,
+    offset: usize,
+    heap: &Heap,
+    out: &mut String,
+) -> usize {
+    let _ = write!(out, "{:04} ", offset);
+    if offset > 0 && chunk.line(offset) == chunk.line(offset - 1) {
+        out.push_str("   | ");
+    } else {
+        let _ = write!(out, "{:4} ", chunk.line(offset));
+    }
+
+    let op = match OpCode::try_from(chunk.code[offset]) {
+        Ok(op) => op,
+        Err(byte) => {
+            let _ = writeln!(out, "Unknown opcode {}", byte);
+            return offset + 1;
+        }
+    };
+    let name = format!("{:?}", op);
+
+    match op {
+        OpCode::Constant
+        | OpCode::ConstantLong
+        | OpCode::GetGlobal
+        | OpCode::GetGlobalLong
+        | OpCode::DefineGlobal
+        | OpCode::DefineGlobalLong
+        | OpCode::SetGlobal
+        | OpCode::SetGlobalLong
+        | OpCode::GetProperty
+        | OpCode::GetPropertyLong
+        | OpCode::SetProperty
+        | OpCode::SetPropertyLong
+        | OpCode::GetSuper
+        | OpCode::GetSuperLong
+        | OpCode::Class
+        | OpCode::ClassLong
+        | OpCode::Method
+        | OpCode::MethodLong => constant_instruction(&name, op, chunk, offset, heap, out),
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call
+        | OpCode::BuildList
+        | OpCode::BuildMap => byte_instruction(&name, chunk, offset, out),
+        OpCode::Jump | OpCode::JumpIfFalse => jump_instruction(&name, true, chunk, offset, out),
+        OpCode::Loop => jump_instruction(&name, false, chunk, offset, out),
+        OpCode::Invoke | OpCode::InvokeLong | OpCode::SuperInvoke | OpCode::SuperInvokeLong => {
+            invoke_instruction(&name, op, chunk, offset, heap, out)
+        }
+        OpCode::Closure | OpCode::ClosureLong => {
+            closure_instruction(&name, op, chunk, offset, heap, out)
+        }
+        OpCode::Nil
+        | OpCode::True
+        | OpCode::False
+        | OpCode::Pop
+        | OpCode::Equal
+        | OpCode::Greater
+        | OpCode::Less
+        | OpCode::Add
+        | OpCode::Subtract
+        | OpCode::Multiply
+        | OpCode::Divide
+        | OpCode::Modulo
+        | OpCode::Not
+        | OpCode::Negate
+        | OpCode::Print
+        | OpCode::CloseUpvalue
+        | OpCode::Return
+        | OpCode::Inherit
+        | OpCode::GetIndex
+        | OpCode::SetIndex
+        | OpCode::Iter => {
+            let _ = writeln!(out, "{}", name);
+            offset + 1
+        }
+    }
+}
+
+/// Formats the value stack from the bottom up, one slot per bracket.
+pub fn format_stack(stack: &[Value], heap: &Heap) -> String {
+    let mut out = String::from("          ");
+    for value in stack {
+        let _ = write!(out, "[ {} ]", value.display(heap));
+    }
+    out
+}
+
+/// Decodes the constant index operand of the `op` at `offset`, returning it
+/// along with the offset just past it.
+fn constant_operand(op: OpCode, chunk: &Chunk, offset: usize) -> (usize, usize) {
+    if op.is_long() {
+        let constant = (chunk.code[offset + 1] as usize) << 16
+            | (chunk.code[offset + 2] as usize) << 8
+            | chunk.code[offset + 3] as usize;
+        (constant, offset + 4)
+    } else {
+        (chunk.code[offset + 1] as usize, offset + 2)
+    }
+}
+
+fn constant_instruction(
+    name: &str,
+    op: OpCode,
+    chunk: &Chunk,
+    offset: usize,
+    heap: &Heap,
+    out: &mut String,
+) -> usize {
+    let (constant, next) = constant_operand(op, chunk, offset);
+    let value = chunk.constants[constant];
+    let _ = writeln!(out, "{:<16} {:4} '{}'", name, constant, value.display(heap));
+    next
+}
+
+fn byte_instruction(name: &str, chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    let slot = chunk.code[offset + 1];
+    let _ = writeln!(out, "{:<16} {:4}", name, slot);
+    offset + 2
+}
+
+fn jump_instruction(
+    name: &str,
+    forward: bool,
+    chunk: &Chunk,
+    offset: usize,
+    out: &mut String,
+) -> usize {
+    let jump = (chunk.code[offset + 1] as usize) << 8 | chunk.code[offset + 2] as usize;
+    let next = offset + 3;
+    let target = if forward { next + jump } else { next - jump };
+    let _ = writeln!(out, "{:<16} {:4} -> {}", name, offset, target);
+    next
+}
+
+fn invoke_instruction(
+    name: &str,
+    op: OpCode,
+    chunk: &Chunk,
+    offset: usize,
+    heap: &Heap,
+    out: &mut String,
+) -> usize {
+    let (constant, next) = constant_operand(op, chunk, offset);
+    let arg_count = chunk.code[next];
+    let value = chunk.constants[constant];
+    let _ = writeln!(
+        out,
+        "{:<16} ({} args) {:4} '{}'",
+        name,
+        arg_count,
+        constant,
+        value.display(heap)
+    );
+    next + 1
+}
+
+/// The function constant is followed by an `is_local`/index pair for each
+/// upvalue, shown one per line below the instruction.
+fn closure_instruction(
+    name: &str,
+    op: OpCode,
+    chunk: &Chunk,
+    offset: usize,
+    heap: &Heap,
+    out: &mut String,
+) -> usize {
+    let (constant, next) = constant_operand(op, chunk, offset);
+    let value = chunk.constants[constant];
+    let _ = writeln!(out, "{:<16} {:4} {}", name, constant, value.display(heap));
+
+    let upvalue_count = match value {
+        Value::Obj(function) => heap.function(function).upvalue_count,
+        _ => 0,
+    };
+    let mut offset = next;
+    for _ in 0..upvalue_count {
+        let is_local = chunk.code[offset];
+        let index = chunk.code[offset + 1];
+        let kind = if is_local == 1 { "local" } else { "upvalue" };
+        let _ = writeln!(
+            out,
+            "{:04}    |                     {} {}",
+            offset, kind, index
+        );
+        offset += 2;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prints_offsets_lines_and_operands() {
+        let heap = Heap::new();
+        let mut chunk = Chunk::new();
+        let constant = chunk.add_constant(Value::Number(1.5));
+        chunk.write(OpCode::Constant as u8, 1);
+        chunk.write(constant as u8, 1);
+        chunk.write(OpCode::Negate as u8, 1);
+        chunk.write(OpCode::GetLocal as u8, 2);
+        chunk.write(3, 2);
+        chunk.write(OpCode::Return as u8, 2);
+
+        assert_eq!(
+            disassemble_chunk(&chunk, "test", &heap),
+            "== test ==\n\
+             0000    1 Constant            0 '1.5'\n\
+             0002    | Negate\n\
+             0003    2 GetLocal            3\n\
+             0005    | Return\n"
+        );
+    }
+
+    #[test]
+    fn resolves_jump_targets() {
+        let heap = Heap::new();
+        let mut chunk = Chunk::new();
+        for byte in [OpCode::JumpIfFalse as u8, 0, 1, OpCode::Pop as u8] {
+            chunk.write(byte, 1);
+        }
+        for byte in [OpCode::Loop as u8, 0, 7] {
+            chunk.write(byte, 1);
+        }
+
+        let listing = disassemble_chunk(&chunk, "jumps", &heap);
+        assert!(listing.contains("0000    1 JumpIfFalse         0 -> 4\n"));
+        assert!(listing.contains("0004    | Loop                4 -> 0\n"));
+    }
+
+    #[test]
+    fn decodes_wide_constant_operands() {
+        let mut heap = Heap::new();
+        let mut chunk = Chunk::new();
+        for n in 0..300 {
+            chunk.add_constant(Value::Number(n as f64));
+        }
+        let name = Value::Obj(heap.intern("wide"));
+        let constant = chunk.add_constant(name);
+        chunk.write(OpCode::GetGlobalLong as u8, 1);
+        for byte in [
+            (constant >> 16) as u8,
+            (constant >> 8) as u8,
+            constant as u8,
+        ] {
+            chunk.write(byte, 1);
+        }
+        chunk.write(OpCode::Pop as u8, 1);
+
+        let mut out = String::new();
+        assert_eq!(disassemble_instruction(&chunk, 0, &heap, &mut out), 4);
+        assert_eq!(out, "0000    1 GetGlobalLong     300 'wide'\n");
+    }
+
+    #[test]
+    fn skips_unknown_opcodes() {
+        let heap = Heap::new();
+        let mut chunk = Chunk::new();
+        chunk.write(255, 4);
+        let mut out = String::new();
+        assert_eq!(disassemble_instruction(&chunk, 0, &heap, &mut out), 1);
+        assert_eq!(out, "0000    4 Unknown opcode 255\n");
+    }
+
+    #[test]
+    fn formats_the_stack_bottom_up() {
+        let mut heap = Heap::new();
+        let text = Value::Obj(heap.intern("top"));
+        let stack = [Value::Nil, Value::Number(2.0), text];
+        assert_eq!(format_stack(&stack, &heap), "          [ nil ][ 2 ][ top ]");
+        assert_eq!(format_stack(&[], &heap), "          ");
+    }
+}
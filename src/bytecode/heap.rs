@@ -0,0 +1,520 @@
+use std::fmt;
+use std::mem;
+
+use crate::bytecode::object::{Class, Closure, Function, LoxString, Map, Obj, ObjRef, Upvalue};
+use crate::bytecode::table::{hash_string, Table};
+use crate::bytecode::value::Value;
+
+/// Collect once this many bytes are allocated, before the heap has grown.
+const INITIAL_NEXT_GC: usize = 1024 * 1024;
+/// After a collection, the threshold is this multiple of what survived.
+const HEAP_GROW_FACTOR: usize = 2;
+
+#[derive(Debug)]
+struct Entry {
+    object: Obj,
+    marked: bool,
+    // The size counted when the object was allocated, subtracted again when
+    // it's freed.
+    size: usize,
+}
+
+/// Owns every object the VM and compiler create, and frees the unreachable
+/// ones with a mark-and-sweep collector.
+///
+/// The heap can't see the VM's roots, so it never collects on its own. The
+/// VM asks `should_collect` before allocating, marks its roots, and then
+/// calls `collect` to trace from them and sweep the rest.
+///
+/// Only the bytecode backend has a collector. The tree-walker shares its
+/// values through `Rc`, so cycles there, such as an instance with a field
+/// pointing back at itself, are never freed before the process exits.
+#[derive(Debug)]
+pub struct Heap {
+    objects: Vec<Option<Entry>>,
+    // Slots of freed objects, reused by later allocations.
+    free: Vec<u32>,
+    // Objects marked but whose references haven't been traced yet. Marked
+    // objects off this list have been traced.
+    gray: Vec<ObjRef>,
+    // Every live string, so each distinct string exists only once. Only the
+    // keys are used. The table holds them weakly: it isn't a root, and
+    // strings nothing else reaches are removed from it before being freed.
+    strings: Table,
+    bytes_allocated: usize,
+    next_gc: usize,
+    stress: bool,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Heap {
+            objects: vec![],
+            free: vec![],
+            gray: vec![],
+            strings: Table::new(),
+            bytes_allocated: 0,
+            next_gc: INITIAL_NEXT_GC,
+            stress: false,
+        }
+    }
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Heap::default()
+    }
+
+    /// Makes `should_collect` always true, so every allocation the VM makes
+    /// runs a full collection. Slow, but quick to expose missing roots.
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    pub fn alloc(&mut self, object: Obj) -> ObjRef {
+        let size = size_of(&object);
+        self.bytes_allocated += size;
+        let entry = Some(Entry {
+            object,
+            marked: false,
+            size,
+        });
+
+        match self.free.pop() {
+            Some(index) => {
+                self.objects[index as usize] = entry;
+                ObjRef(index)
+            }
+            None => {
+                self.objects.push(entry);
+                ObjRef((self.objects.len() - 1) as u32)
+            }
+        }
+    }
+
+    /// Returns the string object for `chars`, creating it only if an equal
+    /// string doesn't already exist.
+    pub fn intern(&mut self, chars: &str) -> ObjRef {
+        let hash = hash_string(chars);
+        let objects = &self.objects;
+        let existing = self
+            .strings
+            .find_string(hash, |key| match &objects[key.0 as usize] {
+                Some(Entry {
+                    object: Obj::String(string),
+                    ..
+                }) => &*string.chars == chars,
+                _ => false,
+            });
+        if let Some(existing) = existing {
+            return existing;
+        }
+
+        let string = self.alloc(Obj::String(LoxString {
+            chars: Box::from(chars),
+            hash,
+        }));
+        self.strings.set(string, hash, Value::Nil);
+        string
+    }
+
+    /// Every object that hasn't been freed.
+    pub fn objects(&self) -> impl Iterator<Item = &Obj> {
+        self.objects.iter().flatten().map(|entry| &entry.object)
+    }
+
+    pub fn get(&self, reference: ObjRef) -> &Obj {
+        match &self.objects[reference.0 as usize] {
+            Some(entry) => &entry.object,
+            None => unreachable!("use of freed object {:?}", reference),
+        }
+    }
+
+    pub fn get_mut(&mut self, reference: ObjRef) -> &mut Obj {
+        match &mut self.objects[reference.0 as usize] {
+            Some(entry) => &mut entry.object,
+            None => unreachable!("use of freed object {:?}", reference),
+        }
+    }
+
+    // Collection
+
+    pub fn should_collect(&self) -> bool {
+        self.stress || self.bytes_allocated > self.next_gc
+    }
+
+    /// Marks a value as reachable, if it's an object.
+    pub fn mark_value(&mut self, value: Value) {
+        if let Value::Obj(reference) = value {
+            self.mark_object(reference);
+        }
+    }
+
+    /// Marks an object as reachable and queues it to have its own references
+    /// traced. Objects already marked are skipped, which is what stops
+    /// cycles from looping forever.
+    pub fn mark_object(&mut self, reference: ObjRef) {
+        if let Some(entry) = &mut self.objects[reference.0 as usize] {
+            if entry.marked {
+                return;
+            }
+            entry.marked = true;
+            self.gray.push(reference);
+        }
+    }
+
+    /// Finishes a collection once the roots are marked: traces everything
+    /// reachable from them, frees the rest and sets the next threshold from
+    /// what survived.
+    pub fn collect(&mut self) {
+        self.trace_references();
+        let objects = &self.objects;
+        self.strings.retain(|key| {
+            objects[key.0 as usize]
+                .as_ref()
+                .is_some_and(|entry| entry.marked)
+        });
+        self.sweep();
+        self.next_gc = (self.bytes_allocated * HEAP_GROW_FACTOR).max(INITIAL_NEXT_GC);
+    }
+
+    fn trace_references(&mut self) {
+        let mut children = vec![];
+        while let Some(reference) = self.gray.pop() {
+            self.blacken(reference, &mut children);
+            for value in children.drain(..) {
+                self.mark_value(value);
+            }
+        }
+    }
+
+    /// Collects the values `reference` refers to into `out`.
+    fn blacken(&self, reference: ObjRef, out: &mut Vec<Value>) {
+        match self.get(reference) {
+            Obj::String(_) => {}
+            Obj::Function(function) => {
+                out.extend(&function.chunk.constants);
+                out.extend(function.name.map(Value::Obj));
+            }
+            Obj::Closure(closure) => {
+                out.push(Value::Obj(closure.function));
+                out.extend(closure.upvalues.iter().copied().map(Value::Obj));
+            }
+            Obj::Upvalue(Upvalue::Open(_)) => {}
+            Obj::Upvalue(Upvalue::Closed(value)) => out.push(*value),
+            Obj::Class(class) => {
+                out.push(Value::Obj(class.name));
+                for (name, _, method) in class.methods.entries() {
+                    out.extend([Value::Obj(name), method]);
+                }
+            }
+            Obj::Instance(instance) => {
+                out.push(Value::Obj(instance.class));
+                for (name, _, value) in instance.fields.entries() {
+                    out.extend([Value::Obj(name), value]);
+                }
+            }
+            Obj::BoundMethod(bound) => out.extend([bound.receiver, Value::Obj(bound.method)]),
+            Obj::Native(native) => out.push(Value::Obj(native.name)),
+            Obj::List(elements) => out.extend(elements),
+            Obj::BoundListMethod(bound) => out.push(Value::Obj(bound.list)),
+            Obj::Map(map) => {
+                for (key, value) in map.iter() {
+                    out.extend([Value::from_key(*key), *value]);
+                }
+            }
+            Obj::BoundMapMethod(bound) => out.push(Value::Obj(bound.map)),
+            Obj::Iteration(iteration) => {
+                out.push(Value::Obj(iteration.iterable));
+                // Deleted keys stay marked, so their strings can't be freed
+                // and reused for a new key while the loop is still running.
+                out.extend(iteration.keys.iter().map(|key| Value::from_key(*key)));
+            }
+        }
+    }
+
+    /// Frees every unmarked object and clears the marks on the rest, ready
+    /// for the next collection.
+    fn sweep(&mut self) {
+        for (index, slot) in self.objects.iter_mut().enumerate() {
+            match slot {
+                Some(entry) if entry.marked => entry.marked = false,
+                Some(entry) => {
+                    self.bytes_allocated -= entry.size;
+                    *slot = None;
+                    self.free.push(index as u32);
+                }
+                None => {}
+            }
+        }
+    }
+
+    // Typed accessors. Callers know what kind of object they hold, usually
+    // because the compiler put it there, so a mismatch is a VM bug.
+
+    /// The hash a string was interned with.
+    pub fn hash(&self, reference: ObjRef) -> u32 {
+        match self.get(reference) {
+            Obj::String(string) => string.hash,
+            object => unreachable!("expected a string, found {:?}", object),
+        }
+    }
+
+    pub fn string(&self, reference: ObjRef) -> &str {
+        match self.get(reference) {
+            Obj::String(string) => &string.chars,
+            object => unreachable!("expected a string, found {:?}", object),
+        }
+    }
+
+    pub fn function(&self, reference: ObjRef) -> &Function {
+        match self.get(reference) {
+            Obj::Function(function) => function,
+            object => unreachable!("expected a function, found {:?}", object),
+        }
+    }
+
+    pub fn is_function(&self, reference: ObjRef) -> bool {
+        matches!(self.get(reference), Obj::Function(_))
+    }
+
+    pub fn closure(&self, reference: ObjRef) -> &Closure {
+        match self.get(reference) {
+            Obj::Closure(closure) => closure,
+            object => unreachable!("expected a closure, found {:?}", object),
+        }
+    }
+
+    pub fn upvalue(&self, reference: ObjRef) -> &Upvalue {
+        match self.get(reference) {
+            Obj::Upvalue(upvalue) => upvalue,
+            object => unreachable!("expected an upvalue, found {:?}", object),
+        }
+    }
+
+    pub fn upvalue_mut(&mut self, reference: ObjRef) -> &mut Upvalue {
+        match self.get_mut(reference) {
+            Obj::Upvalue(upvalue) => upvalue,
+            object => unreachable!("expected an upvalue, found {:?}", object),
+        }
+    }
+
+    pub fn class(&self, reference: ObjRef) -> &Class {
+        match self.get(reference) {
+            Obj::Class(class) => class,
+            object => unreachable!("expected a class, found {:?}", object),
+        }
+    }
+
+    pub fn class_mut(&mut self, reference: ObjRef) -> &mut Class {
+        match self.get_mut(reference) {
+            Obj::Class(class) => class,
+            object => unreachable!("expected a class, found {:?}", object),
+        }
+    }
+
+    pub fn list(&self, reference: ObjRef) -> &Vec<Value> {
+        match self.get(reference) {
+            Obj::List(elements) => elements,
+            object => unreachable!("expected a list, found {:?}", object),
+        }
+    }
+
+    pub fn list_mut(&mut self, reference: ObjRef) -> &mut Vec<Value> {
+        match self.get_mut(reference) {
+            Obj::List(elements) => elements,
+            object => unreachable!("expected a list, found {:?}", object),
+        }
+    }
+
+    pub fn map(&self, reference: ObjRef) -> &Map {
+        match self.get(reference) {
+            Obj::Map(map) => map,
+            object => unreachable!("expected a map, found {:?}", object),
+        }
+    }
+
+    pub fn map_mut(&mut self, reference: ObjRef) -> &mut Map {
+        match self.get_mut(reference) {
+            Obj::Map(map) => map,
+            object => unreachable!("expected a map, found {:?}", object),
+        }
+    }
+
+    /// Writes an object the way `print` shows it.
+    pub fn fmt_object(&self, reference: ObjRef, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.get(reference) {
+            Obj::String(string) => write!(f, "{}", string.chars),
+            Obj::Function(function) => match function.name {
+                Some(name) => write!(f, "<fn {}>", self.string(name)),
+                None => write!(f, "<script>"),
+            },
+            Obj::Closure(closure) => self.fmt_object(closure.function, f),
+            Obj::Upvalue(_) => write!(f, "upvalue"),
+            Obj::Class(class) => write!(f, "{}", self.string(class.name)),
+            Obj::Instance(instance) => {
+                write!(
+                    f,
+                    "{} instance",
+                    self.string(self.class(instance.class).name)
+                )
+            }
+            Obj::BoundMethod(bound) => self.fmt_object(bound.method, f),
+            Obj::Native(_) | Obj::BoundListMethod(_) | Obj::BoundMapMethod(_) => {
+                write!(f, "<native fn>")
+            }
+            Obj::List(_) | Obj::Map(_) => self.fmt_nested(Value::Obj(reference), f, &mut vec![]),
+            Obj::Iteration(_) => write!(f, "<iterator>"),
+        }
+    }
+
+    /// Writes a value that may contain others. `open` holds the lists and
+    /// maps being written further out, so one that contains itself is shown
+    /// as `[...]` or `{...}` where it recurs, rather than forever.
+    fn fmt_nested(
+        &self,
+        value: Value,
+        f: &mut fmt::Formatter<'_>,
+        open: &mut Vec<ObjRef>,
+    ) -> fmt::Result {
+        let reference = match value {
+            Value::Obj(reference) if matches!(self.get(reference), Obj::List(_) | Obj::Map(_)) => {
+                reference
+            }
+            _ => return write!(f, "{}", value.display(self)),
+        };
+        let is_open = open.contains(&reference);
+        open.push(reference);
+        let result = match self.get(reference) {
+            Obj::List(_) if is_open => write!(f, "[...]"),
+            Obj::Map(_) if is_open => write!(f, "{{...}}"),
+            Obj::List(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.fmt_nested(*element, f, open)?;
+                }
+                write!(f, "]")
+            }
+            Obj::Map(map) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", Value::from_key(*key).display(self))?;
+                    self.fmt_nested(*value, f, open)?;
+                }
+                write!(f, "}}")
+            }
+            _ => unreachable!("checked above"),
+        };
+        open.pop();
+        result
+    }
+}
+
+/// Roughly how many bytes an object takes, counting what it owns as well as
+/// the object itself. Only used to pace collections, so it needn't be exact.
+fn size_of(object: &Obj) -> usize {
+    let owned = match object {
+        Obj::String(string) => string.chars.len(),
+        Obj::Function(function) => {
+            function.chunk.code.len() + function.chunk.constants.len() * mem::size_of::<Value>()
+        }
+        Obj::Closure(closure) => closure.upvalues.len() * mem::size_of::<ObjRef>(),
+        // Only counts the elements a list starts with, like the tables below.
+        Obj::List(elements) => elements.len() * mem::size_of::<Value>(),
+        Obj::Map(map) => map.len() * 2 * mem::size_of::<Value>(),
+        Obj::Iteration(iteration) => iteration.keys.len() * mem::size_of::<Value>(),
+        // Classes and instances start out with empty tables.
+        Obj::Class(_)
+        | Obj::Instance(_)
+        | Obj::Upvalue(_)
+        | Obj::BoundMethod(_)
+        | Obj::Native(_)
+        | Obj::BoundListMethod(_)
+        | Obj::BoundMapMethod(_) => 0,
+    };
+    mem::size_of::<Entry>() + owned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(heap: &Heap) -> Vec<&str> {
+        heap.objects()
+            .filter_map(|object| match object {
+                Obj::String(string) => Some(&*string.chars),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frees_what_isnt_marked_and_reuses_its_slot() {
+        let mut heap = Heap::new();
+        let kept = heap.intern("kept");
+        let dropped = heap.intern("dropped");
+        heap.mark_object(kept);
+        heap.collect();
+        assert_eq!(strings(&heap), ["kept"]);
+
+        let reused = heap.intern("new");
+        assert_eq!(reused, dropped);
+        assert_eq!(heap.string(kept), "kept");
+    }
+
+    #[test]
+    fn traces_through_marked_objects() {
+        let mut heap = Heap::new();
+        let name = heap.intern("name");
+        let list = heap.alloc(Obj::List(vec![Value::Obj(name)]));
+        heap.mark_object(list);
+        heap.collect();
+        assert_eq!(strings(&heap), ["name"]);
+
+        // Marks are cleared after each collection.
+        heap.collect();
+        assert_eq!(heap.objects().count(), 0);
+    }
+
+    #[test]
+    fn threshold_grows_with_what_survives() {
+        let mut heap = Heap::new();
+        assert!(!heap.should_collect());
+        let big = "x".repeat(INITIAL_NEXT_GC);
+        let kept = heap.intern(&big);
+        assert!(heap.should_collect());
+
+        heap.mark_object(kept);
+        heap.collect();
+        assert!(!heap.should_collect());
+        assert_eq!(heap.next_gc, heap.bytes_allocated * HEAP_GROW_FACTOR);
+
+        heap.collect();
+        assert_eq!(heap.bytes_allocated, 0);
+        assert_eq!(heap.next_gc, INITIAL_NEXT_GC);
+    }
+
+    #[test]
+    fn stress_mode_always_asks_to_collect() {
+        let mut heap = Heap::new();
+        heap.set_stress(true);
+        assert!(heap.should_collect());
+    }
+
+    #[test]
+    fn interning_returns_the_same_object_for_equal_strings() {
+        let mut heap = Heap::new();
+        let first = heap.intern("name");
+        let other = heap.intern("other");
+        assert_eq!(heap.intern("name"), first);
+        assert_ne!(first, other);
+        assert_eq!(heap.hash(first), hash_string("name"));
+        assert_eq!(strings(&heap), ["name", "other"]);
+    }
+}
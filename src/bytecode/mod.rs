@@ -0,0 +1,10 @@
+pub mod chunk;
+pub mod compiler;
+pub mod debug;
+pub mod heap;
+pub mod object;
+pub mod table;
+pub mod value;
+pub mod vm;
+
+pub use vm::{InterpretError, Vm};
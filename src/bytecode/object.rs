@@ -0,0 +1,138 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::bytecode::chunk::Chunk;
+use crate::bytecode::heap::Heap;
+use crate::bytecode::table::Table;
+use crate::bytecode::value::Value;
+use crate::interpreter::RuntimeError;
+use crate::stdlib::list::ListMethod;
+use crate::stdlib::map::{MapKey, MapMethod, OrderedMap};
+
+/// A handle to an object on the `Heap`. Copying it doesn't copy the object.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ObjRef(pub(super) u32);
+
+#[derive(Debug)]
+pub enum Obj {
+    String(LoxString),
+    Function(Function),
+    Closure(Closure),
+    Upvalue(Upvalue),
+    Class(Class),
+    Instance(Instance),
+    BoundMethod(BoundMethod),
+    Native(Native),
+    List(Vec<Value>),
+    BoundListMethod(BoundListMethod),
+    Map(Map),
+    BoundMapMethod(BoundMapMethod),
+    Iteration(Iteration),
+}
+
+#[derive(Debug)]
+pub struct LoxString {
+    pub chars: Box<str>,
+    pub hash: u32,
+}
+
+/// A compiled function. The chunk is shared with the call frames running it
+/// so the VM can read code without going through the heap.
+#[derive(Debug)]
+pub struct Function {
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Rc<Chunk>,
+    /// `None` for the top level script.
+    pub name: Option<ObjRef>,
+}
+
+/// A function together with the variables it captured from enclosing
+/// functions.
+#[derive(Debug)]
+pub struct Closure {
+    pub function: ObjRef,
+    pub upvalues: Vec<ObjRef>,
+}
+
+/// A captured variable. It points into the stack while the variable is in
+/// scope there, and holds the value itself once the variable goes out of
+/// scope.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: ObjRef,
+    // Method names mapped to their closures.
+    pub methods: Table,
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: ObjRef,
+    pub fields: Table,
+}
+
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: ObjRef,
+}
+
+/// A list method looked up as a property, as in `list.push`, to be called
+/// later.
+#[derive(Debug)]
+pub struct BoundListMethod {
+    pub list: ObjRef,
+    pub method: ListMethod,
+}
+
+/// Keys are interned, so string keys can be compared by identity here too.
+pub type Map = OrderedMap<MapKey<ObjRef>, Value>;
+
+#[derive(Debug)]
+pub struct BoundMapMethod {
+    pub map: ObjRef,
+    pub method: MapMethod,
+}
+
+/// How far a `for` loop has got through a list, map or string. Only the
+/// loop itself ever sees one. `position` is an index for lists, an index
+/// into `keys` for maps and a byte offset for strings.
+#[derive(Debug)]
+pub struct Iteration {
+    pub iterable: ObjRef,
+    pub position: usize,
+    /// A map's keys as they were when the loop started, so deleting the
+    /// current key doesn't skip the next one and adding keys can't keep the
+    /// loop going. Keys deleted before the loop reaches them are passed
+    /// over. Empty for lists and strings, which are read afresh.
+    pub keys: Vec<MapKey<ObjRef>>,
+}
+
+/// The Rust side of a native function. It's only called with as many
+/// arguments as the function's arity. It gets the heap to read and create
+/// strings, and mustn't hold on to objects that aren't reachable from the
+/// VM, since they can be collected once it returns.
+pub type NativeBody = Rc<dyn Fn(&mut Heap, &[Value]) -> Result<Value, RuntimeError>>;
+
+/// A function implemented in Rust, defined by the host with
+/// `Vm::define_native` or part of the standard library.
+pub struct Native {
+    pub name: ObjRef,
+    pub arity: usize,
+    pub function: NativeBody,
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Native")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,283 @@
+use crate::bytecode::object::ObjRef;
+use crate::bytecode::value::Value;
+
+/// Grow once this fraction of the entries are in use, counting tombstones.
+const MAX_LOAD: f64 = 0.75;
+const MIN_CAPACITY: usize = 8;
+
+/// Hashes a string with 32-bit FNV-1a. Strings store their hash when they
+/// are created, so it's only ever computed once per string.
+pub fn hash_string(chars: &str) -> u32 {
+    let mut hash: u32 = 2166136261;
+    for byte in chars.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(16777619);
+    }
+    hash
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Entry {
+    Empty,
+    // Left behind by a deletion so probe sequences running through the
+    // entry still reach the keys beyond it.
+    Tombstone,
+    Full {
+        key: ObjRef,
+        hash: u32,
+        value: Value,
+    },
+}
+
+/// Hash table keyed by interned strings, using open addressing with linear
+/// probing.
+///
+/// Because every string is interned, two keys are the same string exactly
+/// when their `ObjRef`s are equal, so lookups never compare characters. The
+/// caller passes each key's hash along with it, which it has from the
+/// string object.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    entries: Vec<Entry>,
+    // Full entries plus tombstones, since both lengthen probe sequences.
+    used: usize,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Table::default()
+    }
+
+    pub fn get(&self, key: ObjRef, hash: u32) -> Option<Value> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        match self.entries[self.find(key, hash)] {
+            Entry::Full { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Sets the value for `key`, returning whether the key is new.
+    pub fn set(&mut self, key: ObjRef, hash: u32, value: Value) -> bool {
+        if (self.used + 1) as f64 > self.entries.len() as f64 * MAX_LOAD {
+            self.grow();
+        }
+
+        let index = self.find(key, hash);
+        let entry = &mut self.entries[index];
+        let is_new = !matches!(entry, Entry::Full { .. });
+        // Reusing a tombstone doesn't change how many entries are used.
+        if matches!(entry, Entry::Empty) {
+            self.used += 1;
+        }
+        *entry = Entry::Full { key, hash, value };
+        is_new
+    }
+
+    /// Removes `key`, returning whether it was there.
+    pub fn delete(&mut self, key: ObjRef, hash: u32) -> bool {
+        if self.entries.is_empty() {
+            return false;
+        }
+        let index = self.find(key, hash);
+        if !matches!(self.entries[index], Entry::Full { .. }) {
+            return false;
+        }
+        self.entries[index] = Entry::Tombstone;
+        true
+    }
+
+    /// Copies every entry of `from` into this table, replacing values of
+    /// keys already here.
+    pub fn add_all(&mut self, from: &Table) {
+        for (key, hash, value) in from.entries() {
+            self.set(key, hash, value);
+        }
+    }
+
+    /// Looks for a key by contents rather than identity. This is how strings
+    /// get interned in the first place, so it's the one lookup that can't
+    /// rely on keys being unique.
+    pub fn find_string(&self, hash: u32, matches: impl Fn(ObjRef) -> bool) -> Option<ObjRef> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let mask = self.entries.len() - 1;
+        let mut index = hash as usize & mask;
+        loop {
+            match self.entries[index] {
+                Entry::Empty => return None,
+                Entry::Full {
+                    key, hash: found, ..
+                } if found == hash && matches(key) => return Some(key),
+                _ => {}
+            }
+            index = (index + 1) & mask;
+        }
+    }
+
+    /// Deletes every entry whose key `keep` rejects.
+    pub fn retain(&mut self, mut keep: impl FnMut(ObjRef) -> bool) {
+        for entry in &mut self.entries {
+            if let Entry::Full { key, .. } = entry {
+                if !keep(*key) {
+                    *entry = Entry::Tombstone;
+                }
+            }
+        }
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (ObjRef, u32, Value)> + '_ {
+        self.entries.iter().filter_map(|entry| match *entry {
+            Entry::Full { key, hash, value } => Some((key, hash, value)),
+            _ => None,
+        })
+    }
+
+    /// Finds the entry holding `key`, or the one it should go in: the first
+    /// tombstone passed on the way, or else the empty entry that ended the
+    /// probe.
+    fn find(&self, key: ObjRef, hash: u32) -> usize {
+        let mask = self.entries.len() - 1;
+        let mut index = hash as usize & mask;
+        let mut tombstone = None;
+        loop {
+            match self.entries[index] {
+                Entry::Empty => return tombstone.unwrap_or(index),
+                Entry::Tombstone => {
+                    tombstone.get_or_insert(index);
+                }
+                Entry::Full { key: found, .. } if found == key => return index,
+                Entry::Full { .. } => {}
+            }
+            index = (index + 1) & mask;
+        }
+    }
+
+    /// Doubles the capacity, dropping tombstones as the entries are
+    /// reinserted.
+    fn grow(&mut self) {
+        let capacity = (self.entries.len() * 2).max(MIN_CAPACITY);
+        let old = std::mem::replace(&mut self.entries, vec![Entry::Empty; capacity]);
+        self.used = 0;
+        for entry in old {
+            if let Entry::Full { key, hash, value } = entry {
+                let index = self.find(key, hash);
+                self.entries[index] = Entry::Full { key, hash, value };
+                self.used += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The table never looks inside keys, so any handle will do.
+    fn key(index: u32) -> ObjRef {
+        ObjRef(index)
+    }
+
+    #[test]
+    fn set_get_and_delete() {
+        let mut table = Table::new();
+        assert_eq!(table.get(key(1), 1), None);
+        assert!(!table.delete(key(1), 1));
+
+        assert!(table.set(key(1), 1, Value::Number(1.0)));
+        assert!(!table.set(key(1), 1, Value::Number(2.0)));
+        assert_eq!(table.get(key(1), 1), Some(Value::Number(2.0)));
+
+        assert!(table.delete(key(1), 1));
+        assert_eq!(table.get(key(1), 1), None);
+        assert!(!table.delete(key(1), 1));
+    }
+
+    #[test]
+    fn probes_past_tombstones_and_reuses_them() {
+        // Keys with the same hash share a probe sequence.
+        let mut table = Table::new();
+        table.set(key(1), 7, Value::Number(1.0));
+        table.set(key(2), 7, Value::Number(2.0));
+        let used = table.used;
+
+        assert!(table.delete(key(1), 7));
+        assert_eq!(table.get(key(2), 7), Some(Value::Number(2.0)));
+
+        assert!(table.set(key(3), 7, Value::Number(3.0)));
+        assert_eq!(table.used, used);
+        assert!(matches!(table.entries[7], Entry::Full { key: found, .. } if found == key(3)));
+        assert_eq!(table.entries().count(), 2);
+    }
+
+    #[test]
+    fn grows_and_keeps_every_entry() {
+        let mut table = Table::new();
+        for i in 0..1000 {
+            table.set(key(i), hash_string(&i.to_string()), Value::Number(i as f64));
+        }
+        assert!(table.entries.len().is_power_of_two());
+        assert!(table.used as f64 <= table.entries.len() as f64 * MAX_LOAD);
+        for i in 0..1000 {
+            let found = table.get(key(i), hash_string(&i.to_string()));
+            assert_eq!(found, Some(Value::Number(i as f64)));
+        }
+    }
+
+    #[test]
+    fn growing_drops_tombstones() {
+        let mut table = Table::new();
+        for i in 0..6 {
+            table.set(key(i), i, Value::Nil);
+        }
+        for i in 0..5 {
+            table.delete(key(i), i);
+        }
+        assert_eq!(table.used, 6);
+        // Going over the load factor rebuilds the table without them.
+        for i in 6..8 {
+            table.set(key(i), i, Value::Nil);
+        }
+        assert_eq!(table.used, 3);
+        assert_eq!(table.entries().count(), 3);
+    }
+
+    #[test]
+    fn hashes_with_fnv_1a() {
+        assert_eq!(hash_string(""), 0x811c9dc5);
+        assert_eq!(hash_string("a"), 0xe40c292c);
+        assert_eq!(hash_string("foobar"), 0xbf9cf968);
+    }
+
+    #[test]
+    fn finds_strings_by_contents_and_hash() {
+        let mut table = Table::new();
+        assert_eq!(table.find_string(5, |_| true), None);
+        table.set(key(1), 5, Value::Nil);
+        table.set(key(2), 5, Value::Nil);
+        table.set(key(3), 6, Value::Nil);
+
+        assert_eq!(table.find_string(5, |found| found == key(2)), Some(key(2)));
+        // Only keys with the same hash are ever compared.
+        assert_eq!(table.find_string(5, |found| found == key(3)), None);
+        assert_eq!(table.find_string(7, |_| true), None);
+
+        // A tombstone doesn't end the probe sequence.
+        table.delete(key(1), 5);
+        assert_eq!(table.find_string(5, |found| found == key(2)), Some(key(2)));
+    }
+
+    #[test]
+    fn retain_removes_rejected_keys() {
+        let mut table = Table::new();
+        for i in 0..4 {
+            table.set(key(i), 3, Value::Nil);
+        }
+        table.retain(|found| found.0 % 2 == 0);
+        assert_eq!(table.get(key(1), 3), None);
+        assert_eq!(table.get(key(2), 3), Some(Value::Nil));
+        assert_eq!(table.entries().count(), 2);
+    }
+}
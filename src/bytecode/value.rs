@@ -0,0 +1,114 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::bytecode::heap::Heap;
+use crate::bytecode::object::{Obj, ObjRef};
+use crate::interpreter::RuntimeError;
+use crate::stdlib::map::{self, MapKey};
+use crate::stdlib::Primitive;
+
+/// A value in the bytecode VM. It's small and `Copy`, anything bigger lives
+/// on the heap behind an `ObjRef`. Objects compare by identity, which is
+/// Lox equality for strings too since they're all interned.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Obj(ObjRef),
+}
+
+impl Value {
+    /// `nil` and `false` are falsey, everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    /// Converts the value to pass it to the standard library.
+    pub fn to_primitive(self, heap: &Heap) -> Primitive {
+        match self {
+            Value::Nil => Primitive::Nil,
+            Value::Bool(value) => Primitive::Bool(value),
+            Value::Number(value) => Primitive::Number(value),
+            Value::Obj(reference) => match heap.get(reference) {
+                Obj::String(string) => Primitive::String(Rc::from(&*string.chars)),
+                // Nested lists are passed on as they'd print, which stays
+                // finite for lists that contain themselves.
+                Obj::List(elements) => Primitive::List(
+                    elements
+                        .iter()
+                        .map(|element| match element {
+                            Value::Obj(inner) if matches!(heap.get(*inner), Obj::List(_)) => {
+                                Primitive::Other(Rc::from(element.display(heap).to_string()))
+                            }
+                            other => other.to_primitive(heap),
+                        })
+                        .collect(),
+                ),
+                _ => Primitive::Other(Rc::from(self.display(heap).to_string())),
+            },
+        }
+    }
+
+    /// Converts a result from the standard library, interning any string.
+    /// This never collects garbage, so the new objects are safe until the
+    /// VM next allocates.
+    pub fn from_primitive(primitive: Primitive, heap: &mut Heap) -> Value {
+        match primitive {
+            Primitive::Nil => Value::Nil,
+            Primitive::Bool(value) => Value::Bool(value),
+            Primitive::Number(value) => Value::Number(value),
+            Primitive::String(chars) | Primitive::Other(chars) => Value::Obj(heap.intern(&chars)),
+            Primitive::List(elements) => {
+                let elements = elements
+                    .into_iter()
+                    .map(|element| Value::from_primitive(element, heap))
+                    .collect();
+                Value::Obj(heap.alloc(Obj::List(elements)))
+            }
+        }
+    }
+
+    /// The value as a map key, failing if it can't be one.
+    pub fn to_key(self, heap: &Heap) -> Result<MapKey<ObjRef>, RuntimeError> {
+        match self {
+            Value::Nil => Ok(MapKey::Nil),
+            Value::Bool(value) => Ok(MapKey::Bool(value)),
+            Value::Number(value) => MapKey::number(value),
+            Value::Obj(reference) => match heap.get(reference) {
+                Obj::String(_) => Ok(MapKey::String(reference)),
+                _ => Err(map::key_error()),
+            },
+        }
+    }
+
+    pub fn from_key(key: MapKey<ObjRef>) -> Value {
+        match key {
+            MapKey::Nil => Value::Nil,
+            MapKey::Bool(value) => Value::Bool(value),
+            MapKey::Number(bits) => Value::Number(f64::from_bits(bits)),
+            MapKey::String(reference) => Value::Obj(reference),
+        }
+    }
+
+    /// Displays the value the way `print` shows it.
+    pub fn display<'h>(&self, heap: &'h Heap) -> ValueDisplay<'h> {
+        ValueDisplay { value: *self, heap }
+    }
+}
+
+pub struct ValueDisplay<'h> {
+    value: Value,
+    heap: &'h Heap,
+}
+
+impl fmt::Display for ValueDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::Obj(reference) => self.heap.fmt_object(reference, f),
+        }
+    }
+}
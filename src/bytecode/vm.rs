@@ -0,0 +1,1366 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::bytecode::chunk::{Chunk, OpCode};
+use crate::bytecode::compiler::{compile, CompileError};
+use crate::bytecode::debug;
+use crate::bytecode::heap::Heap;
+use crate::bytecode::object::{
+    BoundListMethod, BoundMapMethod, BoundMethod, Class, Closure, Instance, Iteration, Map, Native,
+    NativeBody, Obj, ObjRef, Upvalue,
+};
+use crate::bytecode::table::Table;
+use crate::bytecode::value::Value;
+use crate::interpreter::RuntimeError;
+use crate::limits::FRAMES_MAX;
+use crate::native;
+use crate::stdlib;
+use crate::stdlib::list::{self, ListMethod};
+use crate::stdlib::map::{self, MapKey, MapMethod};
+
+#[derive(Debug)]
+pub enum InterpretError {
+    Compile(Vec<CompileError>),
+    Runtime(RuntimeError),
+}
+
+impl fmt::Display for InterpretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterpretError::Compile(errors) => {
+                let lines: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            InterpretError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// A function call in progress.
+#[derive(Debug)]
+struct CallFrame {
+    closure: ObjRef,
+    chunk: Rc<Chunk>,
+    ip: usize,
+    // Index of the frame's first stack slot, which holds the callee.
+    slots: usize,
+}
+
+/// Stack-based virtual machine running the output of the bytecode compiler.
+/// Globals and the heap persist between calls to `interpret`, so the prompt
+/// can build on earlier lines.
+#[derive(Debug)]
+pub struct Vm {
+    heap: Heap,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: Table,
+    // Upvalues still pointing into the stack, ordered by slot.
+    open_upvalues: Vec<ObjRef>,
+    // Interned once so looking up initializers needn't intern it each call.
+    init_string: ObjRef,
+    // Likewise for the method `for` loops call on instances.
+    iter_string: ObjRef,
+    print_code: bool,
+    trace_execution: bool,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        let mut heap = Heap::new();
+        let init_string = heap.intern("init");
+        let iter_string = heap.intern("iter");
+        let mut vm = Vm {
+            heap,
+            stack: vec![],
+            frames: vec![],
+            globals: Table::new(),
+            open_upvalues: vec![],
+            init_string,
+            iter_string,
+            print_code: false,
+            trace_execution: false,
+        };
+        vm.define_native("clock", 0, |_| Ok(Value::Number(native::clock())));
+        for (name, value) in stdlib::constants() {
+            let name = vm.heap.intern(name);
+            vm.globals
+                .set(name, vm.heap.hash(name), Value::Number(*value));
+        }
+        for library_fn in stdlib::functions() {
+            let function = library_fn.function;
+            let body = move |heap: &mut Heap, args: &[Value]| {
+                let args: Vec<_> = args.iter().map(|arg| arg.to_primitive(heap)).collect();
+                function(&args).map(|result| Value::from_primitive(result, heap))
+            };
+            vm.define_native_body(library_fn.name, library_fn.arity, Rc::new(body));
+        }
+        vm
+    }
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Vm::default()
+    }
+
+    /// Disassembles each script to stderr after compiling it.
+    pub fn print_code(mut self) -> Self {
+        self.print_code = true;
+        self
+    }
+
+    /// Prints the value stack and each instruction to stderr as it runs.
+    pub fn trace_execution(mut self) -> Self {
+        self.trace_execution = true;
+        self
+    }
+
+    /// Collects garbage before every allocation, to flush out objects the
+    /// VM uses without keeping them reachable.
+    pub fn stress_gc(mut self) -> Self {
+        self.heap.set_stress(true);
+        self
+    }
+
+    /// Defines a global function implemented in Rust. Natives defined before
+    /// `interpret` runs a script are visible to it, and redefining a name
+    /// replaces the old function.
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value, RuntimeError> + 'static,
+    ) {
+        let body = move |_: &mut Heap, args: &[Value]| function(args);
+        self.define_native_body(name, arity, Rc::new(body));
+    }
+
+    fn define_native_body(&mut self, name: &str, arity: usize, function: NativeBody) {
+        // Both objects go on the stack so a collection can't free them
+        // before they're stored in the globals.
+        let name = self.intern(name);
+        self.push(Value::Obj(name));
+        let native = self.alloc(Obj::Native(Native {
+            name,
+            arity,
+            function,
+        }));
+        self.push(Value::Obj(native));
+        self.globals
+            .set(name, self.heap.hash(name), Value::Obj(native));
+        self.pop();
+        self.pop();
+    }
+
+    pub fn interpret(&mut self, source: &str) -> Result<(), InterpretError> {
+        let function = compile(source, &mut self.heap).map_err(InterpretError::Compile)?;
+        if self.print_code {
+            eprint!("{}", debug::disassemble_function(&self.heap, function));
+        }
+        // Keep the function reachable while its closure is allocated.
+        self.push(Value::Obj(function));
+        let closure = self.alloc(Obj::Closure(Closure {
+            function,
+            upvalues: vec![],
+        }));
+        self.pop();
+        self.push(Value::Obj(closure));
+
+        let result = self.call(closure, 0).and_then(|_| self.run(0));
+        match result {
+            // Drop what the script returned.
+            Ok(()) => {
+                self.pop();
+            }
+            Err(_) => {
+                self.stack.clear();
+                self.frames.clear();
+                self.open_upvalues.clear();
+            }
+        }
+        result.map_err(InterpretError::Runtime)
+    }
+
+    /// Runs until the frame count drops back to `depth`, leaving the value
+    /// the last frame returned on the stack. The script runs down to zero;
+    /// natives that call back into Lox code run nested loops on top of that.
+    fn run(&mut self, depth: usize) -> Result<(), RuntimeError> {
+        loop {
+            if self.trace_execution {
+                self.trace_instruction();
+            }
+
+            let byte = self.read_byte();
+            let op = OpCode::try_from(byte)
+                .unwrap_or_else(|byte| unreachable!("invalid opcode {}", byte));
+
+            match op {
+                OpCode::Constant | OpCode::ConstantLong => {
+                    let constant = self.read_constant(op);
+                    self.push(constant);
+                }
+                OpCode::Nil => self.push(Value::Nil),
+                OpCode::True => self.push(Value::Bool(true)),
+                OpCode::False => self.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let value = self.stack[self.frame().slots + slot];
+                    self.push(value);
+                }
+                OpCode::SetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let base = self.frame().slots;
+                    self.stack[base + slot] = self.peek(0);
+                }
+                OpCode::GetGlobal | OpCode::GetGlobalLong => {
+                    let name = self.read_string(op);
+                    match self.globals.get(name, self.heap.hash(name)) {
+                        Some(value) => self.push(value),
+                        None => return Err(self.undefined_variable(name)),
+                    }
+                }
+                OpCode::DefineGlobal | OpCode::DefineGlobalLong => {
+                    let name = self.read_string(op);
+                    let value = self.pop();
+                    self.globals.set(name, self.heap.hash(name), value);
+                }
+                OpCode::SetGlobal | OpCode::SetGlobalLong => {
+                    let name = self.read_string(op);
+                    let value = self.peek(0);
+                    let hash = self.heap.hash(name);
+                    // Assignment can't create a global, so undo the set if
+                    // it added one.
+                    if self.globals.set(name, hash, value) {
+                        self.globals.delete(name, hash);
+                        return Err(self.undefined_variable(name));
+                    }
+                }
+                OpCode::GetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = self.heap.closure(self.frame().closure).upvalues[index];
+                    let value = match self.heap.upvalue(upvalue) {
+                        Upvalue::Open(slot) => self.stack[*slot],
+                        Upvalue::Closed(value) => *value,
+                    };
+                    self.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = self.heap.closure(self.frame().closure).upvalues[index];
+                    let value = self.peek(0);
+                    match self.heap.upvalue_mut(upvalue) {
+                        Upvalue::Open(slot) => {
+                            let slot = *slot;
+                            self.stack[slot] = value;
+                        }
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                OpCode::GetProperty | OpCode::GetPropertyLong => {
+                    let name = self.read_string(op);
+                    if let Some(list) = self.as_list(self.peek(0)) {
+                        self.bind_list_method(list, name)?;
+                        continue;
+                    }
+                    if let Some(map) = self.as_map(self.peek(0)) {
+                        self.bind_map_method(map, name)?;
+                        continue;
+                    }
+                    let instance = match self.as_instance(self.peek(0)) {
+                        Some(instance) => instance,
+                        None => return Err(self.error("Only instances have properties.")),
+                    };
+                    let field = self
+                        .instance(instance)
+                        .fields
+                        .get(name, self.heap.hash(name));
+                    if let Some(value) = field {
+                        self.pop();
+                        self.push(value);
+                    } else {
+                        let class = self.instance(instance).class;
+                        self.bind_method(class, name)?;
+                    }
+                }
+                OpCode::SetProperty | OpCode::SetPropertyLong => {
+                    let name = self.read_string(op);
+                    let instance = match self.as_instance(self.peek(1)) {
+                        Some(instance) => instance,
+                        None => return Err(self.error("Only instances have fields.")),
+                    };
+                    let value = self.pop();
+                    let hash = self.heap.hash(name);
+                    self.instance_mut(instance).fields.set(name, hash, value);
+                    self.pop();
+                    self.push(value);
+                }
+                OpCode::GetSuper | OpCode::GetSuperLong => {
+                    let name = self.read_string(op);
+                    let superclass = self.pop_obj();
+                    self.bind_method(superclass, name)?;
+                }
+                OpCode::Equal => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.push(Value::Bool(a == b));
+                }
+                OpCode::Greater => self.comparison(|a, b| a > b)?,
+                OpCode::Less => self.comparison(|a, b| a < b)?,
+                OpCode::Add => self.add()?,
+                OpCode::Subtract => self.arithmetic(|a, b| a - b)?,
+                OpCode::Multiply => self.arithmetic(|a, b| a * b)?,
+                OpCode::Divide => self.arithmetic(|a, b| a / b)?,
+                OpCode::Modulo => {
+                    let (a, b) = self.number_operands()?;
+                    let remainder = stdlib::modulo(a, b).map_err(|message| self.error(message))?;
+                    self.push(Value::Number(remainder));
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::Bool(!value.is_truthy()));
+                }
+                OpCode::Negate => match self.peek(0) {
+                    Value::Number(value) => {
+                        self.pop();
+                        self.push(Value::Number(-value));
+                    }
+                    _ => return Err(self.error("Operand must be a number.")),
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    println!("{}", value.display(&self.heap));
+                }
+                OpCode::Jump => {
+                    let offset = self.read_short();
+                    self.frame_mut().ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_short();
+                    if !self.peek(0).is_truthy() {
+                        self.frame_mut().ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = self.read_short();
+                    self.frame_mut().ip -= offset;
+                }
+                OpCode::Call => {
+                    let arg_count = self.read_byte() as usize;
+                    self.call_value(self.peek(arg_count), arg_count)?;
+                }
+                OpCode::Invoke | OpCode::InvokeLong => {
+                    let name = self.read_string(op);
+                    let arg_count = self.read_byte() as usize;
+                    self.invoke(name, arg_count)?;
+                }
+                OpCode::SuperInvoke | OpCode::SuperInvokeLong => {
+                    let name = self.read_string(op);
+                    let arg_count = self.read_byte() as usize;
+                    let superclass = self.pop_obj();
+                    self.invoke_from_class(superclass, name, arg_count)?;
+                }
+                OpCode::Closure | OpCode::ClosureLong => {
+                    let function = match self.read_constant(op) {
+                        Value::Obj(function) => function,
+                        value => unreachable!("expected a function, found {:?}", value),
+                    };
+                    let upvalue_count = self.heap.function(function).upvalue_count;
+                    let mut upvalues = Vec::with_capacity(upvalue_count);
+                    for _ in 0..upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        upvalues.push(if is_local {
+                            let slot = self.frame().slots + index;
+                            self.capture_upvalue(slot)
+                        } else {
+                            self.heap.closure(self.frame().closure).upvalues[index]
+                        });
+                    }
+                    let closure = self.alloc(Obj::Closure(Closure { function, upvalues }));
+                    self.push(Value::Obj(closure));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().expect("returning from a call frame");
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+                    self.push(result);
+                    if self.frames.len() == depth {
+                        return Ok(());
+                    }
+                }
+                OpCode::Class | OpCode::ClassLong => {
+                    let name = self.read_string(op);
+                    let class = self.alloc(Obj::Class(Class {
+                        name,
+                        methods: Table::new(),
+                    }));
+                    self.push(Value::Obj(class));
+                }
+                OpCode::Inherit => {
+                    let superclass = match self.as_class(self.peek(1)) {
+                        Some(superclass) => superclass,
+                        None => return Err(self.error("Superclass must be a class.")),
+                    };
+                    let subclass = self.pop_obj();
+                    // Copying the methods down now means lookups never have
+                    // to walk the inheritance chain. Subclass methods are
+                    // added afterwards so they override these.
+                    let methods = self.heap.class(superclass).methods.clone();
+                    self.heap.class_mut(subclass).methods.add_all(&methods);
+                }
+                OpCode::Method | OpCode::MethodLong => {
+                    let name = self.read_string(op);
+                    let method = self.pop_obj();
+                    let class = match self.peek(0) {
+                        Value::Obj(class) => class,
+                        value => unreachable!("expected a class, found {:?}", value),
+                    };
+                    let hash = self.heap.hash(name);
+                    let methods = &mut self.heap.class_mut(class).methods;
+                    methods.set(name, hash, Value::Obj(method));
+                }
+                OpCode::BuildList => {
+                    let count = self.read_byte() as usize;
+                    let start = self.stack.len() - count;
+                    // The elements stay on the stack, and so reachable,
+                    // until the list holds them.
+                    let elements = self.stack[start..].to_vec();
+                    let list = self.alloc(Obj::List(elements));
+                    self.stack.truncate(start);
+                    self.push(Value::Obj(list));
+                }
+                OpCode::BuildMap => {
+                    let count = self.read_byte() as usize;
+                    let start = self.stack.len() - count * 2;
+                    let mut map = Map::new();
+                    for entry in self.stack[start..].chunks(2) {
+                        map.insert(self.map_key(entry[0])?, entry[1]);
+                    }
+                    // The entries stay on the stack, and so reachable, until
+                    // the map holds them.
+                    let map = self.alloc(Obj::Map(map));
+                    self.stack.truncate(start);
+                    self.push(Value::Obj(map));
+                }
+                OpCode::GetIndex => {
+                    let (object, index) = (self.peek(1), self.peek(0));
+                    let value = if let Some(list) = self.as_list(object) {
+                        let index = self.list_index(list, index)?;
+                        self.heap.list(list)[index]
+                    } else if let Some(map) = self.as_map(object) {
+                        let key = self.map_key(index)?;
+                        match self.heap.map(map).get(&key) {
+                            Some(value) => *value,
+                            None => {
+                                let shown = index.display(&self.heap).to_string();
+                                return Err(map::undefined_key(&shown).or_line(self.line()));
+                            }
+                        }
+                    } else {
+                        return Err(self.error("Only lists and maps can be indexed."));
+                    };
+                    self.pop();
+                    self.pop();
+                    self.push(value);
+                }
+                OpCode::SetIndex => {
+                    let (object, index, value) = (self.peek(2), self.peek(1), self.peek(0));
+                    if let Some(list) = self.as_list(object) {
+                        let index = self.list_index(list, index)?;
+                        self.heap.list_mut(list)[index] = value;
+                    } else if let Some(map) = self.as_map(object) {
+                        let key = self.map_key(index)?;
+                        self.heap.map_mut(map).insert(key, value);
+                    } else {
+                        return Err(self.error("Only lists and maps can be indexed."));
+                    }
+                    self.stack.truncate(self.stack.len() - 3);
+                    self.push(value);
+                }
+                OpCode::Iter => match self.peek(0) {
+                    Value::Obj(iterable)
+                        if matches!(
+                            self.heap.get(iterable),
+                            Obj::List(_) | Obj::Map(_) | Obj::String(_)
+                        ) =>
+                    {
+                        let keys = match self.heap.get(iterable) {
+                            Obj::Map(map) => map.keys().copied().collect(),
+                            _ => vec![],
+                        };
+                        let iteration = self.alloc(Obj::Iteration(Iteration {
+                            iterable,
+                            position: 0,
+                            keys,
+                        }));
+                        self.pop();
+                        self.push(Value::Obj(iteration));
+                    }
+                    value if self.as_instance(value).is_some() => {
+                        self.invoke(self.iter_string, 0)?;
+                    }
+                    _ => {
+                        return Err(
+                            self.error("Can only iterate over lists, maps, strings and instances.")
+                        )
+                    }
+                },
+            }
+        }
+    }
+
+    fn trace_instruction(&self) {
+        let frame = self.frame();
+        let mut out = debug::format_stack(&self.stack, &self.heap);
+        out.push('\n');
+        debug::disassemble_instruction(&frame.chunk, frame.ip, &self.heap, &mut out);
+        eprint!("{}", out);
+    }
+
+    // Calls
+
+    fn call_value(&mut self, callee: Value, arg_count: usize) -> Result<(), RuntimeError> {
+        if let Value::Obj(reference) = callee {
+            match self.heap.get(reference) {
+                Obj::BoundMethod(bound) => {
+                    let (receiver, method) = (bound.receiver, bound.method);
+                    let slot = self.stack.len() - arg_count - 1;
+                    self.stack[slot] = receiver;
+                    return self.call(method, arg_count);
+                }
+                Obj::Class(class) => {
+                    let init = self.init_string;
+                    let initializer = class.methods.get(init, self.heap.hash(init));
+                    let instance = self.alloc(Obj::Instance(Instance {
+                        class: reference,
+                        fields: Table::new(),
+                    }));
+                    let slot = self.stack.len() - arg_count - 1;
+                    self.stack[slot] = Value::Obj(instance);
+                    return match initializer {
+                        Some(Value::Obj(initializer)) => self.call(initializer, arg_count),
+                        Some(value) => unreachable!("expected a method, found {:?}", value),
+                        None if arg_count != 0 => {
+                            Err(self.error(format!("Expected 0 arguments but got {}.", arg_count)))
+                        }
+                        None => Ok(()),
+                    };
+                }
+                Obj::BoundListMethod(bound) => {
+                    let (list, method) = (bound.list, bound.method);
+                    let slot = self.stack.len() - arg_count - 1;
+                    self.stack[slot] = Value::Obj(list);
+                    return self.call_list_method(list, method, arg_count);
+                }
+                Obj::BoundMapMethod(bound) => {
+                    let (map, method) = (bound.map, bound.method);
+                    let slot = self.stack.len() - arg_count - 1;
+                    self.stack[slot] = Value::Obj(map);
+                    return self.call_map_method(map, method, arg_count);
+                }
+                Obj::Closure(_) => return self.call(reference, arg_count),
+                Obj::Native(native) => {
+                    if arg_count != native.arity {
+                        return Err(self.error(format!(
+                            "Expected {} arguments but got {}.",
+                            native.arity, arg_count
+                        )));
+                    }
+                    let function = Rc::clone(&native.function);
+                    let arguments = self.stack.len() - arg_count;
+                    let result = function(&mut self.heap, &self.stack[arguments..])
+                        .map_err(|error| error.or_line(self.line()))?;
+                    // Pop the arguments and the native itself.
+                    self.stack.truncate(arguments - 1);
+                    self.push(result);
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        Err(self.error("Can only call functions and classes."))
+    }
+
+    fn call(&mut self, closure: ObjRef, arg_count: usize) -> Result<(), RuntimeError> {
+        let function = self.heap.function(self.heap.closure(closure).function);
+        if arg_count != function.arity {
+            return Err(self.error(format!(
+                "Expected {} arguments but got {}.",
+                function.arity, arg_count
+            )));
+        }
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.error("Stack overflow."));
+        }
+
+        let chunk = Rc::clone(&function.chunk);
+        self.frames.push(CallFrame {
+            closure,
+            chunk,
+            ip: 0,
+            slots: self.stack.len() - arg_count - 1,
+        });
+        Ok(())
+    }
+
+    /// Calls `callee` from Rust and runs it to completion, for natives that
+    /// take callbacks.
+    fn call_function(&mut self, callee: Value, arguments: &[Value]) -> Result<Value, RuntimeError> {
+        let depth = self.frames.len();
+        self.push(callee);
+        self.stack.extend_from_slice(arguments);
+        self.call_value(callee, arguments.len())?;
+        // Natives have already left their result, but a Lox function has
+        // only had its frame pushed.
+        if self.frames.len() > depth {
+            self.run(depth)?;
+        }
+        Ok(self.pop())
+    }
+
+    /// Calls a method on the receiver below the arguments, preferring a
+    /// field of the same name as `obj.name()` would.
+    fn invoke(&mut self, name: ObjRef, arg_count: usize) -> Result<(), RuntimeError> {
+        if let Value::Obj(receiver) = self.peek(arg_count) {
+            if let Obj::Iteration(_) = self.heap.get(receiver) {
+                return self.step_iteration(receiver, name, arg_count);
+            }
+        }
+        if let Some(list) = self.as_list(self.peek(arg_count)) {
+            return match ListMethod::from_name(self.heap.string(name)) {
+                Some(method) => self.call_list_method(list, method, arg_count),
+                None => Err(self.undefined_property(name)),
+            };
+        }
+        if let Some(map) = self.as_map(self.peek(arg_count)) {
+            return match MapMethod::from_name(self.heap.string(name)) {
+                Some(method) => self.call_map_method(map, method, arg_count),
+                None => Err(self.undefined_property(name)),
+            };
+        }
+
+        let instance = match self.as_instance(self.peek(arg_count)) {
+            Some(instance) => instance,
+            None => return Err(self.error("Only instances have properties.")),
+        };
+
+        let field = self
+            .instance(instance)
+            .fields
+            .get(name, self.heap.hash(name));
+        if let Some(value) = field {
+            let slot = self.stack.len() - arg_count - 1;
+            self.stack[slot] = value;
+            return self.call_value(value, arg_count);
+        }
+
+        let class = self.instance(instance).class;
+        self.invoke_from_class(class, name, arg_count)
+    }
+
+    fn invoke_from_class(
+        &mut self,
+        class: ObjRef,
+        name: ObjRef,
+        arg_count: usize,
+    ) -> Result<(), RuntimeError> {
+        match self.find_method(class, name) {
+            Some(method) => self.call(method, arg_count),
+            None => Err(self.undefined_property(name)),
+        }
+    }
+
+    /// Replaces the instance on top of the stack with its method `name`
+    /// bound to it.
+    fn bind_method(&mut self, class: ObjRef, name: ObjRef) -> Result<(), RuntimeError> {
+        let method = match self.find_method(class, name) {
+            Some(method) => method,
+            None => return Err(self.undefined_property(name)),
+        };
+        // The receiver stays on the stack, and so reachable, until the bound
+        // method replaces it.
+        let receiver = self.peek(0);
+        let bound = self.alloc(Obj::BoundMethod(BoundMethod { receiver, method }));
+        self.pop();
+        self.push(Value::Obj(bound));
+        Ok(())
+    }
+
+    // Lists
+
+    /// Resolves `index` into `list`, failing if it's out of range.
+    fn list_index(&self, list: ObjRef, index: Value) -> Result<usize, RuntimeError> {
+        let len = self.heap.list(list).len();
+        list::index(&index.to_primitive(&self.heap), len)
+            .map_err(|error| error.or_line(self.line()))
+    }
+
+    /// Replaces the list on top of the stack with its method `name` bound
+    /// to it.
+    fn bind_list_method(&mut self, list: ObjRef, name: ObjRef) -> Result<(), RuntimeError> {
+        let method = match ListMethod::from_name(self.heap.string(name)) {
+            Some(method) => method,
+            None => return Err(self.undefined_property(name)),
+        };
+        let bound = self.alloc(Obj::BoundListMethod(BoundListMethod { list, method }));
+        self.pop();
+        self.push(Value::Obj(bound));
+        Ok(())
+    }
+
+    /// Runs a method of the list below the arguments, replacing the list and
+    /// the arguments with the result.
+    fn call_list_method(
+        &mut self,
+        list: ObjRef,
+        method: ListMethod,
+        arg_count: usize,
+    ) -> Result<(), RuntimeError> {
+        let arguments = self.stack.len() - arg_count;
+        let result = method
+            .check_arity(arg_count)
+            .and_then(|_| self.run_list_method(list, method, arguments, arg_count))
+            .map_err(|error| error.or_line(self.line()))?;
+        self.stack.truncate(arguments - 1);
+        self.push(result);
+        Ok(())
+    }
+
+    /// The arguments start at stack slot `arguments` and stay there, and so
+    /// reachable, until the method returns. Anything else it creates must go
+    /// on the stack before the next allocation.
+    fn run_list_method(
+        &mut self,
+        list: ObjRef,
+        method: ListMethod,
+        arguments: usize,
+        arg_count: usize,
+    ) -> Result<Value, RuntimeError> {
+        let argument = |vm: &Vm, index: usize| vm.stack[arguments + index];
+        let len = self.heap.list(list).len();
+
+        match method {
+            ListMethod::Push => {
+                let value = argument(self, 0);
+                self.heap.list_mut(list).push(value);
+                Ok(Value::Nil)
+            }
+            ListMethod::Pop => self
+                .heap
+                .list_mut(list)
+                .pop()
+                .ok_or_else(|| RuntimeError::native("Can't pop from an empty list.")),
+            ListMethod::Insert => {
+                let index =
+                    list::insertion_index(&argument(self, 0).to_primitive(&self.heap), len)?;
+                let value = argument(self, 1);
+                self.heap.list_mut(list).insert(index, value);
+                Ok(Value::Nil)
+            }
+            ListMethod::Remove => {
+                let index = list::index(&argument(self, 0).to_primitive(&self.heap), len)?;
+                Ok(self.heap.list_mut(list).remove(index))
+            }
+            ListMethod::Len => Ok(Value::Number(len as f64)),
+            ListMethod::Slice => {
+                let bounds = [
+                    argument(self, 0).to_primitive(&self.heap),
+                    argument(self, 1).to_primitive(&self.heap),
+                ];
+                let range = list::slice(&bounds, len)?;
+                let elements = self.heap.list(list)[range].to_vec();
+                Ok(Value::Obj(self.alloc(Obj::List(elements))))
+            }
+            ListMethod::Sort => {
+                let elements = self.heap.list(list).clone();
+                let sorted = if arg_count == 1 {
+                    let comparator = argument(self, 0);
+                    // Sort a copy, kept reachable in case the comparator
+                    // changes the list.
+                    let copy = self.alloc(Obj::List(elements.clone()));
+                    self.push(Value::Obj(copy));
+                    let sorted = list::sort(&elements, &mut |a, b| {
+                        let result = self.call_function(comparator, &[*a, *b])?;
+                        list::comparator_ordering(&result.to_primitive(&self.heap))
+                    });
+                    self.pop();
+                    sorted?
+                } else {
+                    list::sort(&elements, &mut |a, b| {
+                        list::compare(&a.to_primitive(&self.heap), &b.to_primitive(&self.heap))
+                    })?
+                };
+                *self.heap.list_mut(list) = sorted;
+                Ok(Value::Nil)
+            }
+            ListMethod::Map | ListMethod::Filter => {
+                let function = argument(self, 0);
+                let result = self.alloc(Obj::List(vec![]));
+                self.push(Value::Obj(result));
+                // Read each element afresh, since the callback can change
+                // the list, but stop at the length it started with so
+                // pushing onto it can't keep the loop going forever.
+                let len = self.heap.list(list).len();
+                for index in 0..len {
+                    let Some(&element) = self.heap.list(list).get(index) else {
+                        break;
+                    };
+                    let value = self.call_function(function, &[element])?;
+                    match method {
+                        ListMethod::Map => self.heap.list_mut(result).push(value),
+                        _ if value.is_truthy() => self.heap.list_mut(result).push(element),
+                        _ => {}
+                    }
+                }
+                self.pop();
+                Ok(Value::Obj(result))
+            }
+            ListMethod::Reduce => {
+                let function = argument(self, 0);
+                // The accumulator lives in the second argument's slot. Like
+                // `map`, only the elements there to begin with are visited.
+                let len = self.heap.list(list).len();
+                for index in 0..len {
+                    let Some(&element) = self.heap.list(list).get(index) else {
+                        break;
+                    };
+                    let accumulator = argument(self, 1);
+                    self.stack[arguments + 1] =
+                        self.call_function(function, &[accumulator, element])?;
+                }
+                Ok(argument(self, 1))
+            }
+        }
+    }
+
+    // Maps
+
+    /// Converts a value to use it as a key, failing for values that can't
+    /// be.
+    fn map_key(&self, value: Value) -> Result<MapKey<ObjRef>, RuntimeError> {
+        value
+            .to_key(&self.heap)
+            .map_err(|error| error.or_line(self.line()))
+    }
+
+    /// Replaces the map on top of the stack with its method `name` bound to
+    /// it.
+    fn bind_map_method(&mut self, map: ObjRef, name: ObjRef) -> Result<(), RuntimeError> {
+        let method = match MapMethod::from_name(self.heap.string(name)) {
+            Some(method) => method,
+            None => return Err(self.undefined_property(name)),
+        };
+        let bound = self.alloc(Obj::BoundMapMethod(BoundMapMethod { map, method }));
+        self.pop();
+        self.push(Value::Obj(bound));
+        Ok(())
+    }
+
+    /// Runs a method of the map below the arguments, replacing the map and
+    /// the arguments with the result.
+    fn call_map_method(
+        &mut self,
+        map: ObjRef,
+        method: MapMethod,
+        arg_count: usize,
+    ) -> Result<(), RuntimeError> {
+        method
+            .check_arity(arg_count)
+            .map_err(|error| error.or_line(self.line()))?;
+        let result = match method {
+            MapMethod::Keys => {
+                let keys = self.heap.map(map).keys().map(|key| Value::from_key(*key));
+                let keys = keys.collect();
+                Value::Obj(self.alloc(Obj::List(keys)))
+            }
+            MapMethod::Values => {
+                let values = self.heap.map(map).values().copied().collect();
+                Value::Obj(self.alloc(Obj::List(values)))
+            }
+            MapMethod::Has => {
+                let key = self.map_key(self.peek(0))?;
+                Value::Bool(self.heap.map(map).contains_key(&key))
+            }
+            MapMethod::Delete => {
+                let key = self.map_key(self.peek(0))?;
+                Value::Bool(self.heap.map_mut(map).remove(&key).is_some())
+            }
+            MapMethod::Len => Value::Number(self.heap.map(map).len() as f64),
+        };
+        self.stack.truncate(self.stack.len() - arg_count - 1);
+        self.push(result);
+        Ok(())
+    }
+
+    // Iteration
+
+    /// Answers `done()` or `next()` for an iteration below the arguments,
+    /// as a `for` loop over a list, map or string asks them.
+    fn step_iteration(
+        &mut self,
+        iteration: ObjRef,
+        name: ObjRef,
+        arg_count: usize,
+    ) -> Result<(), RuntimeError> {
+        let Obj::Iteration(state) = self.heap.get(iteration) else {
+            unreachable!("checked by the caller");
+        };
+        let (iterable, mut position) = (state.iterable, state.position);
+        let done = match self.heap.get(iterable) {
+            Obj::List(elements) => position >= elements.len(),
+            Obj::Map(map) => {
+                // Pass over keys deleted since the loop started.
+                while state
+                    .keys
+                    .get(position)
+                    .is_some_and(|key| !map.contains_key(key))
+                {
+                    position += 1;
+                }
+                position >= state.keys.len()
+            }
+            Obj::String(string) => position >= string.chars.len(),
+            _ => unreachable!("only lists, maps and strings get an iteration"),
+        };
+
+        let result = match self.heap.string(name) {
+            "done" => Value::Bool(done),
+            "next" if done => Value::Nil,
+            "next" => {
+                let (value, step) = match self.heap.get(iterable) {
+                    Obj::List(elements) => (elements[position], 1),
+                    Obj::Map(_) => match self.heap.get(iteration) {
+                        Obj::Iteration(state) => (Value::from_key(state.keys[position]), 1),
+                        _ => unreachable!("checked by the caller"),
+                    },
+                    Obj::String(string) => {
+                        let character = string.chars[position..]
+                            .chars()
+                            .next()
+                            .expect("checked it isn't done");
+                        let mut buffer = [0; 4];
+                        let character = character.encode_utf8(&mut buffer);
+                        (Value::Obj(self.intern(character)), character.len())
+                    }
+                    _ => unreachable!("only lists, maps and strings get an iteration"),
+                };
+                position += step;
+                value
+            }
+            _ => return Err(self.undefined_property(name)),
+        };
+        if let Obj::Iteration(iteration) = self.heap.get_mut(iteration) {
+            iteration.position = position;
+        }
+        self.stack.truncate(self.stack.len() - arg_count - 1);
+        self.push(result);
+        Ok(())
+    }
+
+    // Memory
+
+    /// Allocates an object, collecting garbage first if the heap is due.
+    /// Anything the VM still needs must be reachable from the roots by now,
+    /// usually by being on the stack.
+    fn alloc(&mut self, object: Obj) -> ObjRef {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.alloc(object)
+    }
+
+    fn intern(&mut self, chars: &str) -> ObjRef {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.intern(chars)
+    }
+
+    fn collect_garbage(&mut self) {
+        for value in &self.stack {
+            self.heap.mark_value(*value);
+        }
+        for frame in &self.frames {
+            self.heap.mark_object(frame.closure);
+        }
+        for upvalue in &self.open_upvalues {
+            self.heap.mark_object(*upvalue);
+        }
+        for (name, _, value) in self.globals.entries() {
+            self.heap.mark_object(name);
+            self.heap.mark_value(value);
+        }
+        self.heap.mark_object(self.init_string);
+        self.heap.mark_object(self.iter_string);
+        self.heap.collect();
+    }
+
+    fn find_method(&self, class: ObjRef, name: ObjRef) -> Option<ObjRef> {
+        let methods = &self.heap.class(class).methods;
+        match methods.get(name, self.heap.hash(name))? {
+            Value::Obj(method) => Some(method),
+            value => unreachable!("expected a method, found {:?}", value),
+        }
+    }
+
+    // Upvalues
+
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+        let position = self
+            .open_upvalues
+            .binary_search_by_key(&slot, |upvalue| self.open_slot(*upvalue));
+        match position {
+            Ok(index) => self.open_upvalues[index],
+            Err(index) => {
+                let upvalue = self.alloc(Obj::Upvalue(Upvalue::Open(slot)));
+                self.open_upvalues.insert(index, upvalue);
+                upvalue
+            }
+        }
+    }
+
+    /// Moves the values of upvalues pointing at `last` or above off the
+    /// stack, since those slots are about to be popped.
+    fn close_upvalues(&mut self, last: usize) {
+        while let Some(&upvalue) = self.open_upvalues.last() {
+            let slot = self.open_slot(upvalue);
+            if slot < last {
+                break;
+            }
+            *self.heap.upvalue_mut(upvalue) = Upvalue::Closed(self.stack[slot]);
+            self.open_upvalues.pop();
+        }
+    }
+
+    fn open_slot(&self, upvalue: ObjRef) -> usize {
+        match self.heap.upvalue(upvalue) {
+            Upvalue::Open(slot) => *slot,
+            Upvalue::Closed(_) => unreachable!("closed upvalue in the open list"),
+        }
+    }
+
+    // Operators
+
+    fn add(&mut self) -> Result<(), RuntimeError> {
+        match (self.peek(1), self.peek(0)) {
+            (Value::Number(a), Value::Number(b)) => {
+                self.pop();
+                self.pop();
+                self.push(Value::Number(a + b));
+                Ok(())
+            }
+            (Value::Obj(a), Value::Obj(b))
+                if matches!(
+                    (self.heap.get(a), self.heap.get(b)),
+                    (Obj::String(_), Obj::String(_))
+                ) =>
+            {
+                let mut result = String::from(self.heap.string(a));
+                result.push_str(self.heap.string(b));
+                let string = self.intern(&result);
+                self.pop();
+                self.pop();
+                self.push(Value::Obj(string));
+                Ok(())
+            }
+            _ => Err(self.error("Operands must be two numbers or two strings.")),
+        }
+    }
+
+    fn arithmetic(&mut self, op: fn(f64, f64) -> f64) -> Result<(), RuntimeError> {
+        let (a, b) = self.number_operands()?;
+        self.push(Value::Number(op(a, b)));
+        Ok(())
+    }
+
+    fn comparison(&mut self, op: fn(f64, f64) -> bool) -> Result<(), RuntimeError> {
+        let (a, b) = self.number_operands()?;
+        self.push(Value::Bool(op(a, b)));
+        Ok(())
+    }
+
+    fn number_operands(&mut self) -> Result<(f64, f64), RuntimeError> {
+        match (self.peek(1), self.peek(0)) {
+            (Value::Number(a), Value::Number(b)) => {
+                self.pop();
+                self.pop();
+                Ok((a, b))
+            }
+            _ => Err(self.error("Operands must be numbers.")),
+        }
+    }
+
+    // Stack and code access
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    /// Pops a value the compiler guarantees to be an object.
+    fn pop_obj(&mut self) -> ObjRef {
+        match self.pop() {
+            Value::Obj(reference) => reference,
+            value => unreachable!("expected an object, found {:?}", value),
+        }
+    }
+
+    fn peek(&self, distance: usize) -> Value {
+        self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("a call frame is running")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("a call frame is running")
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = frame.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_short(&mut self) -> usize {
+        let high = self.read_byte() as usize;
+        let low = self.read_byte() as usize;
+        (high << 8) | low
+    }
+
+    /// Reads the constant index operand of `op`, which takes three bytes for
+    /// the wide forms of instructions.
+    fn read_constant(&mut self, op: OpCode) -> Value {
+        let index = if op.is_long() {
+            let high = self.read_byte() as usize;
+            (high << 16) | self.read_short()
+        } else {
+            self.read_byte() as usize
+        };
+        self.frame().chunk.constants[index]
+    }
+
+    fn read_string(&mut self, op: OpCode) -> ObjRef {
+        match self.read_constant(op) {
+            Value::Obj(reference) => reference,
+            value => unreachable!("expected a string constant, found {:?}", value),
+        }
+    }
+
+    fn as_instance(&self, value: Value) -> Option<ObjRef> {
+        match value {
+            Value::Obj(reference) if matches!(self.heap.get(reference), Obj::Instance(_)) => {
+                Some(reference)
+            }
+            _ => None,
+        }
+    }
+
+    fn as_list(&self, value: Value) -> Option<ObjRef> {
+        match value {
+            Value::Obj(reference) if matches!(self.heap.get(reference), Obj::List(_)) => {
+                Some(reference)
+            }
+            _ => None,
+        }
+    }
+
+    fn as_map(&self, value: Value) -> Option<ObjRef> {
+        match value {
+            Value::Obj(reference) if matches!(self.heap.get(reference), Obj::Map(_)) => {
+                Some(reference)
+            }
+            _ => None,
+        }
+    }
+
+    fn as_class(&self, value: Value) -> Option<ObjRef> {
+        match value {
+            Value::Obj(reference) if matches!(self.heap.get(reference), Obj::Class(_)) => {
+                Some(reference)
+            }
+            _ => None,
+        }
+    }
+
+    fn instance(&self, reference: ObjRef) -> &Instance {
+        match self.heap.get(reference) {
+            Obj::Instance(instance) => instance,
+            object => unreachable!("expected an instance, found {:?}", object),
+        }
+    }
+
+    fn instance_mut(&mut self, reference: ObjRef) -> &mut Instance {
+        match self.heap.get_mut(reference) {
+            Obj::Instance(instance) => instance,
+            object => unreachable!("expected an instance, found {:?}", object),
+        }
+    }
+
+    // Errors
+
+    /// Builds an error at the line of the instruction being run.
+    fn error(&self, message: impl Into<String>) -> RuntimeError {
+        RuntimeError::new(self.line(), message)
+    }
+
+    /// Source line of the instruction being run.
+    fn line(&self) -> usize {
+        let frame = self.frame();
+        frame.chunk.line(frame.ip - 1)
+    }
+
+    fn undefined_variable(&self, name: ObjRef) -> RuntimeError {
+        self.error(format!("Undefined variable '{}'.", self.heap.string(name)))
+    }
+
+    fn undefined_property(&self, name: ObjRef) -> RuntimeError {
+        self.error(format!("Undefined property '{}'.", self.heap.string(name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    fn instances(vm: &Vm) -> usize {
+        vm.heap
+            .objects()
+            .filter(|object| matches!(object, Obj::Instance(_)))
+            .count()
+    }
+
+    #[test]
+    fn collects_unreachable_cycles() {
+        let mut vm = Vm::new().stress_gc();
+        let source = "
+            class Node {}
+            {
+                var node = Node();
+                node.next = node;
+            }
+            var kept = Node();
+        ";
+        assert!(vm.interpret(source).is_ok());
+        // Allocating `kept` collected the node that only pointed at itself.
+        assert_eq!(instances(&vm), 1);
+    }
+
+    #[test]
+    fn keeps_reachable_cycles() {
+        let mut vm = Vm::new().stress_gc();
+        let source = "
+            class Node {}
+            var node = Node();
+            node.next = node;
+            var other = Node();
+            var text = \"allocate\" + \"more\";
+        ";
+        assert!(vm.interpret(source).is_ok());
+        assert_eq!(instances(&vm), 2);
+        assert!(vm.interpret("print node.next == node;").is_ok());
+    }
+
+    #[test]
+    fn keeps_values_captured_by_closures() {
+        let mut vm = Vm::new().stress_gc();
+        let source = "
+            class Box {}
+            fun make() {
+                var box = Box();
+                fun get() { return box; }
+                return get;
+            }
+            var get = make();
+            var garbage = Box();
+            garbage = nil;
+            var more = Box();
+        ";
+        assert!(vm.interpret(source).is_ok());
+        // The captured box and `more` survive; `garbage` doesn't.
+        assert_eq!(instances(&vm), 2);
+    }
+
+    /// Defines `record`, which appends its number argument to the returned
+    /// list and returns nil.
+    fn recorder(vm: &mut Vm) -> Rc<RefCell<Vec<f64>>> {
+        let recorded = Rc::new(RefCell::new(vec![]));
+        let sink = Rc::clone(&recorded);
+        vm.define_native("record", 1, move |args| match args[0] {
+            Value::Number(number) => {
+                sink.borrow_mut().push(number);
+                Ok(Value::Nil)
+            }
+            _ => Err(RuntimeError::native("Expected a number.")),
+        });
+        recorded
+    }
+
+    fn runtime_error(result: Result<(), InterpretError>) -> RuntimeError {
+        match result {
+            Err(InterpretError::Runtime(error)) => error,
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn natives_are_called_with_their_arguments() {
+        let mut vm = Vm::new().stress_gc();
+        let recorded = recorder(&mut vm);
+        vm.define_native("add", 2, |args| match (args[0], args[1]) {
+            (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+            _ => Err(RuntimeError::native("Expected numbers.")),
+        });
+        assert!(vm.interpret("record(1); record(add(2, 3));").is_ok());
+        assert_eq!(*recorded.borrow(), [1.0, 5.0]);
+    }
+
+    #[test]
+    fn native_errors_are_reported_at_the_call() {
+        let mut vm = Vm::new();
+        recorder(&mut vm);
+        let error = runtime_error(vm.interpret("var a = 1;\nrecord(nil);"));
+        assert_eq!(error.message, "Expected a number.");
+        assert_eq!(error.line, 2);
+
+        let error = runtime_error(vm.interpret("record(1, 2);"));
+        assert_eq!(error.message, "Expected 1 arguments but got 2.");
+    }
+
+    #[test]
+    fn redefining_a_native_replaces_it() {
+        let mut vm = Vm::new();
+        let recorded = recorder(&mut vm);
+        vm.define_native("answer", 0, |_| Ok(Value::Number(1.0)));
+        vm.define_native("answer", 0, |_| Ok(Value::Number(42.0)));
+        assert!(vm.interpret("record(answer());").is_ok());
+        assert_eq!(*recorded.borrow(), [42.0]);
+    }
+
+    #[test]
+    fn clock_counts_seconds_since_the_epoch() {
+        let mut vm = Vm::new();
+        let recorded = recorder(&mut vm);
+        let before = native::clock();
+        assert!(vm.interpret("record(clock()); record(clock());").is_ok());
+        let recorded = recorded.borrow();
+        assert!(before <= recorded[0] && recorded[0] <= recorded[1]);
+        assert!(recorded[1] <= native::clock());
+    }
+}
@@ -0,0 +1,103 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::Name;
+use crate::function::LoxFunction;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::symbol::{Symbol, INIT};
+use crate::value::{Callable, Value};
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: Symbol,
+    pub superclass: Option<Rc<Class>>,
+    methods: HashMap<Symbol, Rc<LoxFunction>>,
+}
+
+impl Class {
+    pub fn new(
+        name: Symbol,
+        superclass: Option<Rc<Class>>,
+        methods: HashMap<Symbol, Rc<LoxFunction>>,
+    ) -> Self {
+        Class {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    /// Looks a method up on this class, then on its superclasses.
+    pub fn find_method(&self, name: Symbol) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(&name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+
+    /// Calling a class takes the arguments of its `init` method, if it has
+    /// one.
+    pub fn arity(&self) -> usize {
+        self.find_method(*INIT)
+            .map_or(0, |initializer| initializer.arity())
+    }
+
+    /// Creates a new instance and runs `init` on it.
+    pub fn instantiate(
+        class: &Rc<Class>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let instance = Rc::new(RefCell::new(Instance::new(Rc::clone(class))));
+        if let Some(initializer) = class.find_method(*INIT) {
+            initializer
+                .bind(Rc::clone(&instance))
+                .call(interpreter, arguments)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    fields: HashMap<Symbol, Value>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Self {
+        Instance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Reads a field, or failing that a method bound to this instance.
+    /// Fields shadow methods.
+    pub fn get(instance: &Rc<RefCell<Instance>>, name: &Name) -> Result<Value, RuntimeError> {
+        if let Some(value) = instance.borrow().fields.get(&name.symbol) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(name.symbol);
+        match method {
+            Some(method) => Ok(Value::Callable(Rc::new(method.bind(Rc::clone(instance))))),
+            None => Err(RuntimeError::new(
+                name.line,
+                format!("Undefined property '{}'.", name.symbol),
+            )),
+        }
+    }
+
+    pub fn set(&mut self, name: Symbol, value: Value) {
+        self.fields.insert(name, value);
+    }
+}
@@ -0,0 +1,167 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::Name;
+use crate::interpreter::RuntimeError;
+use crate::symbol::Symbol;
+use crate::value::Value;
+
+/// The variables of one scope, chained to the scope that encloses it. Lookups
+/// walk outwards, so inner scopes shadow outer ones.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<Symbol, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// Declares a variable in this scope. Redeclaring one replaces it.
+    pub fn define(&mut self, name: Symbol, value: Value) {
+        self.values.insert(name, value);
+    }
+
+    pub fn get(&self, name: &Name) -> Result<Value, RuntimeError> {
+        if let Some(value) = self.values.get(&name.symbol) {
+            return Ok(value.clone());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(undefined_variable(name)),
+        }
+    }
+
+    /// Reads a variable declared `depth` scopes out from this one, as worked
+    /// out by the resolver.
+    pub fn get_at(&self, depth: usize, name: &Name) -> Result<Value, RuntimeError> {
+        if depth == 0 {
+            return self
+                .get_here(name.symbol)
+                .ok_or_else(|| undefined_variable(name));
+        }
+        self.enclosing()
+            .expect("resolver depth is within the environment chain")
+            .borrow()
+            .get_at(depth - 1, name)
+    }
+
+    pub fn assign_at(
+        &mut self,
+        depth: usize,
+        name: &Name,
+        value: Value,
+    ) -> Result<(), RuntimeError> {
+        if depth == 0 {
+            return match self.values.get_mut(&name.symbol) {
+                Some(slot) => {
+                    *slot = value;
+                    Ok(())
+                }
+                None => Err(undefined_variable(name)),
+            };
+        }
+        self.enclosing()
+            .expect("resolver depth is within the environment chain")
+            .borrow_mut()
+            .assign_at(depth - 1, name, value)
+    }
+
+    fn enclosing(&self) -> Option<&Rc<RefCell<Environment>>> {
+        self.enclosing.as_ref()
+    }
+
+    /// Reads a variable declared in this scope itself, ignoring enclosing ones.
+    pub fn get_here(&self, name: Symbol) -> Option<Value> {
+        self.values.get(&name).cloned()
+    }
+
+    /// Assigns to an existing variable in the nearest scope declaring it.
+    pub fn assign(&mut self, name: &Name, value: Value) -> Result<(), RuntimeError> {
+        if let Some(slot) = self.values.get_mut(&name.symbol) {
+            *slot = value;
+            return Ok(());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(undefined_variable(name)),
+        }
+    }
+}
+
+fn undefined_variable(name: &Name) -> RuntimeError {
+    RuntimeError::new(name.line, format!("Undefined variable '{}'.", name.symbol))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source_map::Span;
+
+    fn name(text: &str) -> Name {
+        Name {
+            symbol: Symbol::intern(text),
+            line: 1,
+            column: 1,
+            span: Span::default(),
+        }
+    }
+
+    fn number(value: Result<Value, RuntimeError>) -> f64 {
+        match value {
+            Ok(Value::Number(number)) => number,
+            other => panic!("expected a number, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lookups_walk_out_through_enclosing_scopes() {
+        let outer = Rc::new(RefCell::new(Environment::new()));
+        outer
+            .borrow_mut()
+            .define(Symbol::intern("a"), Value::Number(1.0));
+        outer
+            .borrow_mut()
+            .define(Symbol::intern("b"), Value::Number(2.0));
+        let mut inner = Environment::with_enclosing(Rc::clone(&outer));
+        inner.define(Symbol::intern("a"), Value::Number(10.0));
+
+        assert_eq!(number(inner.get(&name("a"))), 10.0);
+        assert_eq!(number(inner.get(&name("b"))), 2.0);
+        assert_eq!(number(inner.get_at(1, &name("a"))), 1.0);
+        assert!(inner.get_here(Symbol::intern("b")).is_none());
+    }
+
+    #[test]
+    fn assignment_updates_the_nearest_scope() {
+        let outer = Rc::new(RefCell::new(Environment::new()));
+        outer
+            .borrow_mut()
+            .define(Symbol::intern("a"), Value::Number(1.0));
+        let mut inner = Environment::with_enclosing(Rc::clone(&outer));
+
+        inner.assign(&name("a"), Value::Number(2.0)).unwrap();
+        assert_eq!(number(outer.borrow().get(&name("a"))), 2.0);
+        inner.assign_at(1, &name("a"), Value::Number(3.0)).unwrap();
+        assert_eq!(number(outer.borrow().get(&name("a"))), 3.0);
+    }
+
+    #[test]
+    fn undefined_variables_are_errors() {
+        let environment = Environment::new();
+        let error = environment.get(&name("missing")).unwrap_err();
+        assert_eq!(error.message, "Undefined variable 'missing'.");
+        let mut environment = environment;
+        assert!(environment.assign(&name("missing"), Value::Nil).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::FunctionDecl;
+use crate::class::Instance;
+use crate::environment::Environment;
+use crate::interpreter::{Interpreter, RuntimeError, Unwind};
+use crate::symbol::THIS;
+use crate::value::{Callable, Value};
+
+/// A function declared in Lox code, along with the environment it was
+/// declared in so it can see the variables around it.
+#[derive(Debug)]
+pub struct LoxFunction {
+    declaration: Rc<FunctionDecl>,
+    closure: Rc<RefCell<Environment>>,
+    // Initializers always return `this`, whatever their body returns.
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Rc<FunctionDecl>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    /// Makes a copy of this method with `this` bound to `instance`.
+    pub fn bind(&self, instance: Rc<RefCell<Instance>>) -> LoxFunction {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        environment.define(*THIS, Value::Instance(instance));
+        LoxFunction::new(
+            Rc::clone(&self.declaration),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+
+    fn this(&self) -> Value {
+        self.closure
+            .borrow()
+            .get_here(*THIS)
+            .expect("initializers are bound to an instance")
+    }
+}
+
+impl Callable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.symbol, argument);
+        }
+
+        match interpreter.execute_call(&self.declaration.body, Rc::new(RefCell::new(environment))) {
+            Ok(()) | Err(Unwind::Return(_)) if self.is_initializer => Ok(self.this()),
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(error),
+            Err(Unwind::Break | Unwind::Continue) => {
+                unreachable!("the resolver keeps 'break' and 'continue' inside loops")
+            }
+        }
+    }
+}
+
+impl fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.symbol)
+    }
+}
@@ -0,0 +1,703 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{
+    BinaryOp, ClassDecl, Expr, LiteralValue, LogicalOp, Name, Stmt, UnaryOp, Variable,
+};
+use crate::class::{Class, Instance};
+use crate::environment::Environment;
+use crate::function::LoxFunction;
+use crate::iteration::Iteration;
+use crate::limits::FRAMES_MAX;
+use crate::list;
+use crate::map;
+use crate::native::{self, NativeFn};
+use crate::stdlib::map::OrderedMap;
+use crate::stdlib::{self, arity_message};
+use crate::symbol::{Symbol, INIT, SUPER, THIS};
+use crate::value::Value;
+
+/// An error raised while running a program, reported with the line of the
+/// code that caused it.
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub message: String,
+    pub line: usize,
+}
+
+impl RuntimeError {
+    pub fn new(line: usize, message: impl Into<String>) -> Self {
+        RuntimeError {
+            message: message.into(),
+            line,
+        }
+    }
+
+    /// An error raised by a native function, which can't know where it was
+    /// called from. The call fills in its line.
+    pub fn native(message: impl Into<String>) -> Self {
+        RuntimeError::new(0, message)
+    }
+
+    /// Sets the line if the error doesn't have one yet.
+    pub fn or_line(mut self, line: usize) -> Self {
+        if self.line == 0 {
+            self.line = line;
+        }
+        self
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n[line {}]", self.message, self.line)
+    }
+}
+
+type RuntimeResult<T> = Result<T, RuntimeError>;
+
+/// Why a statement stopped running before reaching its end.
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    /// A `return` statement, carrying its value up to the function call.
+    Return(Value),
+    /// `break` and `continue`, carried up to the innermost loop.
+    Break,
+    Continue,
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+type ExecResult = Result<(), Unwind>;
+
+/// Tree-walking interpreter. Global variables persist between calls to
+/// `interpret`, so a prompt session can build on earlier lines.
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    // The innermost scope of the code being run.
+    environment: Rc<RefCell<Environment>>,
+    // How many Lox function calls are running, so runaway recursion is
+    // reported instead of overflowing the Rust stack.
+    call_depth: usize,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        let mut interpreter = Interpreter {
+            environment: Rc::clone(&globals),
+            globals,
+            call_depth: 0,
+        };
+        interpreter.define_native("clock", 0, |_| Ok(Value::Number(native::clock())));
+        for (name, value) in stdlib::constants() {
+            interpreter
+                .globals
+                .borrow_mut()
+                .define(Symbol::intern(name), Value::Number(*value));
+        }
+        for library_fn in stdlib::functions() {
+            let function = library_fn.function;
+            interpreter.define_native(library_fn.name, library_fn.arity, move |args| {
+                let args: Vec<_> = args.iter().map(Value::to_primitive).collect();
+                function(&args).map(Value::from)
+            });
+        }
+        interpreter
+    }
+
+    /// Defines a global function implemented in Rust. Natives defined before
+    /// `interpret` runs a script are visible to it, and redefining a name
+    /// replaces the old function.
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value, RuntimeError> + 'static,
+    ) {
+        let native = NativeFn::new(name, arity, function);
+        self.globals
+            .borrow_mut()
+            .define(Symbol::intern(name), Value::Callable(Rc::new(native)));
+    }
+
+    /// Runs the statements in order, stopping at the first runtime error.
+    pub fn interpret(&mut self, statements: &[Stmt]) -> RuntimeResult<()> {
+        for statement in statements {
+            match self.execute(statement) {
+                Ok(()) => {}
+                // Returning from the top level ends the script.
+                Err(Unwind::Return(_)) => return Ok(()),
+                Err(Unwind::Error(error)) => return Err(error),
+                Err(Unwind::Break | Unwind::Continue) => {
+                    unreachable!("the resolver keeps 'break' and 'continue' inside loops")
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, statement: &Stmt) -> ExecResult {
+        match statement {
+            Stmt::Expression(expr) => {
+                self.evaluate(expr)?;
+            }
+            Stmt::Print(expr) => {
+                let value = self.evaluate(expr)?;
+                println!("{}", value);
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                let value = match initializer {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Value::Nil,
+                };
+                self.environment.borrow_mut().define(name.symbol, value);
+            }
+            Stmt::Block(statements) => {
+                let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                self.execute_block(statements, Rc::new(RefCell::new(environment)))?;
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.evaluate(condition)?.is_truthy() {
+                    self.execute(then_branch)?;
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(else_branch)?;
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                while self.evaluate(condition)?.is_truthy() {
+                    if !self.execute_loop_body(body)? {
+                        break;
+                    }
+                    if let Some(increment) = increment {
+                        self.evaluate(increment)?;
+                    }
+                }
+            }
+            Stmt::ForIn {
+                variable,
+                keyword,
+                iterable,
+                body,
+            } => {
+                let iterable = self.evaluate(iterable)?;
+                let mut iteration = Iteration::new(self, iterable, keyword)?;
+                while let Some(value) = iteration.next(self, keyword)? {
+                    let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                    environment.define(variable.symbol, value);
+                    let previous = std::mem::replace(
+                        &mut self.environment,
+                        Rc::new(RefCell::new(environment)),
+                    );
+                    let carry_on = self.execute_loop_body(body);
+                    self.environment = previous;
+                    if !carry_on? {
+                        break;
+                    }
+                }
+            }
+            Stmt::Break { .. } => return Err(Unwind::Break),
+            Stmt::Continue { .. } => return Err(Unwind::Continue),
+            Stmt::Function(declaration) => {
+                let function =
+                    LoxFunction::new(Rc::clone(declaration), Rc::clone(&self.environment), false);
+                self.environment
+                    .borrow_mut()
+                    .define(declaration.name.symbol, Value::Callable(Rc::new(function)));
+            }
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Nil,
+                };
+                return Err(Unwind::Return(value));
+            }
+            Stmt::Class(declaration) => self.class_declaration(declaration)?,
+        }
+        Ok(())
+    }
+
+    /// Runs one pass of a loop's body, returning whether the loop should
+    /// carry on, which it does unless the body hit a `break`.
+    fn execute_loop_body(&mut self, body: &Stmt) -> Result<bool, Unwind> {
+        match self.execute(body) {
+            Ok(()) | Err(Unwind::Continue) => Ok(true),
+            Err(Unwind::Break) => Ok(false),
+            Err(unwind) => Err(unwind),
+        }
+    }
+
+    /// Runs `body` as a call to a Lox function, failing if calls are nested
+    /// too deeply. The top-level script counts towards `FRAMES_MAX` as it
+    /// does on the VM.
+    pub fn execute_call(
+        &mut self,
+        body: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> ExecResult {
+        if self.call_depth == FRAMES_MAX - 1 {
+            return Err(Unwind::Error(RuntimeError::native("Stack overflow.")));
+        }
+        self.call_depth += 1;
+        let result = self.execute_block(body, environment);
+        self.call_depth -= 1;
+        result
+    }
+
+    /// Runs `statements` in `environment`, restoring the current environment
+    /// afterwards even if they fail or return.
+    pub fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> ExecResult {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement));
+        self.environment = previous;
+        result
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> RuntimeResult<Value> {
+        match expr {
+            Expr::Literal(value) => Ok(match value {
+                LiteralValue::Nil => Value::Nil,
+                LiteralValue::Bool(value) => Value::Bool(*value),
+                LiteralValue::Number(value) => Value::Number(*value),
+                LiteralValue::String(value) => Value::String(Rc::from(value.as_str())),
+            }),
+            Expr::Grouping(expr) => self.evaluate(expr),
+            Expr::Unary { operator, right } => {
+                let right = self.evaluate(right)?;
+                match operator.kind {
+                    UnaryOp::Bang => Ok(Value::Bool(!right.is_truthy())),
+                    UnaryOp::Minus => match right {
+                        Value::Number(value) => Ok(Value::Number(-value)),
+                        _ => Err(RuntimeError::new(
+                            operator.line,
+                            "Operand must be a number.",
+                        )),
+                    },
+                }
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                binary(operator.kind, left, right)
+                    .map_err(|message| RuntimeError::new(operator.line, message))
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.evaluate(left)?;
+                let short_circuits = match operator.kind {
+                    LogicalOp::Or => left.is_truthy(),
+                    LogicalOp::And => !left.is_truthy(),
+                };
+                if short_circuits {
+                    Ok(left)
+                } else {
+                    self.evaluate(right)
+                }
+            }
+            Expr::Variable(variable) => self.look_up_variable(variable),
+            Expr::Assign { variable, value } => {
+                let value = self.evaluate(value)?;
+                match variable.depth.get() {
+                    Some(depth) => self.environment.borrow_mut().assign_at(
+                        depth,
+                        &variable.name,
+                        value.clone(),
+                    )?,
+                    None => self
+                        .globals
+                        .borrow_mut()
+                        .assign(&variable.name, value.clone())?,
+                }
+                Ok(value)
+            }
+            Expr::Call {
+                callee,
+                arguments,
+                line,
+            } => {
+                let callee = self.evaluate(callee)?;
+                let mut values = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    values.push(self.evaluate(argument)?);
+                }
+                self.call(callee, values)
+                    .map_err(|error| error.or_line(*line))
+            }
+            Expr::Get { object, name } => property(&self.evaluate(object)?, name),
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                let instance = match self.evaluate(object)? {
+                    Value::Instance(instance) => instance,
+                    _ => return Err(RuntimeError::new(name.line, "Only instances have fields.")),
+                };
+                let value = self.evaluate(value)?;
+                instance.borrow_mut().set(name.symbol, value.clone());
+                Ok(value)
+            }
+            Expr::List(elements) => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.evaluate(element)?);
+                }
+                Ok(list::new(values))
+            }
+            Expr::Map { entries, line } => {
+                let mut map = OrderedMap::new();
+                for (key, value) in entries {
+                    let key = self.evaluate(key)?;
+                    let value = self.evaluate(value)?;
+                    let key = map::key(&key).map_err(|error| error.or_line(*line))?;
+                    map.insert(key, value);
+                }
+                Ok(map::new(map))
+            }
+            Expr::Index {
+                object,
+                index,
+                line,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                match object {
+                    Value::List(list) => list::get(&list, &index),
+                    Value::Map(map) => map::get(&map, &index),
+                    _ => Err(RuntimeError::native("Only lists and maps can be indexed.")),
+                }
+                .map_err(|error| error.or_line(*line))
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                line,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                let value = self.evaluate(value)?;
+                match object {
+                    Value::List(list) => list::set(&list, &index, value.clone()),
+                    Value::Map(map) => map::set(&map, &index, value.clone()),
+                    _ => Err(RuntimeError::native("Only lists and maps can be indexed.")),
+                }
+                .map_err(|error| error.or_line(*line))?;
+                Ok(value)
+            }
+            Expr::This(keyword) => self.look_up_variable(keyword).map_err(|_| {
+                RuntimeError::new(keyword.name.line, "Can't use 'this' outside of a class.")
+            }),
+            Expr::Super { keyword, method } => self.super_method(keyword, method),
+        }
+    }
+
+    /// Calls a function or class. Errors from the call itself, such as a
+    /// wrong argument count, have no line; the caller knows where it is.
+    pub fn call(&mut self, callee: Value, arguments: Vec<Value>) -> RuntimeResult<Value> {
+        let (min, max) = match &callee {
+            Value::Callable(callable) => (callable.arity(), callable.max_arity()),
+            Value::Class(class) => (class.arity(), class.arity()),
+            _ => return Err(RuntimeError::native("Can only call functions and classes.")),
+        };
+        if !(min..=max).contains(&arguments.len()) {
+            return Err(RuntimeError::native(arity_message(
+                min,
+                max,
+                arguments.len(),
+            )));
+        }
+        match callee {
+            Value::Class(class) => Class::instantiate(&class, self, arguments),
+            Value::Callable(callable) => callable.call(self, arguments),
+            _ => unreachable!("checked above"),
+        }
+    }
+
+    fn class_declaration(&mut self, declaration: &ClassDecl) -> RuntimeResult<()> {
+        let superclass = match &declaration.superclass {
+            Some(variable) => match self.look_up_variable(variable)? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    return Err(RuntimeError::new(
+                        variable.name.line,
+                        "Superclass must be a class.",
+                    ))
+                }
+            },
+            None => None,
+        };
+
+        // Methods of a subclass close over an extra scope holding `super`.
+        let mut closure = Rc::clone(&self.environment);
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::with_enclosing(closure);
+            environment.define(*SUPER, Value::Class(Rc::clone(superclass)));
+            closure = Rc::new(RefCell::new(environment));
+        }
+
+        let methods = declaration
+            .methods
+            .iter()
+            .map(|method| {
+                let function = LoxFunction::new(
+                    Rc::clone(method),
+                    Rc::clone(&closure),
+                    method.name.symbol == *INIT,
+                );
+                (method.name.symbol, Rc::new(function))
+            })
+            .collect();
+
+        let class = Class::new(declaration.name.symbol, superclass, methods);
+        self.environment
+            .borrow_mut()
+            .define(declaration.name.symbol, Value::Class(Rc::new(class)));
+        Ok(())
+    }
+
+    /// Reads a variable from the scope the resolver found it in, or from the
+    /// globals if it wasn't found in any.
+    fn look_up_variable(&self, variable: &Variable) -> RuntimeResult<Value> {
+        match variable.depth.get() {
+            Some(depth) => self.environment.borrow().get_at(depth, &variable.name),
+            None => self.globals.borrow().get(&variable.name),
+        }
+    }
+
+    /// Looks `method` up on the superclass of the class whose method is
+    /// running, bound to the current `this`.
+    fn super_method(&mut self, keyword: &Variable, method: &Name) -> RuntimeResult<Value> {
+        // `this` is bound in the scope just inside the one holding `super`.
+        let this = Variable {
+            name: Name {
+                symbol: *THIS,
+                ..keyword.name
+            },
+            depth: Cell::new(keyword.depth.get().map(|depth| depth.saturating_sub(1))),
+        };
+
+        let superclass = match self.look_up_variable(keyword) {
+            Ok(Value::Class(superclass)) => superclass,
+            _ => {
+                let message = if self.look_up_variable(&this).is_ok() {
+                    "Can't use 'super' in a class with no superclass."
+                } else {
+                    "Can't use 'super' outside of a class."
+                };
+                return Err(RuntimeError::new(keyword.name.line, message));
+            }
+        };
+        let instance = match self.look_up_variable(&this)? {
+            Value::Instance(instance) => instance,
+            _ => unreachable!("'this' is always bound to an instance"),
+        };
+
+        match superclass.find_method(method.symbol) {
+            Some(function) => Ok(Value::Callable(Rc::new(function.bind(instance)))),
+            None => Err(RuntimeError::new(
+                method.line,
+                format!("Undefined property '{}'.", method.symbol),
+            )),
+        }
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+/// `object.name`: a field or method of an instance, or a method of a
+/// built-in collection.
+pub fn property(object: &Value, name: &Name) -> RuntimeResult<Value> {
+    match object {
+        Value::Instance(instance) => Instance::get(instance, name),
+        Value::List(list) => list::method(list, name),
+        Value::Map(map) => map::method(map, name),
+        _ => Err(RuntimeError::new(
+            name.line,
+            "Only instances have properties.",
+        )),
+    }
+}
+
+/// Applies a binary operator, returning the error message if the operands
+/// have the wrong types.
+fn binary(operator: BinaryOp, left: Value, right: Value) -> Result<Value, &'static str> {
+    let value = match (operator, &left, &right) {
+        (BinaryOp::EqualEqual, _, _) => Value::Bool(left == right),
+        (BinaryOp::BangEqual, _, _) => Value::Bool(left != right),
+        (BinaryOp::Plus, Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+        (BinaryOp::Plus, Value::String(a), Value::String(b)) => {
+            Value::String(Rc::from(format!("{}{}", a, b)))
+        }
+        (BinaryOp::Plus, _, _) => return Err("Operands must be two numbers or two strings."),
+        (operator, Value::Number(a), Value::Number(b)) => match operator {
+            BinaryOp::Minus => Value::Number(a - b),
+            BinaryOp::Star => Value::Number(a * b),
+            BinaryOp::Slash => Value::Number(a / b),
+            BinaryOp::Percent => Value::Number(stdlib::modulo(*a, *b)?),
+            BinaryOp::Greater => Value::Bool(a > b),
+            BinaryOp::GreaterEqual => Value::Bool(a >= b),
+            BinaryOp::Less => Value::Bool(a < b),
+            BinaryOp::LessEqual => Value::Bool(a <= b),
+            _ => unreachable!("handled above"),
+        },
+        _ => return Err("Operands must be numbers."),
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+
+    /// Runs `source` on a fresh interpreter.
+    fn interpret(source: &str) -> RuntimeResult<()> {
+        let (tokens, errors) = Scanner::new(source).scan_tokens();
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        let statements = Parser::new(tokens).parse().expect("failed to parse");
+        Resolver::new()
+            .resolve(&statements)
+            .expect("failed to resolve");
+        Interpreter::new().interpret(&statements)
+    }
+
+    #[test]
+    fn runtime_errors_carry_the_line() {
+        let error = interpret("var a = 1;\nvar b = a +\n  nil;").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Operands must be two numbers or two strings."
+        );
+        assert_eq!(error.line, 2);
+        assert_eq!(
+            error.to_string(),
+            "Operands must be two numbers or two strings.\n[line 2]"
+        );
+    }
+
+    #[test]
+    fn native_errors_take_the_line_of_their_caller() {
+        let error = RuntimeError::native("Bad argument.");
+        assert_eq!(error.line, 0);
+        assert_eq!(error.clone().or_line(7).line, 7);
+        assert_eq!(RuntimeError::new(3, "x").or_line(7).line, 3);
+    }
+
+    #[test]
+    fn globals_persist_between_runs() {
+        let mut interpreter = Interpreter::new();
+        let mut run = |source: &str| {
+            let (tokens, _) = Scanner::new(source).scan_tokens();
+            let statements = Parser::new(tokens).parse().unwrap();
+            interpreter.interpret(&statements)
+        };
+        assert!(run("var a = 1;").is_ok());
+        assert!(run("a = a + 1;").is_ok());
+        assert!(run("-a;").is_ok());
+        assert!(run("a + \"s\";").is_err());
+    }
+
+    /// Parses and resolves `source`, then runs it on `interpreter`.
+    fn run_on(interpreter: &mut Interpreter, source: &str) -> RuntimeResult<()> {
+        let (tokens, _) = Scanner::new(source).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        Resolver::new().resolve(&statements).unwrap();
+        interpreter.interpret(&statements)
+    }
+
+    /// Defines `record`, which appends its number argument to the returned
+    /// list and returns nil.
+    fn recorder(interpreter: &mut Interpreter) -> Rc<RefCell<Vec<f64>>> {
+        let recorded = Rc::new(RefCell::new(vec![]));
+        let sink = Rc::clone(&recorded);
+        interpreter.define_native("record", 1, move |args| match args[0] {
+            Value::Number(number) => {
+                sink.borrow_mut().push(number);
+                Ok(Value::Nil)
+            }
+            _ => Err(RuntimeError::native("Expected a number.")),
+        });
+        recorded
+    }
+
+    #[test]
+    fn natives_are_called_with_their_arguments() {
+        let mut interpreter = Interpreter::new();
+        let recorded = recorder(&mut interpreter);
+        interpreter.define_native("add", 2, |args| match (&args[0], &args[1]) {
+            (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+            _ => Err(RuntimeError::native("Expected numbers.")),
+        });
+        assert!(run_on(&mut interpreter, "record(1); record(add(2, 3));").is_ok());
+        assert_eq!(*recorded.borrow(), [1.0, 5.0]);
+    }
+
+    #[test]
+    fn native_errors_are_reported_at_the_call() {
+        let mut interpreter = Interpreter::new();
+        recorder(&mut interpreter);
+        let error = run_on(&mut interpreter, "var a = 1;\nrecord(\"a\");").unwrap_err();
+        assert_eq!(error.message, "Expected a number.");
+        assert_eq!(error.line, 2);
+
+        let error = run_on(&mut interpreter, "record(1, 2);").unwrap_err();
+        assert_eq!(error.message, "Expected 1 arguments but got 2.");
+    }
+
+    #[test]
+    fn redefining_a_native_replaces_it() {
+        let mut interpreter = Interpreter::new();
+        let recorded = recorder(&mut interpreter);
+        interpreter.define_native("answer", 0, |_| Ok(Value::Number(1.0)));
+        interpreter.define_native("answer", 0, |_| Ok(Value::Number(42.0)));
+        assert!(run_on(&mut interpreter, "record(answer());").is_ok());
+        assert_eq!(*recorded.borrow(), [42.0]);
+    }
+
+    #[test]
+    fn clock_counts_seconds_since_the_epoch() {
+        let mut interpreter = Interpreter::new();
+        let recorded = recorder(&mut interpreter);
+        let before = native::clock();
+        assert!(run_on(&mut interpreter, "record(clock()); record(clock());").is_ok());
+        let recorded = recorded.borrow();
+        assert!(before <= recorded[0] && recorded[0] <= recorded[1]);
+        assert!(recorded[1] <= native::clock());
+    }
+}
@@ -0,0 +1,102 @@
+use std::rc::Rc;
+use std::vec;
+
+use crate::ast::Name;
+use crate::interpreter::{self, Interpreter, RuntimeError};
+use crate::list::List;
+use crate::map::{self, Map};
+use crate::symbol::{Symbol, DONE, ITER, NEXT};
+use crate::value::Value;
+
+/// How far a `for (x in ...)` loop has got through what it's looping over.
+/// Lists are read afresh at each step, so the loop sees changes its body
+/// makes to them.
+pub enum Iteration {
+    List(List, usize),
+    /// Maps give their keys, in insertion order. The keys are taken when the
+    /// loop starts, so deleting the current key doesn't skip the next one
+    /// and adding keys can't keep the loop going. Keys deleted before the
+    /// loop reaches them are passed over.
+    Map(Map, vec::IntoIter<map::Key>),
+    /// Strings give their characters. The number is the byte offset of the
+    /// next one.
+    String(Rc<str>, usize),
+    /// What an instance's `iter()` method returned, whose `done()` method
+    /// is asked before each call to `next()`.
+    Object(Value),
+}
+
+impl Iteration {
+    /// Starts looping over `iterable`. `keyword` is the loop's `in`, where
+    /// errors point.
+    pub fn new(
+        interpreter: &mut Interpreter,
+        iterable: Value,
+        keyword: &Name,
+    ) -> Result<Self, RuntimeError> {
+        match iterable {
+            Value::List(list) => Ok(Iteration::List(list, 0)),
+            Value::Map(map) => {
+                let keys: Vec<map::Key> = map.borrow().keys().cloned().collect();
+                Ok(Iteration::Map(map, keys.into_iter()))
+            }
+            Value::String(string) => Ok(Iteration::String(string, 0)),
+            Value::Instance(_) => {
+                call_method(interpreter, &iterable, *ITER, keyword).map(Iteration::Object)
+            }
+            _ => Err(RuntimeError::new(
+                keyword.line,
+                "Can only iterate over lists, maps, strings and instances.",
+            )),
+        }
+    }
+
+    /// The next value, or `None` once there are no more.
+    pub fn next(
+        &mut self,
+        interpreter: &mut Interpreter,
+        keyword: &Name,
+    ) -> Result<Option<Value>, RuntimeError> {
+        match self {
+            Iteration::List(list, index) => {
+                let element = list.borrow().get(*index).cloned();
+                *index += 1;
+                Ok(element)
+            }
+            Iteration::Map(map, keys) => {
+                let key = keys.find(|key| map.borrow().contains_key(key));
+                Ok(key.as_ref().map(map::key_value))
+            }
+            Iteration::String(string, offset) => {
+                let Some(character) = string[*offset..].chars().next() else {
+                    return Ok(None);
+                };
+                *offset += character.len_utf8();
+                Ok(Some(Value::String(Rc::from(character.to_string()))))
+            }
+            Iteration::Object(iterator) => {
+                if call_method(interpreter, iterator, *DONE, keyword)?.is_truthy() {
+                    return Ok(None);
+                }
+                call_method(interpreter, iterator, *NEXT, keyword).map(Some)
+            }
+        }
+    }
+}
+
+/// Calls `object.name()`, as the loop does for the iteration protocol.
+fn call_method(
+    interpreter: &mut Interpreter,
+    object: &Value,
+    name: Symbol,
+    keyword: &Name,
+) -> Result<Value, RuntimeError> {
+    let name = Name {
+        symbol: name,
+        ..*keyword
+    };
+    let method = interpreter::property(object, &name)?;
+    interpreter
+        .call(method, vec![])
+        .map_err(|error| error.or_line(keyword.line))
+}
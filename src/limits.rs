@@ -0,0 +1,5 @@
+/// How deep calls can nest before running a program is a "Stack overflow."
+/// error. Both backends share it, so a script that recurses too deeply fails
+/// the same way on each. The count includes the top-level script, which the
+/// VM runs as a call of its own.
+pub const FRAMES_MAX: usize = 256;
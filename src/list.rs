@@ -0,0 +1,188 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::Name;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::stdlib::list::{self, ListMethod};
+use crate::stdlib::Primitive;
+use crate::value::{Callable, Value};
+
+/// A list's elements. Lists are shared, so every variable holding the same
+/// list sees changes made through any of them.
+pub type List = Rc<RefCell<Vec<Value>>>;
+
+pub fn new(elements: Vec<Value>) -> Value {
+    Value::List(Rc::new(RefCell::new(elements)))
+}
+
+/// `list[index]`.
+pub fn get(list: &List, index: &Value) -> Result<Value, RuntimeError> {
+    let index = index.to_primitive();
+    let elements = list.borrow();
+    let index = list::index(&index, elements.len())?;
+    Ok(elements[index].clone())
+}
+
+/// `list[index] = value`.
+pub fn set(list: &List, index: &Value, value: Value) -> Result<(), RuntimeError> {
+    // Converting the index borrows it, and it could be this very list.
+    let index = index.to_primitive();
+    let mut elements = list.borrow_mut();
+    let index = list::index(&index, elements.len())?;
+    elements[index] = value;
+    Ok(())
+}
+
+/// Looks up a method, as in `list.push`, bound to the list.
+pub fn method(list: &List, name: &Name) -> Result<Value, RuntimeError> {
+    match ListMethod::from_name(name.symbol.as_str()) {
+        Some(method) => Ok(Value::Callable(Rc::new(BoundListMethod {
+            list: Rc::clone(list),
+            method,
+        }))),
+        None => Err(RuntimeError::new(
+            name.line,
+            format!("Undefined property '{}'.", name.symbol),
+        )),
+    }
+}
+
+#[derive(Debug)]
+pub struct BoundListMethod {
+    list: List,
+    method: ListMethod,
+}
+
+impl BoundListMethod {
+    /// The element at `index`, read afresh each time since a callback can
+    /// change the list while `map` and friends are walking it.
+    fn element(&self, index: usize) -> Option<Value> {
+        self.list.borrow().get(index).cloned()
+    }
+
+    /// The elements `map` and friends visit, in order. Only those already
+    /// there when the method was called are visited, so a callback that
+    /// pushes onto the list can't keep it going forever.
+    fn elements(&self) -> impl Iterator<Item = Value> + '_ {
+        let len = self.list.borrow().len();
+        (0..len).map_while(|index| self.element(index))
+    }
+}
+
+impl Callable for BoundListMethod {
+    fn arity(&self) -> usize {
+        self.method.arity().0
+    }
+
+    fn max_arity(&self) -> usize {
+        self.method.arity().1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let mut arguments = arguments.into_iter();
+        let mut argument = || arguments.next().expect("checked against the arity");
+
+        match self.method {
+            ListMethod::Push => {
+                self.list.borrow_mut().push(argument());
+                Ok(Value::Nil)
+            }
+            ListMethod::Pop => self
+                .list
+                .borrow_mut()
+                .pop()
+                .ok_or_else(|| RuntimeError::native("Can't pop from an empty list.")),
+            ListMethod::Insert => {
+                let index = argument().to_primitive();
+                let mut elements = self.list.borrow_mut();
+                let index = list::insertion_index(&index, elements.len())?;
+                elements.insert(index, argument());
+                Ok(Value::Nil)
+            }
+            ListMethod::Remove => {
+                let index = argument().to_primitive();
+                let mut elements = self.list.borrow_mut();
+                let index = list::index(&index, elements.len())?;
+                Ok(elements.remove(index))
+            }
+            ListMethod::Len => Ok(Value::Number(self.list.borrow().len() as f64)),
+            ListMethod::Slice => {
+                let bounds = [argument().to_primitive(), argument().to_primitive()];
+                let elements = self.list.borrow();
+                let range = list::slice(&bounds, elements.len())?;
+                Ok(new(elements[range].to_vec()))
+            }
+            ListMethod::Sort => {
+                // Sort a copy, so a comparator that looks at the list sees
+                // it as it was.
+                let elements = self.list.borrow().clone();
+                let sorted = match arguments.next() {
+                    Some(comparator) => list::sort(&elements, &mut |a, b| {
+                        let result =
+                            interpreter.call(comparator.clone(), vec![a.clone(), b.clone()])?;
+                        list::comparator_ordering(&result.to_primitive())
+                    }),
+                    None => list::sort(&elements, &mut |a, b| {
+                        list::compare(&a.to_primitive(), &b.to_primitive())
+                    }),
+                }?;
+                *self.list.borrow_mut() = sorted;
+                Ok(Value::Nil)
+            }
+            ListMethod::Map => {
+                let function = argument();
+                let mut results = vec![];
+                for element in self.elements() {
+                    results.push(interpreter.call(function.clone(), vec![element])?);
+                }
+                Ok(new(results))
+            }
+            ListMethod::Filter => {
+                let predicate = argument();
+                let mut kept = vec![];
+                for element in self.elements() {
+                    if interpreter
+                        .call(predicate.clone(), vec![element.clone()])?
+                        .is_truthy()
+                    {
+                        kept.push(element);
+                    }
+                }
+                Ok(new(kept))
+            }
+            ListMethod::Reduce => {
+                let function = argument();
+                let mut accumulator = argument();
+                for element in self.elements() {
+                    accumulator = interpreter.call(function.clone(), vec![accumulator, element])?;
+                }
+                Ok(accumulator)
+            }
+        }
+    }
+}
+
+impl fmt::Display for BoundListMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
+
+/// Converts a list to pass it to the standard library.
+pub fn to_primitive(list: &List) -> Primitive {
+    let elements = list.borrow();
+    Primitive::List(
+        elements
+            .iter()
+            .map(|element| match element {
+                Value::List(_) => Primitive::Other(Rc::from(element.to_string())),
+                other => other.to_primitive(),
+            })
+            .collect(),
+    )
+}
@@ -1,26 +1,124 @@
 #![allow(dead_code)]
 
+mod ast;
+mod bytecode;
+mod class;
+mod environment;
+mod function;
+mod interpreter;
+mod iteration;
+mod limits;
+mod list;
+mod map;
+mod native;
+mod parser;
+mod resolver;
 mod scanner;
+mod source_map;
+mod stdlib;
+mod symbol;
+mod value;
 
 use std::io::{stdin, stdout, Write};
-use std::process;
-use std::{env, fs};
+use std::{env, fs, panic, process, thread};
 
+use crate::bytecode::{InterpretError, Vm};
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
 use crate::scanner::Scanner;
 
+/// Exit code for scripts that fail to scan or parse (EX_DATAERR in sysexits.h).
+const EXIT_DATA_ERROR: i32 = 65;
+/// Exit code for scripts that fail while running (EX_SOFTWARE in sysexits.h).
+const EXIT_RUNTIME_ERROR: i32 = 70;
+
+/// The tree-walker recurses on the Rust stack for every Lox call and every
+/// level of nesting inside one, so a program `FRAMES_MAX` calls deep can need
+/// far more than the main thread's stack in a debug build. Programs run on a
+/// thread with room for that instead. Only the pages actually used are ever
+/// committed.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    match args.as_slice() {
-        [_] => run_prompt(),
-        [_, file_arg] => run_file(file_arg),
-        _ => {
-            println!("Usage: lox [script]");
-            process::exit(64)
-        }
+    let lox = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(lox)
+        .expect("Failed to start the interpreter thread");
+    if let Err(payload) = lox.join() {
+        panic::resume_unwind(payload);
+    }
+}
+
+fn lox() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (flags, rest): (Vec<&String>, Vec<&String>) =
+        args.iter().partition(|arg| arg.starts_with("--"));
+
+    let mut vm = None;
+    for flag in flags {
+        vm = Some(match flag.as_str() {
+            "--vm" => vm.unwrap_or_else(Vm::new),
+            // Debugging output only exists for the bytecode backend.
+            "--disassemble" => vm.unwrap_or_else(Vm::new).print_code(),
+            "--trace" => vm.unwrap_or_else(Vm::new).trace_execution(),
+            "--stress-gc" => vm.unwrap_or_else(Vm::new).stress_gc(),
+            _ => usage(),
+        });
+    }
+    let backend = match vm {
+        Some(vm) => Backend::Bytecode(Box::new(vm)),
+        None => Backend::TreeWalker(Interpreter::new()),
     };
+
+    match rest.as_slice() {
+        [] => run_prompt(backend),
+        [file_arg] => run_file(backend, file_arg),
+        _ => usage(),
+    };
+}
+
+fn usage() -> ! {
+    println!("Usage: lox [--vm] [--disassemble] [--trace] [--stress-gc] [script]");
+    println!();
+    println!("  --vm           run on the bytecode VM, which garbage collects cycles");
+    println!("  --disassemble  print each function's bytecode (implies --vm)");
+    println!("  --trace        print each instruction as it runs (implies --vm)");
+    println!("  --stress-gc    collect garbage before every allocation (implies --vm)");
+    println!();
+    println!("The default tree-walker frees values by reference count and, by design,");
+    println!("never collects cycles: an object reachable from itself stays allocated");
+    println!("until the process exits. Use --vm for long-running sessions.");
+    process::exit(64)
+}
+
+/// Which implementation runs the code. Both run the same language, so a
+/// script can be compared across them.
+enum Backend {
+    TreeWalker(Interpreter),
+    Bytecode(Box<Vm>),
+}
+
+impl Backend {
+    /// Runs a chunk of source, returning the exit code to use if it failed.
+    fn run(&mut self, source: &str) -> Result<(), i32> {
+        match self {
+            Backend::TreeWalker(interpreter) => run(interpreter, source),
+            Backend::Bytecode(vm) => match vm.interpret(source) {
+                Ok(()) => Ok(()),
+                Err(error) => {
+                    eprintln!("{}", error);
+                    Err(match error {
+                        InterpretError::Compile(_) => EXIT_DATA_ERROR,
+                        InterpretError::Runtime(_) => EXIT_RUNTIME_ERROR,
+                    })
+                }
+            },
+        }
+    }
 }
 
-fn run_prompt() {
+fn run_prompt(mut backend: Backend) {
     loop {
         let mut input = String::new();
         print!(">");
@@ -29,16 +127,48 @@ fn run_prompt() {
             if n == 0 {
                 end();
             }
-            run(input.as_ref())
+            // Errors have already been reported; the prompt keeps going.
+            let _ = backend.run(input.as_ref());
         } else {
             end();
         }
     }
 }
 
-fn run(source: &str) {
+/// Runs a chunk of source on the tree-walking interpreter, returning the exit
+/// code to use if it failed.
+fn run(interpreter: &mut Interpreter, source: &str) -> Result<(), i32> {
     let scanner = Scanner::new(source);
-    dbg!(scanner.scan_tokens());
+    let (tokens, errors) = scanner.scan_tokens();
+    if !errors.is_empty() {
+        for error in &errors {
+            eprintln!("{}", error);
+        }
+        return Err(EXIT_DATA_ERROR);
+    }
+
+    let statements = match Parser::new(tokens).parse() {
+        Ok(statements) => statements,
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("{}", error);
+            }
+            return Err(EXIT_DATA_ERROR);
+        }
+    };
+
+    if let Err(errors) = Resolver::new().resolve(&statements) {
+        for error in &errors {
+            eprintln!("{}", error);
+        }
+        return Err(EXIT_DATA_ERROR);
+    }
+
+    if let Err(error) = interpreter.interpret(&statements) {
+        eprintln!("{}", error);
+        return Err(EXIT_RUNTIME_ERROR);
+    }
+    Ok(())
 }
 
 fn end() -> ! {
@@ -46,10 +176,12 @@ fn end() -> ! {
     process::exit(0)
 }
 
-fn run_file(file_name: &str) {
+fn run_file(mut backend: Backend, file_name: &str) {
     println!("Running file: {}", file_name);
-    let file_contents =
-        fs::read_to_string(&file_name).expect(&format!("Failed to open file {}", file_name));
+    let file_contents = fs::read_to_string(file_name)
+        .unwrap_or_else(|_| panic!("Failed to open file {}", file_name));
 
-    run(&file_contents);
+    if let Err(code) = backend.run(&file_contents) {
+        process::exit(code);
+    }
 }
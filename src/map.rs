@@ -0,0 +1,104 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::Name;
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::list;
+use crate::stdlib::map::{self, MapKey, MapMethod, OrderedMap};
+use crate::value::{Callable, Value};
+
+pub type Key = MapKey<Rc<str>>;
+
+/// A map's entries. Like lists, maps are shared rather than copied.
+pub type Map = Rc<RefCell<OrderedMap<Key, Value>>>;
+
+pub fn new(entries: OrderedMap<Key, Value>) -> Value {
+    Value::Map(Rc::new(RefCell::new(entries)))
+}
+
+/// Converts a value to use it as a key, failing for values that can't be.
+pub fn key(value: &Value) -> Result<Key, RuntimeError> {
+    match value {
+        Value::Nil => Ok(MapKey::Nil),
+        Value::Bool(value) => Ok(MapKey::Bool(*value)),
+        Value::Number(value) => MapKey::number(*value),
+        Value::String(value) => Ok(MapKey::String(Rc::clone(value))),
+        _ => Err(map::key_error()),
+    }
+}
+
+pub fn key_value(key: &Key) -> Value {
+    match key {
+        MapKey::Nil => Value::Nil,
+        MapKey::Bool(value) => Value::Bool(*value),
+        MapKey::Number(bits) => Value::Number(f64::from_bits(*bits)),
+        MapKey::String(value) => Value::String(Rc::clone(value)),
+    }
+}
+
+/// `map[key]`.
+pub fn get(map: &Map, key: &Value) -> Result<Value, RuntimeError> {
+    map.borrow()
+        .get(&self::key(key)?)
+        .cloned()
+        .ok_or_else(|| map::undefined_key(&key.to_string()))
+}
+
+/// `map[key] = value`, adding the key if it's new.
+pub fn set(map: &Map, key: &Value, value: Value) -> Result<(), RuntimeError> {
+    map.borrow_mut().insert(self::key(key)?, value);
+    Ok(())
+}
+
+/// Looks up a method, as in `map.keys`, bound to the map.
+pub fn method(map: &Map, name: &Name) -> Result<Value, RuntimeError> {
+    match MapMethod::from_name(name.symbol.as_str()) {
+        Some(method) => Ok(Value::Callable(Rc::new(BoundMapMethod {
+            map: Rc::clone(map),
+            method,
+        }))),
+        None => Err(RuntimeError::new(
+            name.line,
+            format!("Undefined property '{}'.", name.symbol),
+        )),
+    }
+}
+
+#[derive(Debug)]
+pub struct BoundMapMethod {
+    map: Map,
+    method: MapMethod,
+}
+
+impl Callable for BoundMapMethod {
+    fn arity(&self) -> usize {
+        self.method.arity()
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        match self.method {
+            MapMethod::Keys => Ok(list::new(self.map.borrow().keys().map(key_value).collect())),
+            MapMethod::Values => Ok(list::new(self.map.borrow().values().cloned().collect())),
+            MapMethod::Has => {
+                let key = key(&arguments[0])?;
+                Ok(Value::Bool(self.map.borrow().contains_key(&key)))
+            }
+            MapMethod::Delete => {
+                let key = key(&arguments[0])?;
+                Ok(Value::Bool(self.map.borrow_mut().remove(&key).is_some()))
+            }
+            MapMethod::Len => Ok(Value::Number(self.map.borrow().len() as f64)),
+        }
+    }
+}
+
+impl fmt::Display for BoundMapMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
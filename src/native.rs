@@ -0,0 +1,67 @@
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::value::{Callable, Value};
+
+/// The Rust side of a native function. It's only called with as many
+/// arguments as the function's arity.
+pub type NativeBody = Rc<dyn Fn(&[Value]) -> Result<Value, RuntimeError>>;
+
+/// A function implemented in Rust and called from Lox code like any other.
+pub struct NativeFn {
+    name: String,
+    arity: usize,
+    function: NativeBody,
+}
+
+impl NativeFn {
+    pub fn new(
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value, RuntimeError> + 'static,
+    ) -> Self {
+        NativeFn {
+            name: String::from(name),
+            arity,
+            function: Rc::new(function),
+        }
+    }
+}
+
+impl Callable for NativeFn {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        (self.function)(&arguments)
+    }
+}
+
+impl fmt::Debug for NativeFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFn")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for NativeFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
+
+/// Seconds since the Unix epoch, for `clock()`. Both backends share it.
+pub fn clock() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |elapsed| elapsed.as_secs_f64())
+}
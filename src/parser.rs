@@ -0,0 +1,1020 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{
+    BinaryOp, ClassDecl, Expr, FunctionDecl, LiteralValue, LogicalOp, Name, Operator, Stmt,
+    UnaryOp, Variable,
+};
+use crate::scanner::{Literal, Token, TokenType};
+use crate::source_map::Span;
+use crate::symbol::Symbol;
+
+/// Functions can't take more arguments than this, to match the limits of
+/// the reference implementation.
+const MAX_ARGUMENTS: usize = 255;
+/// The bytecode VM counts the elements of list and map literals in a byte,
+/// so both backends stop at the same size.
+const MAX_ELEMENTS: usize = 255;
+
+/// A syntax error. `lexeme` is the text of the token the parser choked on,
+/// empty at the end of input.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub message: String,
+    pub lexeme: String,
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
+    pub at_end: bool,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[line {}:{}] Error", self.line, self.column)?;
+        if self.at_end {
+            write!(f, " at end")?;
+        } else {
+            write!(f, " at '{}'", self.lexeme)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+type ParseResult<T> = Result<T, ParseError>;
+
+/// Recursive descent parser over the tokens produced by `Scanner`. Each
+/// grammar rule gets a method, from lowest precedence to highest:
+///
+/// ```text
+/// program     → declaration* EOF
+/// declaration → classDecl | funDecl | varDecl | statement
+/// classDecl   → "class" IDENTIFIER ( "<" IDENTIFIER )? "{" function* "}"
+/// funDecl     → "fun" function
+/// function    → IDENTIFIER "(" parameters? ")" block
+/// varDecl     → "var" IDENTIFIER ( "=" expression )? ";"
+/// statement   → exprStmt | forStmt | ifStmt | printStmt | returnStmt
+///             | whileStmt | breakStmt | continueStmt | block
+/// forStmt     → "for" "(" ( varDecl | exprStmt | ";" ) expression? ";"
+///               expression? ")" statement
+///             | "for" "(" IDENTIFIER "in" expression ")" statement
+/// ifStmt      → "if" "(" expression ")" statement ( "else" statement )?
+/// returnStmt  → "return" expression? ";"
+/// whileStmt   → "while" "(" expression ")" statement
+/// breakStmt   → "break" ";"
+/// continueStmt → "continue" ";"
+/// block       → "{" declaration* "}"
+///
+/// expression  → assignment
+/// assignment  → ( call "." )? IDENTIFIER "=" assignment
+///             | call "[" expression "]" "=" assignment | or
+/// or          → and ( "or" and )*
+/// and         → equality ( "and" equality )*
+/// equality    → comparison ( ( "!=" | "==" ) comparison )*
+/// comparison  → term ( ( ">" | ">=" | "<" | "<=" ) term )*
+/// term        → factor ( ( "-" | "+" ) factor )*
+/// factor      → unary ( ( "/" | "*" | "%" ) unary )*
+/// unary       → ( "!" | "-" ) unary | call
+/// call        → primary ( "(" arguments? ")" | "." IDENTIFIER
+///               | "[" expression "]" )*
+/// primary     → NUMBER | STRING | "true" | "false" | "nil" | "this"
+///             | IDENTIFIER | "(" expression ")" | "super" "." IDENTIFIER
+///             | list | map
+/// list        → "[" ( expression ( "," expression )* ","? )? "]"
+/// map         → "{" ( entry ( "," entry )* ","? )? "}"
+/// entry       → expression ":" expression
+/// ```
+///
+/// On a syntax error the parser records it and skips ahead to the next
+/// statement boundary, so one pass reports every error in the file.
+pub struct Parser<'src> {
+    tokens: Vec<Token<'src>>,
+    current: usize,
+    errors: Vec<ParseError>,
+    // Doc comments, keyed by the index of the token that follows them.
+    docs: HashMap<usize, String>,
+}
+
+impl<'src> Parser<'src> {
+    /// `tokens` must end with an `Eof` token, as `Scanner` output does.
+    pub fn new(tokens: Vec<Token<'src>>) -> Self {
+        // Doc comments are set aside rather than parsed, and picked back up
+        // by the declaration that follows them.
+        let mut docs: HashMap<usize, String> = HashMap::new();
+        let mut code_tokens = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            match token.doc_text() {
+                Some(text) => {
+                    let doc = docs.entry(code_tokens.len()).or_default();
+                    if !doc.is_empty() {
+                        doc.push('\n');
+                    }
+                    doc.push_str(text);
+                }
+                None => code_tokens.push(token),
+            }
+        }
+
+        Parser {
+            tokens: code_tokens,
+            current: 0,
+            errors: vec![],
+            docs,
+        }
+    }
+
+    /// Parses a whole program, returning every syntax error found if there
+    /// are any.
+    pub fn parse(mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        let mut statements = vec![];
+        while !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    /// Parses a declaration, or returns `None` after recording an error and
+    /// synchronizing.
+    fn declaration(&mut self) -> Option<Stmt> {
+        let doc = self.docs.remove(&self.current);
+        let result = if self.r#match(&[TokenType::Class]) {
+            self.class_declaration(doc)
+        } else if self.r#match(&[TokenType::Fun]) {
+            self.function("function", doc).map(Stmt::Function)
+        } else if self.r#match(&[TokenType::Var]) {
+            self.var_declaration(doc)
+        } else {
+            self.statement()
+        };
+
+        match result {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn class_declaration(&mut self, doc: Option<String>) -> ParseResult<Stmt> {
+        let name = self.consume_name("Expect class name.")?;
+
+        let superclass = if self.r#match(&[TokenType::Less]) {
+            Some(Variable::new(self.consume_name("Expect superclass name.")?))
+        } else {
+            None
+        };
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+        let mut methods = vec![];
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let doc = self.docs.remove(&self.current);
+            methods.push(self.function("method", doc)?);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+
+        Ok(Stmt::Class(ClassDecl {
+            name,
+            superclass,
+            methods,
+            doc,
+        }))
+    }
+
+    /// Parses a function's name, parameters and body. `kind` is used in
+    /// error messages.
+    fn function(&mut self, kind: &str, doc: Option<String>) -> ParseResult<Rc<FunctionDecl>> {
+        let name = self.consume_name(&format!("Expect {} name.", kind))?;
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expect '(' after {} name.", kind),
+        )?;
+
+        let mut params = vec![];
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    let error = self.error(
+                        self.peek(),
+                        &format!("Can't have more than {} parameters.", MAX_ARGUMENTS),
+                    );
+                    self.errors.push(error);
+                }
+                params.push(self.consume_name("Expect parameter name.")?);
+                if !self.r#match(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        let body = self.block()?;
+
+        Ok(Rc::new(FunctionDecl {
+            name,
+            params,
+            body,
+            doc,
+        }))
+    }
+
+    fn var_declaration(&mut self, doc: Option<String>) -> ParseResult<Stmt> {
+        let name = self.consume_name("Expect variable name.")?;
+
+        let initializer = if self.r#match(&[TokenType::Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+        Ok(Stmt::Var {
+            name,
+            initializer,
+            doc,
+        })
+    }
+
+    fn statement(&mut self) -> ParseResult<Stmt> {
+        if self.r#match(&[TokenType::For]) {
+            return self.for_statement();
+        }
+        if self.r#match(&[TokenType::If]) {
+            return self.if_statement();
+        }
+        if self.r#match(&[TokenType::Print]) {
+            return self.print_statement();
+        }
+        if self.r#match(&[TokenType::Return]) {
+            return self.return_statement();
+        }
+        if self.r#match(&[TokenType::While]) {
+            return self.while_statement();
+        }
+        if self.r#match(&[TokenType::Break]) {
+            let keyword = name(self.previous());
+            self.consume(TokenType::Semicolon, "Expect ';' after 'break'.")?;
+            return Ok(Stmt::Break { keyword });
+        }
+        if self.r#match(&[TokenType::Continue]) {
+            let keyword = name(self.previous());
+            self.consume(TokenType::Semicolon, "Expect ';' after 'continue'.")?;
+            return Ok(Stmt::Continue { keyword });
+        }
+        if self.r#match(&[TokenType::LeftBrace]) {
+            return Ok(Stmt::Block(self.block()?));
+        }
+
+        self.expression_statement()
+    }
+
+    /// A C-style `for` has no node of its own, it's desugared into a `while`
+    /// loop wrapped in a block for the initializer.
+    fn for_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+        if self.check(TokenType::Identifier) && self.check_next(TokenType::In) {
+            return self.for_in_statement();
+        }
+
+        let initializer = if self.r#match(&[TokenType::Semicolon]) {
+            None
+        } else if self.r#match(&[TokenType::Var]) {
+            Some(self.var_declaration(None)?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if self.check(TokenType::Semicolon) {
+            Expr::Literal(LiteralValue::Bool(true))
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = if self.check(TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+
+        let mut body = Stmt::While {
+            condition,
+            body: Box::new(self.statement()?),
+            increment,
+        };
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(vec![initializer, body]);
+        }
+
+        Ok(body)
+    }
+
+    /// `for (x in xs)`, once the `(` has been consumed.
+    fn for_in_statement(&mut self) -> ParseResult<Stmt> {
+        let variable = self.consume_name("Expect variable name.")?;
+        let keyword = name(self.consume(TokenType::In, "Expect 'in' after variable name.")?);
+        let iterable = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+        let body = Box::new(self.statement()?);
+        Ok(Stmt::ForIn {
+            variable,
+            keyword,
+            iterable,
+            body,
+        })
+    }
+
+    fn if_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.r#match(&[TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn print_statement(&mut self) -> ParseResult<Stmt> {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::Print(value))
+    }
+
+    fn return_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = name(self.previous());
+        let value = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return { keyword, value })
+    }
+
+    fn while_statement(&mut self) -> ParseResult<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = Box::new(self.statement()?);
+        Ok(Stmt::While {
+            condition,
+            body,
+            increment: None,
+        })
+    }
+
+    /// Parses the statements of a block whose `{` has been consumed. Errors
+    /// inside the block are recorded and skipped like top level ones.
+    fn block(&mut self) -> ParseResult<Vec<Stmt>> {
+        let mut statements = vec![];
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
+    fn expression_statement(&mut self) -> ParseResult<Stmt> {
+        let expr = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt::Expression(expr))
+    }
+
+    fn expression(&mut self) -> ParseResult<Expr> {
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> ParseResult<Expr> {
+        let expr = self.or()?;
+
+        if self.r#match(&[TokenType::Equal]) {
+            let equals = self.previous().clone();
+            let value = Box::new(self.assignment()?);
+
+            return match expr {
+                Expr::Variable(variable) => Ok(Expr::Assign { variable, value }),
+                Expr::Get { object, name } => Ok(Expr::Set {
+                    object,
+                    name,
+                    value,
+                }),
+                Expr::Index {
+                    object,
+                    index,
+                    line,
+                } => Ok(Expr::SetIndex {
+                    object,
+                    index,
+                    value,
+                    line,
+                }),
+                _ => {
+                    // Not worth synchronizing over, the parser isn't confused.
+                    let error = self.error(&equals, "Invalid assignment target.");
+                    self.errors.push(error);
+                    Ok(expr)
+                }
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.and()?;
+        while self.r#match(&[TokenType::Or]) {
+            let operator = self.operator(LogicalOp::Or);
+            let right = self.and()?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.equality()?;
+        while self.r#match(&[TokenType::And]) {
+            let operator = self.operator(LogicalOp::And);
+            let right = self.equality()?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> ParseResult<Expr> {
+        self.binary(
+            &[TokenType::BangEqual, TokenType::EqualEqual],
+            Self::comparison,
+        )
+    }
+
+    fn comparison(&mut self) -> ParseResult<Expr> {
+        self.binary(
+            &[
+                TokenType::Greater,
+                TokenType::GreaterEqual,
+                TokenType::Less,
+                TokenType::LessEqual,
+            ],
+            Self::term,
+        )
+    }
+
+    fn term(&mut self) -> ParseResult<Expr> {
+        self.binary(&[TokenType::Minus, TokenType::Plus], Self::factor)
+    }
+
+    fn factor(&mut self) -> ParseResult<Expr> {
+        self.binary(
+            &[TokenType::Slash, TokenType::Star, TokenType::Percent],
+            Self::unary,
+        )
+    }
+
+    /// Parses a left-associative chain of binary operators from `types`,
+    /// with `operand` parsing each side.
+    fn binary(
+        &mut self,
+        types: &[TokenType],
+        operand: fn(&mut Self) -> ParseResult<Expr>,
+    ) -> ParseResult<Expr> {
+        let mut expr = operand(self)?;
+        while self.r#match(types) {
+            let operator = self.operator(binary_op(self.previous().r#type));
+            let right = operand(self)?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> ParseResult<Expr> {
+        if self.r#match(&[TokenType::Bang, TokenType::Minus]) {
+            let kind = match self.previous().r#type {
+                TokenType::Bang => UnaryOp::Bang,
+                _ => UnaryOp::Minus,
+            };
+            let operator = self.operator(kind);
+            let right = self.unary()?;
+            return Ok(Expr::Unary {
+                operator,
+                right: Box::new(right),
+            });
+        }
+
+        self.call()
+    }
+
+    fn call(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.r#match(&[TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.r#match(&[TokenType::Dot]) {
+                let name = self.consume_name("Expect property name after '.'.")?;
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                };
+            } else if self.r#match(&[TokenType::LeftBracket]) {
+                let index = self.expression()?;
+                let bracket = self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    index: Box::new(index),
+                    line: bracket.line,
+                };
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> ParseResult<Expr> {
+        let mut arguments = vec![];
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let error = self.error(
+                        self.peek(),
+                        &format!("Can't have more than {} arguments.", MAX_ARGUMENTS),
+                    );
+                    self.errors.push(error);
+                }
+                arguments.push(self.expression()?);
+                if !self.r#match(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            arguments,
+            line: paren.line,
+        })
+    }
+
+    fn primary(&mut self) -> ParseResult<Expr> {
+        let token = self.peek();
+        let expr = match token.r#type {
+            TokenType::False => Expr::Literal(LiteralValue::Bool(false)),
+            TokenType::True => Expr::Literal(LiteralValue::Bool(true)),
+            TokenType::Nil => Expr::Literal(LiteralValue::Nil),
+            TokenType::Number | TokenType::String => match token.literal {
+                Literal::Float(value) => Expr::Literal(LiteralValue::Number(value)),
+                Literal::String(value) => Expr::Literal(LiteralValue::String(value)),
+                _ => unreachable!("scanner attaches literals to numbers and strings"),
+            },
+            TokenType::LeftParen => {
+                self.advance();
+                let expr = self.expression()?;
+                self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+                return Ok(Expr::Grouping(Box::new(expr)));
+            }
+            TokenType::LeftBracket => {
+                self.advance();
+                return self.list();
+            }
+            TokenType::LeftBrace => {
+                self.advance();
+                return self.map();
+            }
+            TokenType::Identifier => Expr::Variable(Variable::new(name(token))),
+            TokenType::This => Expr::This(Variable::new(name(token))),
+            TokenType::Super => {
+                let keyword = Variable::new(name(token));
+                self.advance();
+                self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+                let method = self.consume_name("Expect superclass method name.")?;
+                return Ok(Expr::Super { keyword, method });
+            }
+            _ => return Err(self.error(token, "Expect expression.")),
+        };
+        self.advance();
+        Ok(expr)
+    }
+
+    /// The rest of a list literal after its `[`. A trailing comma is
+    /// allowed, so long lists can be written one element per line.
+    fn list(&mut self) -> ParseResult<Expr> {
+        let mut elements = vec![];
+        while !self.check(TokenType::RightBracket) {
+            if elements.len() >= MAX_ELEMENTS {
+                let error = self.error(
+                    self.peek(),
+                    &format!("Can't have more than {} elements in a list.", MAX_ELEMENTS),
+                );
+                self.errors.push(error);
+            }
+            elements.push(self.expression()?);
+            if !self.r#match(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+        Ok(Expr::List(elements))
+    }
+
+    /// The rest of a map literal after its `{`. Like lists, it may end with
+    /// a trailing comma.
+    fn map(&mut self) -> ParseResult<Expr> {
+        let mut entries = vec![];
+        while !self.check(TokenType::RightBrace) {
+            if entries.len() >= MAX_ELEMENTS {
+                let error = self.error(
+                    self.peek(),
+                    &format!("Can't have more than {} entries in a map.", MAX_ELEMENTS),
+                );
+                self.errors.push(error);
+            }
+            let key = self.expression()?;
+            self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+            let value = self.expression()?;
+            entries.push((key, value));
+            if !self.r#match(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        let brace = self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+        Ok(Expr::Map {
+            entries,
+            line: brace.line,
+        })
+    }
+
+    fn operator<T>(&self, kind: T) -> Operator<T> {
+        let token = self.previous();
+        Operator {
+            kind,
+            line: token.line,
+            span: token.span,
+        }
+    }
+
+    fn consume_name(&mut self, message: &str) -> ParseResult<Name> {
+        let token = self.consume(TokenType::Identifier, message)?;
+        Ok(name(token))
+    }
+
+    /// Skips tokens until what looks like the start of the next statement, to
+    /// avoid cascading errors after a syntax error.
+    fn synchronize(&mut self) {
+        self.advance();
+
+        while !self.is_at_end() {
+            if self.previous().r#type == TokenType::Semicolon {
+                return;
+            }
+
+            match self.peek().r#type {
+                TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return
+                | TokenType::Break
+                | TokenType::Continue => return,
+                _ => {}
+            }
+
+            self.advance();
+        }
+    }
+
+    fn consume(&mut self, r#type: TokenType, message: &str) -> ParseResult<&Token<'src>> {
+        if self.check(r#type) {
+            return Ok(self.advance());
+        }
+        Err(self.error(self.peek(), message))
+    }
+
+    fn r#match(&mut self, types: &[TokenType]) -> bool {
+        for r#type in types {
+            if self.check(*r#type) {
+                self.advance();
+                return true;
+            }
+        }
+        false
+    }
+
+    fn check(&self, r#type: TokenType) -> bool {
+        !self.is_at_end() && self.peek().r#type == r#type
+    }
+
+    /// Like `check`, but for the token after the current one.
+    fn check_next(&self, r#type: TokenType) -> bool {
+        !self.is_at_end() && self.tokens[self.current + 1].r#type == r#type
+    }
+
+    fn advance(&mut self) -> &Token<'src> {
+        if !self.is_at_end() {
+            self.current += 1;
+        }
+        self.previous()
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.peek().r#type == TokenType::Eof
+    }
+
+    fn peek(&self) -> &Token<'src> {
+        &self.tokens[self.current]
+    }
+
+    fn previous(&self) -> &Token<'src> {
+        &self.tokens[self.current - 1]
+    }
+
+    fn error(&self, token: &Token<'src>, message: &str) -> ParseError {
+        ParseError {
+            message: String::from(message),
+            lexeme: String::from(token.source),
+            line: token.line,
+            column: token.start.column,
+            span: token.span,
+            at_end: token.r#type == TokenType::Eof,
+        }
+    }
+}
+
+/// The name an identifier, `this` or `super` token refers to.
+fn name(token: &Token) -> Name {
+    let symbol = match token.literal {
+        Literal::Identifier(symbol) => symbol,
+        _ => Symbol::intern(token.source),
+    };
+    Name {
+        symbol,
+        line: token.line,
+        column: token.start.column,
+        span: token.span,
+    }
+}
+
+fn binary_op(r#type: TokenType) -> BinaryOp {
+    match r#type {
+        TokenType::Plus => BinaryOp::Plus,
+        TokenType::Minus => BinaryOp::Minus,
+        TokenType::Star => BinaryOp::Star,
+        TokenType::Slash => BinaryOp::Slash,
+        TokenType::Percent => BinaryOp::Percent,
+        TokenType::Greater => BinaryOp::Greater,
+        TokenType::GreaterEqual => BinaryOp::GreaterEqual,
+        TokenType::Less => BinaryOp::Less,
+        TokenType::LessEqual => BinaryOp::LessEqual,
+        TokenType::EqualEqual => BinaryOp::EqualEqual,
+        TokenType::BangEqual => BinaryOp::BangEqual,
+        _ => unreachable!("not a binary operator: {:?}", r#type),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn parse(source: &str) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        let (tokens, errors) = Scanner::new(source).scan_tokens();
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        Parser::new(tokens).parse()
+    }
+
+    /// Parses `source` as an expression statement and prints it back.
+    fn expression(source: &str) -> String {
+        let statements = parse(&format!("{};", source)).expect("failed to parse");
+        match &statements[..] {
+            [Stmt::Expression(expr)] => expr.to_string(),
+            other => panic!("expected one expression, got {:?}", other),
+        }
+    }
+
+    /// The messages of the errors parsing `source` reports.
+    fn errors(source: &str) -> Vec<String> {
+        let errors = parse(source).expect_err("expected syntax errors");
+        errors.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn binary_operators_follow_precedence() {
+        assert_eq!(expression("1 + 2 * 3"), "(+ 1 (* 2 3))");
+        assert_eq!(expression("1 * 2 + 3"), "(+ (* 1 2) 3)");
+        assert_eq!(expression("1 < 2 == 3 >= 4"), "(== (< 1 2) (>= 3 4))");
+        assert_eq!(expression("-1 * !true"), "(* (- 1) (! true))");
+        assert_eq!(expression("(1 + 2) * 3"), "(* (group (+ 1 2)) 3)");
+    }
+
+    #[test]
+    fn binary_operators_associate_left() {
+        assert_eq!(expression("1 - 2 - 3"), "(- (- 1 2) 3)");
+        assert_eq!(expression("8 / 4 / 2"), "(/ (/ 8 4) 2)");
+        assert_eq!(expression("1 != 2 != 3"), "(!= (!= 1 2) 3)");
+    }
+
+    #[test]
+    fn unary_operators_nest() {
+        assert_eq!(expression("!!true"), "(! (! true))");
+        assert_eq!(expression("- -1"), "(- (- 1))");
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(expression("a or b and c"), "(or a (and b c))");
+        assert_eq!(expression("a and b or c"), "(or (and a b) c)");
+        assert_eq!(expression("a == b and c"), "(and (== a b) c)");
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(
+            expression("nil == false != \"s\" < 1.5"),
+            "(!= (== nil false) (< \"s\" 1.5))"
+        );
+    }
+
+    #[test]
+    fn expression_errors() {
+        assert_eq!(
+            errors("1 +;"),
+            ["[line 1:4] Error at ';': Expect expression."]
+        );
+        assert_eq!(
+            errors("(1 + 2"),
+            ["[line 1:7] Error at end: Expect ')' after expression."]
+        );
+    }
+
+    #[test]
+    fn for_loops_desugar_to_while() {
+        let statements = parse("for (var i = 0; i < 3; i = i + 1) print i;").unwrap();
+        let [Stmt::Block(block)] = &statements[..] else {
+            panic!("expected a block, got {:?}", statements);
+        };
+        let [Stmt::Var { name, .. }, Stmt::While {
+            condition,
+            body,
+            increment: Some(increment),
+        }] = &block[..]
+        else {
+            panic!("expected a declaration and a loop, got {:?}", block);
+        };
+        assert_eq!(name.symbol.as_str(), "i");
+        assert_eq!(condition.to_string(), "(< i 3)");
+        assert_eq!(increment.to_string(), "(= i (+ i 1))");
+        assert!(matches!(**body, Stmt::Print(_)));
+
+        // Every clause can be left out.
+        let statements = parse("for (;;) {}").unwrap();
+        assert!(matches!(
+            &statements[..],
+            [Stmt::While {
+                condition: Expr::Literal(LiteralValue::Bool(true)),
+                increment: None,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn else_binds_to_the_nearest_if() {
+        let statements = parse("if (a) if (b) print 1; else print 2;").unwrap();
+        let [Stmt::If {
+            then_branch,
+            else_branch: None,
+            ..
+        }] = &statements[..]
+        else {
+            panic!("expected an if without else, got {:?}", statements);
+        };
+        assert!(matches!(
+            **then_branch,
+            Stmt::If {
+                else_branch: Some(_),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn declarations() {
+        let statements = parse(
+            "var a; fun f(x, y) { return x; } class B < A { m() {} } { print a; } while (a) a = nil;",
+        )
+        .unwrap();
+        assert!(matches!(
+            statements[0],
+            Stmt::Var {
+                initializer: None,
+                ..
+            }
+        ));
+        let Stmt::Function(function) = &statements[1] else {
+            panic!("expected a function, got {:?}", statements[1]);
+        };
+        assert_eq!(function.params.len(), 2);
+        assert!(matches!(
+            function.body[..],
+            [Stmt::Return { value: Some(_), .. }]
+        ));
+        let Stmt::Class(class) = &statements[2] else {
+            panic!("expected a class, got {:?}", statements[2]);
+        };
+        assert_eq!(class.superclass.as_ref().unwrap().name.symbol.as_str(), "A");
+        assert_eq!(class.methods.len(), 1);
+        assert!(matches!(statements[3], Stmt::Block(_)));
+        assert!(matches!(
+            statements[4],
+            Stmt::While {
+                increment: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn reports_every_syntax_error() {
+        let source = "var = 1;\nprint 2;\nvar b = ;\nfun f( {}\nprint 3\nclass C {}";
+        assert_eq!(
+            errors(source),
+            [
+                "[line 1:5] Error at '=': Expect variable name.",
+                "[line 3:9] Error at ';': Expect expression.",
+                "[line 4:8] Error at '{': Expect parameter name.",
+                "[line 6:1] Error at 'class': Expect ';' after value.",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_assignment_target_does_not_resynchronize() {
+        assert_eq!(
+            errors("1 = 2; a + b = c; print ;"),
+            [
+                "[line 1:3] Error at '=': Invalid assignment target.",
+                "[line 1:14] Error at '=': Invalid assignment target.",
+                "[line 1:25] Error at ';': Expect expression.",
+            ]
+        );
+    }
+
+    #[test]
+    fn doc_comments_attach_to_the_next_declaration() {
+        let source =
+            "/// First line.\n/// Second line.\nfun f() {}\n/// A class.\nclass C {}\nvar v;";
+        let statements = parse(source).unwrap();
+        let Stmt::Function(function) = &statements[0] else {
+            panic!("expected a function, got {:?}", statements[0]);
+        };
+        assert_eq!(function.doc.as_deref(), Some("First line.\nSecond line."));
+        let Stmt::Class(class) = &statements[1] else {
+            panic!("expected a class, got {:?}", statements[1]);
+        };
+        assert_eq!(class.doc.as_deref(), Some("A class."));
+        assert!(matches!(statements[2], Stmt::Var { doc: None, .. }));
+    }
+}
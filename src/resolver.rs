@@ -0,0 +1,522 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::{ClassDecl, Expr, FunctionDecl, Name, Stmt, Variable};
+use crate::source_map::Span;
+use crate::symbol::{Symbol, INIT, SUPER, THIS};
+
+/// A mistake found by the resolver before the program runs.
+#[derive(Debug, Clone)]
+pub struct ResolveError {
+    pub message: String,
+    pub lexeme: String,
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}:{}] Error at '{}': {}",
+            self.line, self.column, self.lexeme, self.message
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// Static pass run between parsing and interpreting. It works out which
+/// declaration every variable use refers to, storing the distance in scopes
+/// on the `Variable`, and reports mistakes that don't need running the code
+/// to find.
+///
+/// Only block, function and class scopes are tracked. Anything not found in
+/// them is assumed to be a global and looked up dynamically.
+pub struct Resolver {
+    // Innermost scope last. A variable maps to whether its initializer has
+    // finished, so it can't be read in its own initializer.
+    scopes: Vec<HashMap<Symbol, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    // Whether `break` and `continue` have a loop to act on. A function
+    // declared inside a loop starts outside of one.
+    in_loop: bool,
+    errors: Vec<ResolveError>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            scopes: vec![],
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            in_loop: false,
+            errors: vec![],
+        }
+    }
+
+    /// Resolves a whole program, returning every error found if there are
+    /// any.
+    pub fn resolve(mut self, statements: &[Stmt]) -> Result<(), Vec<ResolveError>> {
+        self.resolve_statements(statements);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn resolve_statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.resolve_statement(statement);
+        }
+    }
+
+    fn resolve_statement(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Expression(expr) | Stmt::Print(expr) => self.resolve_expr(expr),
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::Block(statements) => {
+                self.begin_scope();
+                self.resolve_statements(statements);
+                self.end_scope();
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.resolve_expr(condition);
+                self.resolve_statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_statement(else_branch);
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                self.resolve_expr(condition);
+                self.resolve_loop_body(body);
+                if let Some(increment) = increment {
+                    self.resolve_expr(increment);
+                }
+            }
+            Stmt::ForIn {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                self.resolve_expr(iterable);
+                // Mirrors the environment the interpreter creates for each
+                // pass, holding the variable.
+                self.begin_scope();
+                self.declare(variable);
+                self.define(variable);
+                self.resolve_loop_body(body);
+                self.end_scope();
+            }
+            Stmt::Break { keyword } => {
+                if !self.in_loop {
+                    self.error(keyword, "Can't use 'break' outside of a loop.");
+                }
+            }
+            Stmt::Continue { keyword } => {
+                if !self.in_loop {
+                    self.error(keyword, "Can't use 'continue' outside of a loop.");
+                }
+            }
+            Stmt::Function(function) => {
+                // Declared before the body so the function can call itself.
+                self.declare(&function.name);
+                self.define(&function.name);
+                self.resolve_function(function, FunctionType::Function);
+            }
+            Stmt::Return { keyword, value } => {
+                if self.current_function == FunctionType::None {
+                    self.error(keyword, "Can't return from top-level code.");
+                }
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(keyword, "Can't return a value from an initializer.");
+                    }
+                    self.resolve_expr(value);
+                }
+            }
+            Stmt::Class(class) => self.resolve_class(class),
+        }
+    }
+
+    fn resolve_class(&mut self, class: &ClassDecl) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        self.declare(&class.name);
+        self.define(&class.name);
+
+        if let Some(superclass) = &class.superclass {
+            if superclass.name.symbol == class.name.symbol {
+                self.error(&superclass.name, "A class can't inherit from itself.");
+            }
+            self.current_class = ClassType::Subclass;
+            self.resolve_variable(superclass);
+
+            // Mirrors the extra environment the interpreter creates for `super`.
+            self.begin_scope();
+            self.define_symbol(*SUPER);
+        }
+
+        // And the one methods get when they're bound to an instance.
+        self.begin_scope();
+        self.define_symbol(*THIS);
+
+        for method in &class.methods {
+            let function_type = if method.name.symbol == *INIT {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, function_type);
+        }
+
+        self.end_scope();
+        if class.superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+    }
+
+    fn resolve_loop_body(&mut self, body: &Stmt) {
+        let enclosing_loop = std::mem::replace(&mut self.in_loop, true);
+        self.resolve_statement(body);
+        self.in_loop = enclosing_loop;
+    }
+
+    fn resolve_function(&mut self, function: &FunctionDecl, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+        let enclosing_loop = std::mem::replace(&mut self.in_loop, false);
+
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_statements(&function.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+        self.in_loop = enclosing_loop;
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(_) => {}
+            Expr::Grouping(expr) => self.resolve_expr(expr),
+            Expr::Unary { right, .. } => self.resolve_expr(right),
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Variable(variable) => {
+                let name = &variable.name;
+                if let Some(false) = self.scopes.last().and_then(|scope| scope.get(&name.symbol)) {
+                    self.error(name, "Can't read local variable in its own initializer.");
+                }
+                self.resolve_variable(variable);
+            }
+            Expr::Assign { variable, value } => {
+                self.resolve_expr(value);
+                self.resolve_variable(variable);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expr(callee);
+                for argument in arguments {
+                    self.resolve_expr(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.resolve_expr(object),
+            Expr::Set { object, value, .. } => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+            }
+            Expr::List(elements) => {
+                for element in elements {
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.resolve_expr(key);
+                    self.resolve_expr(value);
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+                self.resolve_expr(value);
+            }
+            Expr::This(keyword) => {
+                if self.current_class == ClassType::None {
+                    self.error(&keyword.name, "Can't use 'this' outside of a class.");
+                    return;
+                }
+                self.resolve_variable(keyword);
+            }
+            Expr::Super { keyword, .. } => {
+                match self.current_class {
+                    ClassType::None => {
+                        self.error(&keyword.name, "Can't use 'super' outside of a class.")
+                    }
+                    ClassType::Class => self.error(
+                        &keyword.name,
+                        "Can't use 'super' in a class with no superclass.",
+                    ),
+                    ClassType::Subclass => {}
+                }
+                self.resolve_variable(keyword);
+            }
+        }
+    }
+
+    /// Records how far out the variable was declared, if it's a local.
+    fn resolve_variable(&mut self, variable: &Variable) {
+        let depth = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&variable.name.symbol));
+        variable.depth.set(depth);
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Name) {
+        let scope = match self.scopes.last_mut() {
+            Some(scope) => scope,
+            // Globals can be redeclared freely.
+            None => return,
+        };
+        if scope.insert(name.symbol, false).is_some() {
+            self.error(name, "Already a variable with this name in this scope.");
+        }
+    }
+
+    fn define(&mut self, name: &Name) {
+        self.define_symbol(name.symbol);
+    }
+
+    fn define_symbol(&mut self, symbol: Symbol) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(symbol, true);
+        }
+    }
+
+    fn error(&mut self, name: &Name, message: &str) {
+        self.errors.push(ResolveError {
+            message: String::from(message),
+            lexeme: String::from(name.symbol.as_str()),
+            line: name.line,
+            column: name.column,
+            span: name.span,
+        });
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Resolver::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let (tokens, errors) = Scanner::new(source).scan_tokens();
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        Parser::new(tokens).parse().expect("failed to parse")
+    }
+
+    /// The errors resolving `source` reports.
+    fn errors(source: &str) -> Vec<String> {
+        match Resolver::new().resolve(&parse(source)) {
+            Ok(()) => vec![],
+            Err(errors) => errors.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    /// The depth resolved for the variable printed by the innermost `print`.
+    fn printed_depth(statements: &[Stmt]) -> Option<usize> {
+        for statement in statements {
+            match statement {
+                Stmt::Print(Expr::Variable(variable)) => return variable.depth.get(),
+                Stmt::Block(statements) => return printed_depth(statements),
+                Stmt::Function(function) => return printed_depth(&function.body),
+                _ => {}
+            }
+        }
+        panic!("no print statement in {:?}", statements)
+    }
+
+    fn depth(source: &str) -> Option<usize> {
+        let statements = parse(source);
+        Resolver::new()
+            .resolve(&statements)
+            .expect("failed to resolve");
+        printed_depth(&statements)
+    }
+
+    #[test]
+    fn resolves_locals_to_their_scope_distance() {
+        assert_eq!(depth("{ var a; print a; }"), Some(0));
+        assert_eq!(depth("{ var a; { { print a; } } }"), Some(2));
+        assert_eq!(depth("{ var a; fun f() { print a; } }"), Some(1));
+        assert_eq!(depth("fun f(a) { print a; }"), Some(0));
+    }
+
+    #[test]
+    fn leaves_globals_unresolved() {
+        assert_eq!(depth("var a; print a;"), None);
+        assert_eq!(depth("{ print a; } var a;"), None);
+    }
+
+    #[test]
+    fn local_read_in_its_own_initializer() {
+        assert_eq!(
+            errors("{ var a = a; }"),
+            ["[line 1:11] Error at 'a': Can't read local variable in its own initializer."]
+        );
+        // Globals are looked up when the code runs, so this is fine.
+        assert!(errors("var a = 1; var a = a;").is_empty());
+    }
+
+    #[test]
+    fn local_redeclared_in_the_same_scope() {
+        assert_eq!(
+            errors("{ var a; var a; }"),
+            ["[line 1:14] Error at 'a': Already a variable with this name in this scope."]
+        );
+        assert_eq!(
+            errors("fun f(a, a) {}"),
+            ["[line 1:10] Error at 'a': Already a variable with this name in this scope."]
+        );
+        assert!(errors("{ var a; { var a; } }").is_empty());
+    }
+
+    #[test]
+    fn return_at_top_level() {
+        assert_eq!(
+            errors("return 1;"),
+            ["[line 1:1] Error at 'return': Can't return from top-level code."]
+        );
+    }
+
+    #[test]
+    fn return_value_from_initializer() {
+        assert_eq!(
+            errors("class A { init() { return 1; } }"),
+            ["[line 1:20] Error at 'return': Can't return a value from an initializer."]
+        );
+        assert!(errors("class A { init() { return; } }").is_empty());
+    }
+
+    #[test]
+    fn this_outside_a_class() {
+        assert_eq!(
+            errors("fun f() { print this; }"),
+            ["[line 1:17] Error at 'this': Can't use 'this' outside of a class."]
+        );
+    }
+
+    #[test]
+    fn super_without_a_superclass() {
+        assert_eq!(
+            errors("super.m();"),
+            ["[line 1:1] Error at 'super': Can't use 'super' outside of a class."]
+        );
+        assert_eq!(
+            errors("class A { m() { super.m(); } }"),
+            ["[line 1:17] Error at 'super': Can't use 'super' in a class with no superclass."]
+        );
+    }
+
+    #[test]
+    fn class_inheriting_from_itself() {
+        assert_eq!(
+            errors("class A < A {}"),
+            ["[line 1:11] Error at 'A': A class can't inherit from itself."]
+        );
+    }
+
+    #[test]
+    fn break_and_continue_outside_a_loop() {
+        assert_eq!(
+            errors("break;"),
+            ["[line 1:1] Error at 'break': Can't use 'break' outside of a loop."]
+        );
+        assert_eq!(
+            errors("if (true) continue;"),
+            ["[line 1:11] Error at 'continue': Can't use 'continue' outside of a loop."]
+        );
+        // A function body starts outside of any loop around it.
+        assert_eq!(
+            errors("while (true) { fun f() { break; } }"),
+            ["[line 1:26] Error at 'break': Can't use 'break' outside of a loop."]
+        );
+        assert!(errors("while (true) { { break; } }").is_empty());
+        assert!(errors("for (x in []) { if (x) continue; }").is_empty());
+        assert!(errors("for (;;) { fun f() { while (true) break; } break; }").is_empty());
+    }
+
+    #[test]
+    fn reports_every_error() {
+        assert_eq!(errors("return;\nprint this;\n{ var a; var a; }").len(), 3);
+    }
+}
@@ -1,18 +1,26 @@
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
 use std::iter::Peekable;
 use std::str::Chars;
 
+use crate::source_map::{Position, SourceMap, Span};
+use crate::symbol::Symbol;
+
 lazy_static! {
     static ref KEYWORDS: HashMap<&'static str, TokenType> = {
         let mut m = HashMap::new();
         m.insert("and", TokenType::And);
+        m.insert("break", TokenType::Break);
         m.insert("class", TokenType::Class);
+        m.insert("continue", TokenType::Continue);
         m.insert("else", TokenType::Else);
         m.insert("false", TokenType::False);
         m.insert("for", TokenType::For);
         m.insert("fun", TokenType::Fun);
         m.insert("if", TokenType::If);
+        m.insert("in", TokenType::In);
         m.insert("nil", TokenType::Nil);
         m.insert("or", TokenType::Or);
         m.insert("print", TokenType::Print);
@@ -26,20 +34,24 @@ lazy_static! {
     };
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum TokenType {
     // Single-character tokens.
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
     Dot,
     Minus,
     Plus,
     Semicolon,
     Slash,
     Star,
+    Percent,
 
     // One or two character tokens.
     Bang,
@@ -56,14 +68,20 @@ pub enum TokenType {
     String,
     Number,
 
+    // Comments that are kept in the token stream.
+    DocComment,
+
     // Keywords.
     And,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     Fun,
     For,
     If,
+    In,
     Nil,
     Or,
     Print,
@@ -77,72 +95,240 @@ pub enum TokenType {
     Eof,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 pub enum Literal {
     Empty,
-    String(String),
+    String(Symbol),
     Float(f64),
+    Identifier(Symbol),
+}
+
+/// `line` is the line the token starts on. `span` is the token's byte range
+/// in the source and `start`/`end` the matching positions, where `end` is the
+/// position just past the last character.
+#[derive(Debug, Clone)]
+pub struct Token<'src> {
+    pub r#type: TokenType,
+    pub source: &'src str,
+    pub literal: Literal,
+    pub line: usize,
+    pub span: Span,
+    pub start: Position,
+    pub end: Position,
+    pub leading_trivia: Vec<Trivia<'src>>,
+    pub trailing_trivia: Vec<Trivia<'src>>,
+}
+
+impl<'src> Token<'src> {
+    /// The token's text together with its trivia. Concatenating this for
+    /// every token scanned with trivia preserved reproduces the source.
+    pub fn full_text(&self) -> String {
+        let mut text = String::new();
+        for trivia in &self.leading_trivia {
+            text.push_str(trivia.text);
+        }
+        text.push_str(self.source);
+        for trivia in &self.trailing_trivia {
+            text.push_str(trivia.text);
+        }
+        text
+    }
+
+    /// Text of a `///` comment after the slashes and one optional space.
+    pub fn doc_text(&self) -> Option<&'src str> {
+        if !matches!(self.r#type, TokenType::DocComment) {
+            return None;
+        }
+        let text = self.source[3..].trim_end_matches('\r');
+        Some(text.strip_prefix(' ').unwrap_or(text))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
+    // Text that failed to scan, kept so the source can still be rebuilt.
+    Skipped,
+}
+
+/// Source text that isn't part of any token. Only collected when the scanner
+/// is asked to preserve trivia.
+#[derive(Debug, Clone)]
+pub struct Trivia<'src> {
+    pub kind: TriviaKind,
+    pub text: &'src str,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexErrorKind {
+    UnexpectedCharacter(char),
+    UnterminatedString,
+    InvalidNumber(&'static str),
+    InvalidEscape(char),
+    InvalidUnicodeEscape(&'static str),
+    UnterminatedComment,
+}
+
+impl fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexErrorKind::UnexpectedCharacter(c) => write!(f, "Unexpected character '{}'.", c),
+            LexErrorKind::UnterminatedString => write!(f, "Unterminated string."),
+            LexErrorKind::UnterminatedComment => write!(f, "Unterminated block comment."),
+            LexErrorKind::InvalidNumber(reason) => {
+                write!(f, "Invalid number literal: {}.", reason)
+            }
+            LexErrorKind::InvalidEscape(c) => write!(f, "Invalid escape sequence '\\{}'.", c),
+            LexErrorKind::InvalidUnicodeEscape(reason) => {
+                write!(f, "Invalid unicode escape: {}.", reason)
+            }
+        }
+    }
+}
+
+/// An error found while scanning. `line` and `column` point at the first
+/// character of the offending lexeme, `span` covers all of it.
+#[derive(Debug, Clone)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
 }
 
-#[derive(Debug)]
-pub struct Token {
-    r#type: TokenType,
-    source: String,
-    literal: Literal,
-    line: usize,
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}:{}] Error: {}",
+            self.line, self.column, self.kind
+        )
+    }
 }
 
 pub struct Scanner<'a> {
     source: &'a str,
-    tokens: Vec<Token>,
+    // Scanned tokens and errors, in source order, not yet handed out.
+    queue: VecDeque<Result<Token<'a>, LexError>>,
+    // Whether the `Eof` token has been handed out.
+    finished: bool,
     start: usize,
     current: usize,
-    line: usize,
+    // Gives the line and column of every token and error.
+    source_map: SourceMap<'a>,
     chars: Peekable<Chars<'a>>,
+    preserve_trivia: bool,
+    // Trivia waiting to lead the next token.
+    pending_trivia: Vec<Trivia<'a>>,
+    // Whether trivia still trails the last token, i.e. no newline since it.
+    in_trailing_trivia: bool,
 }
 
 impl<'a> Scanner<'a> {
     pub fn new(source: &'a str) -> Self {
         Scanner {
             source,
-            tokens: vec![],
+            queue: VecDeque::new(),
+            finished: false,
             start: 0,
             current: 0,
-            line: 1,
+            source_map: SourceMap::new(source),
             chars: source.chars().peekable(),
+            preserve_trivia: false,
+            pending_trivia: vec![],
+            in_trailing_trivia: false,
+        }
+    }
+
+    /// Keeps whitespace and comments as trivia on the surrounding tokens. Trivia
+    /// up to the end of a token's line trails it, anything else leads the next
+    /// token.
+    pub fn preserve_trivia(mut self) -> Self {
+        self.preserve_trivia = true;
+        self
+    }
+
+    /// Scans the whole source, returning every token along with every error
+    /// encountered. Scanning carries on past errors so they can all be
+    /// reported in one go.
+    pub fn scan_tokens(self) -> (Vec<Token<'a>>, Vec<LexError>) {
+        let mut tokens = vec![];
+        let mut errors = vec![];
+        for item in self {
+            match item {
+                Ok(token) => tokens.push(token),
+                Err(error) => errors.push(error),
+            }
         }
+        (tokens, errors)
     }
 
-    pub fn scan_tokens(mut self) -> Vec<Token> {
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.scan_token();
+    /// Scans the next lexeme, which may or may not produce a token.
+    fn scan_lexeme(&mut self) {
+        self.start = self.current;
+        let queue_len = self.queue.len();
+        self.scan_token();
+        if self.preserve_trivia {
+            let added_token =
+                self.queue.len() > queue_len && matches!(self.queue.back(), Some(Ok(_)));
+            self.attach_trivia(added_token);
         }
+    }
 
-        // TODO: use add_token
-        self.tokens.push(Token {
+    /// Whether the item at the front of the queue can be handed out. When
+    /// preserving trivia, the latest token has to wait until its trailing
+    /// trivia is complete, and so does anything queued after it.
+    fn front_is_ready(&mut self) -> bool {
+        if self.queue.is_empty() {
+            return false;
+        }
+        if !self.preserve_trivia || !self.in_trailing_trivia || self.is_at_end() {
+            return true;
+        }
+        self.queue.iter().skip(1).any(|item| item.is_ok())
+    }
+
+    /// Built here rather than by `add_token`, since it's handed out once
+    /// scanning is over instead of joining the queue.
+    fn eof_token(&mut self) -> Token<'a> {
+        let position = self.source_map.position(self.current);
+        Token {
             r#type: TokenType::Eof,
-            source: String::from(""),
+            source: "",
             literal: Literal::Empty,
-            line: self.line,
-        });
-
-        self.tokens
+            line: position.line,
+            span: Span::new(self.current, self.current),
+            start: position,
+            end: position,
+            leading_trivia: std::mem::take(&mut self.pending_trivia),
+            trailing_trivia: vec![],
+        }
     }
 
-    pub fn scan_token(&mut self) {
-        let c = self.advance().unwrap(); // TODO: handle None
+    fn scan_token(&mut self) {
+        // `next` only scans while there's source left, but stay safe anyway.
+        let Some(c) = self.advance() else {
+            return;
+        };
         match c {
             '(' => self.add_token(TokenType::LeftParen, Literal::Empty),
             ')' => self.add_token(TokenType::RightParen, Literal::Empty),
             '{' => self.add_token(TokenType::LeftBrace, Literal::Empty),
             '}' => self.add_token(TokenType::RightBrace, Literal::Empty),
+            '[' => self.add_token(TokenType::LeftBracket, Literal::Empty),
+            ']' => self.add_token(TokenType::RightBracket, Literal::Empty),
             ',' => self.add_token(TokenType::Comma, Literal::Empty),
+            ':' => self.add_token(TokenType::Colon, Literal::Empty),
             '.' => self.add_token(TokenType::Dot, Literal::Empty),
             '-' => self.add_token(TokenType::Minus, Literal::Empty),
             '+' => self.add_token(TokenType::Plus, Literal::Empty),
             ';' => self.add_token(TokenType::Semicolon, Literal::Empty),
             '*' => self.add_token(TokenType::Star, Literal::Empty),
+            '%' => self.add_token(TokenType::Percent, Literal::Empty),
 
             // Multiple char operators
             '!' => {
@@ -191,6 +377,9 @@ impl<'a> Scanner<'a> {
             }
             '/' => {
                 if self.r#match('/') {
+                    // `///` starts a doc comment, but `////` is a plain comment again.
+                    let is_doc = self.peek() == Some('/') && self.peek_next() != Some('/');
+
                     // this is a comment, ignore the rest of the line
                     while let Some(c) = self.peek() {
                         // We don't need to check for isAtEnd because peek returns None when we're at the end.
@@ -199,6 +388,12 @@ impl<'a> Scanner<'a> {
                         };
                         self.advance();
                     }
+
+                    if is_doc {
+                        self.handle_doc_comment();
+                    }
+                } else if self.r#match('*') {
+                    self.handle_block_comment();
                 } else {
                     self.add_token(TokenType::Slash, Literal::Empty);
                 }
@@ -208,7 +403,7 @@ impl<'a> Scanner<'a> {
             ' ' => {}
             '\r' => {}
             '\t' => {}
-            '\n' => self.line += 1,
+            '\n' => {}
 
             // String literals
             '"' => {
@@ -216,64 +411,251 @@ impl<'a> Scanner<'a> {
             }
 
             c => {
-                if c.is_numeric() {
+                if c.is_ascii_digit() {
                     self.handle_number();
-                } else if c.is_alphabetic() {
+                } else if c.is_alphabetic() || c == '_' {
                     self.handle_identifier();
                 } else {
-                    // panic!("Unexpected character")
+                    self.error(LexErrorKind::UnexpectedCharacter(c));
                 }
             }
         }
     }
 
+    /// Skips a `/* ... */` comment, whose opening `/*` has already been
+    /// consumed. Block comments nest, so `/* a /* b */ c */` is one comment.
+    fn handle_block_comment(&mut self) {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.advance() {
+                Some('/') if self.r#match('*') => depth += 1,
+                Some('*') if self.r#match('/') => depth -= 1,
+                Some(_) => {}
+                None => {
+                    self.error(LexErrorKind::UnterminatedComment);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Emits the `///` comment just scanned as a token so documentation
+    /// tooling can pick it up.
+    fn handle_doc_comment(&mut self) {
+        self.add_token(TokenType::DocComment, Literal::Empty);
+    }
+
     fn handle_string(&mut self) {
+        // Only strings with escapes need decoding into a new buffer, the rest
+        // are interned straight from the source.
+        let mut decoded: Option<String> = None;
+        let mut valid = true;
         while let Some(c) = self.peek() {
             if c == '"' {
                 break;
             }
-            if c == '\n' {
-                self.line += 1
-            }
+            let escape_start = self.current;
             self.advance();
+            if c != '\\' {
+                if let Some(value) = decoded.as_mut() {
+                    value.push(c);
+                }
+                continue;
+            }
+            let value = decoded
+                .get_or_insert_with(|| String::from(&self.source[self.start + 1..escape_start]));
+            match self.handle_escape() {
+                Ok(Some(decoded)) => value.push(decoded),
+                // Hit the end of input, reported as unterminated below.
+                Ok(None) => {}
+                Err(kind) => {
+                    self.error_at(kind, escape_start);
+                    valid = false;
+                }
+            }
         }
 
         if self.is_at_end() {
-            panic!("Unterminated string")
+            self.error(LexErrorKind::UnterminatedString);
+            return;
         }
 
         // Ending `"`
         self.advance();
 
-        let slice_start = self.start + 1;
-        let slice_end = self.current - 1;
-        let value = &self.source[slice_start..slice_end];
+        if valid {
+            let symbol = match decoded {
+                Some(value) => Symbol::intern(&value),
+                None => Symbol::intern(&self.source[self.start + 1..self.current - 1]),
+            };
+            self.add_token(TokenType::String, Literal::String(symbol))
+        }
+    }
 
-        self.add_token(TokenType::String, Literal::String(String::from(value)))
+    /// Decodes the escape sequence following a `\`, which has already been
+    /// consumed. Returns `None` if the input ends first.
+    fn handle_escape(&mut self) -> Result<Option<char>, LexErrorKind> {
+        let c = match self.advance() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let decoded = match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            'u' => return self.handle_unicode_escape().map(Some),
+            c => return Err(LexErrorKind::InvalidEscape(c)),
+        };
+        Ok(Some(decoded))
     }
 
-    fn handle_number(&mut self) {
+    /// Decodes the `{XXXX}` part of a `\u{XXXX}` escape.
+    fn handle_unicode_escape(&mut self) -> Result<char, LexErrorKind> {
+        if !self.r#match('{') {
+            return Err(LexErrorKind::InvalidUnicodeEscape("expected '{'"));
+        }
+
+        let digits_start = self.current;
         while let Some(c) = self.peek() {
-            if !c.is_numeric() {
+            if !c.is_ascii_hexdigit() {
                 break;
             }
             self.advance();
         }
+        let digits = &self.source[digits_start..self.current];
 
-        if let Some('.') = self.peek() {
-            todo!("Numbers with decimals need a peekable with 2 or more lookahead items");
+        if !self.r#match('}') {
+            return Err(LexErrorKind::InvalidUnicodeEscape("expected '}'"));
+        }
+        if digits.is_empty() || digits.len() > 6 {
+            return Err(LexErrorKind::InvalidUnicodeEscape(
+                "expected 1 to 6 hex digits",
+            ));
         }
 
-        let str_literal = &self.source[self.start..self.current];
-        self.add_token(
-            TokenType::Number,
-            Literal::Float(str_literal.parse::<f64>().unwrap()), // TODO: handle error
-        )
+        // At most 6 hex digits, so this always fits in a u32.
+        let code_point = u32::from_str_radix(digits, 16).unwrap();
+        char::from_u32(code_point)
+            .ok_or(LexErrorKind::InvalidUnicodeEscape("not a valid code point"))
+    }
+
+    fn handle_number(&mut self) {
+        // The first digit has already been consumed.
+        let first = self.source[self.start..].chars().next();
+        let radix = match (first, self.peek()) {
+            (Some('0'), Some('x' | 'X')) => 16,
+            (Some('0'), Some('o' | 'O')) => 8,
+            (Some('0'), Some('b' | 'B')) => 2,
+            _ => 10,
+        };
+
+        if radix == 10 {
+            self.handle_decimal();
+        } else {
+            // Skip the prefix
+            self.advance();
+            self.handle_radix(radix);
+        }
+    }
+
+    fn handle_decimal(&mut self) {
+        self.consume_digits(10);
+
+        if self.peek() == Some('.') {
+            match self.peek_next() {
+                Some(c) if c.is_ascii_digit() => {
+                    self.advance();
+                    self.consume_digits(10);
+                }
+                // `1.foo` is a number followed by a property access.
+                Some(c) if c.is_alphabetic() || c == '_' => {}
+                _ => {
+                    self.advance();
+                    return self.number_error("expected digits after '.'");
+                }
+            }
+        }
+
+        if let Some('e' | 'E') = self.peek() {
+            self.advance();
+            if let Some('+' | '-') = self.peek() {
+                self.advance();
+            }
+            if self.consume_digits(10) == 0 {
+                return self.number_error("expected digits in exponent");
+            }
+        }
+
+        if self.at_number_suffix() {
+            return self.number_error("unexpected character in number");
+        }
+
+        let text = without_separators(&self.source[self.start..self.current]);
+        match text.parse::<f64>() {
+            Ok(value) => self.add_token(TokenType::Number, Literal::Float(value)),
+            Err(_) => self.number_error("not a valid number"),
+        }
+    }
+
+    fn handle_radix(&mut self, radix: u32) {
+        let digits_start = self.current;
+        if self.consume_digits(radix) == 0 {
+            return self.number_error("expected digits after base prefix");
+        }
+        if self.at_number_suffix() {
+            return self.number_error("digit out of range for base");
+        }
+
+        let digits = without_separators(&self.source[digits_start..self.current]);
+        match u64::from_str_radix(&digits, radix) {
+            Ok(value) => self.add_token(TokenType::Number, Literal::Float(value as f64)),
+            Err(_) => self.number_error("number is too large"),
+        }
+    }
+
+    /// Consumes a run of digits in `radix`, allowing single `_` separators
+    /// between digits. Returns the number of digits consumed.
+    fn consume_digits(&mut self, radix: u32) -> usize {
+        let mut count = 0;
+        while let Some(c) = self.peek() {
+            if c.is_digit(radix) {
+                count += 1;
+            } else if c == '_'
+                && self.previous().is_some_and(|p| p.is_digit(radix))
+                && self.peek_next().is_some_and(|n| n.is_digit(radix))
+            {
+                // Separator between two digits
+            } else {
+                break;
+            }
+            self.advance();
+        }
+        count
+    }
+
+    /// Whether the number just scanned runs straight into something that can't
+    /// follow it, such as `1_`, `0b12` or `3px`.
+    fn at_number_suffix(&mut self) -> bool {
+        matches!(self.peek(), Some(c) if c.is_alphanumeric() || c == '_')
+    }
+
+    /// Skips the rest of a malformed number so it's reported as one error.
+    fn number_error(&mut self, reason: &'static str) {
+        while let Some(c) = self.peek() {
+            if !(c.is_alphanumeric() || c == '_' || c == '.') {
+                break;
+            }
+            self.advance();
+        }
+        self.error(LexErrorKind::InvalidNumber(reason));
     }
 
     fn handle_identifier(&mut self) {
         while let Some(c) = self.peek() {
-            if !c.is_alphanumeric() {
+            if !(c.is_alphanumeric() || c == '_') {
                 break;
             }
             self.advance();
@@ -288,11 +670,68 @@ impl<'a> Scanner<'a> {
 
             // Identifier
             None => {
-                self.add_token(TokenType::Identifier, Literal::Empty);
+                self.add_token(
+                    TokenType::Identifier,
+                    Literal::Identifier(Symbol::intern(text)),
+                );
             }
         }
     }
 
+    /// Files the lexeme just scanned as trivia if it didn't produce a token, or
+    /// hands the pending trivia to the token if it did.
+    fn attach_trivia(&mut self, added_token: bool) {
+        if added_token {
+            if let Some(Ok(token)) = self.queue.back_mut() {
+                token.leading_trivia = std::mem::take(&mut self.pending_trivia);
+            }
+            self.in_trailing_trivia = true;
+            return;
+        }
+
+        let text = &self.source[self.start..self.current];
+        let kind = if text == "\n" {
+            TriviaKind::Newline
+        } else if text.starts_with("//") {
+            TriviaKind::LineComment
+        } else if text.starts_with("/*") {
+            TriviaKind::BlockComment
+        } else if text.chars().all(char::is_whitespace) {
+            TriviaKind::Whitespace
+        } else {
+            TriviaKind::Skipped
+        };
+
+        let last_token = self
+            .queue
+            .iter_mut()
+            .rev()
+            .find_map(|item| item.as_mut().ok());
+        let trivia_list = match last_token {
+            Some(token) if self.in_trailing_trivia && kind != TriviaKind::Newline => {
+                &mut token.trailing_trivia
+            }
+            _ => &mut self.pending_trivia,
+        };
+        if kind == TriviaKind::Newline {
+            self.in_trailing_trivia = false;
+        }
+
+        // Merge runs of whitespace into a single piece of trivia.
+        if let Some(last) = trivia_list.last_mut() {
+            if kind == TriviaKind::Whitespace && last.kind == kind {
+                last.span.end = self.current;
+                last.text = &self.source[last.span.start..last.span.end];
+                return;
+            }
+        }
+        trivia_list.push(Trivia {
+            kind,
+            text,
+            span: Span::new(self.start, self.current),
+        });
+    }
+
     fn r#match(&mut self, expected: char) -> bool {
         if self.is_at_end() {
             return false;
@@ -304,7 +743,7 @@ impl<'a> Scanner<'a> {
         }
 
         self.advance();
-        return true;
+        true
     }
 
     fn is_at_end(&mut self) -> bool {
@@ -312,25 +751,429 @@ impl<'a> Scanner<'a> {
     }
 
     fn peek(&mut self) -> Option<char> {
-        match self.chars.peek() {
-            Some(c) => Some(*c),
-            None => None,
-        }
+        self.chars.peek().copied()
+    }
+
+    fn previous(&self) -> Option<char> {
+        self.source[..self.current].chars().next_back()
+    }
+
+    fn peek_next(&self) -> Option<char> {
+        self.source[self.current..].chars().nth(1)
     }
 
     fn advance(&mut self) -> Option<char> {
         let next = self.chars.next()?;
-        self.current += 1;
+        self.current += next.len_utf8();
         Some(next)
     }
 
     fn add_token(&mut self, token_type: TokenType, literal: Literal) {
-        let text = String::from(&self.source[self.start..self.current]);
-        self.tokens.push(Token {
+        let start = self.source_map.position(self.start);
+        self.queue.push_back(Ok(Token {
             r#type: token_type,
-            source: text,
+            source: &self.source[self.start..self.current],
             literal,
-            line: self.line,
-        })
+            line: start.line,
+            span: Span::new(self.start, self.current),
+            start,
+            end: self.source_map.position(self.current),
+            leading_trivia: vec![],
+            trailing_trivia: vec![],
+        }))
+    }
+
+    fn error(&mut self, kind: LexErrorKind) {
+        self.error_at(kind, self.start)
+    }
+
+    /// Reports an error covering everything from `start` up to the current
+    /// character, for problems inside a larger lexeme.
+    fn error_at(&mut self, kind: LexErrorKind, start: usize) {
+        let position = self.source_map.position(start);
+        self.queue.push_back(Err(LexError {
+            kind,
+            line: position.line,
+            column: position.column,
+            span: Span::new(start, self.current),
+        }))
+    }
+}
+
+/// Strips `_` separators from a number literal, only allocating if it has any.
+fn without_separators(text: &str) -> Cow<'_, str> {
+    if text.contains('_') {
+        Cow::Owned(text.replace('_', ""))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Produces tokens on demand, ending with a single `Eof` token. Errors are
+/// yielded in source order alongside the tokens and scanning carries on after
+/// them.
+impl<'a> Iterator for Scanner<'a> {
+    type Item = Result<Token<'a>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.front_is_ready() && !self.is_at_end() {
+            self.scan_lexeme();
+        }
+
+        if let Some(item) = self.queue.pop_front() {
+            return Some(item);
+        }
+        if self.finished {
+            return None;
+        }
+        self.finished = true;
+        Some(Ok(self.eof_token()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(source: &str) -> (Vec<Token<'_>>, Vec<LexError>) {
+        Scanner::new(source).scan_tokens()
+    }
+
+    fn types(source: &str) -> Vec<TokenType> {
+        let (tokens, errors) = scan(source);
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        tokens.iter().map(|token| token.r#type).collect()
+    }
+
+    /// The single error scanning `source` produces.
+    fn error(source: &str) -> LexErrorKind {
+        let (_, errors) = scan(source);
+        assert_eq!(errors.len(), 1, "expected one error, got {:?}", errors);
+        errors[0].kind.clone()
+    }
+
+    #[test]
+    fn reports_every_unexpected_character() {
+        let (tokens, errors) = scan("1 @ 2\n# 3");
+        let kinds: Vec<_> = errors.iter().map(|error| error.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                LexErrorKind::UnexpectedCharacter('@'),
+                LexErrorKind::UnexpectedCharacter('#')
+            ]
+        );
+        assert_eq!((errors[0].line, errors[0].column), (1, 3));
+        assert_eq!(errors[0].span, Span::new(2, 3));
+        assert_eq!((errors[1].line, errors[1].column), (2, 1));
+
+        // Scanning carries on past the bad characters.
+        let types: Vec<_> = tokens.iter().map(|token| token.r#type).collect();
+        assert_eq!(
+            types,
+            [
+                TokenType::Number,
+                TokenType::Number,
+                TokenType::Number,
+                TokenType::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_string_covers_the_rest_of_the_source() {
+        let (tokens, errors) = scan("print \"abc\ndef");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, LexErrorKind::UnterminatedString);
+        assert_eq!((errors[0].line, errors[0].column), (1, 7));
+        assert_eq!(errors[0].span, Span::new(6, 14));
+        assert_eq!(tokens.len(), 2);
+    }
+
+    #[test]
+    fn errors_display_their_position() {
+        let (_, errors) = scan("var a;\n  @");
+        assert_eq!(
+            errors[0].to_string(),
+            "[line 2:3] Error: Unexpected character '@'."
+        );
+        assert_eq!(error("\"open").to_string(), "Unterminated string.");
+    }
+
+    #[test]
+    fn clean_source_has_no_errors() {
+        assert_eq!(
+            types("var a = \"b\";"),
+            [
+                TokenType::Var,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::String,
+                TokenType::Semicolon,
+                TokenType::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn tokens_after_non_ascii_text_have_exact_positions() {
+        let (tokens, _) = scan("\"né\" + 😀\r\n  x");
+        let string = &tokens[0];
+        assert_eq!(string.source, "\"né\"");
+        assert_eq!(string.span, Span::new(0, 5));
+        assert_eq!(string.start, Position::new(1, 1));
+        assert_eq!(string.end, Position::new(1, 5));
+
+        // The emoji is skipped as an error, but still counts as one column.
+        let x = &tokens[2];
+        assert_eq!(x.source, "x");
+        assert_eq!(x.span, Span::new(16, 17));
+        assert_eq!(
+            (x.line, x.start, x.end),
+            (2, Position::new(2, 3), Position::new(2, 4))
+        );
+    }
+
+    fn number(source: &str) -> f64 {
+        let (tokens, errors) = scan(source);
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        match tokens[0].literal {
+            Literal::Float(value) => value,
+            other => panic!("expected a number, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn number_literals() {
+        assert_eq!(number("123"), 123.0);
+        assert_eq!(number("1.5"), 1.5);
+        assert_eq!(number("1_000_000"), 1_000_000.0);
+        assert_eq!(number("0x1F"), 31.0);
+        assert_eq!(number("0o17"), 15.0);
+        assert_eq!(number("0b1010_1010"), 170.0);
+        assert_eq!(number("1e3"), 1000.0);
+        assert_eq!(number("2.5E-1"), 0.25);
+        assert_eq!(number("1e+2"), 100.0);
+    }
+
+    #[test]
+    fn number_followed_by_property_access() {
+        assert_eq!(
+            types("1.foo"),
+            [
+                TokenType::Number,
+                TokenType::Dot,
+                TokenType::Identifier,
+                TokenType::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_numbers() {
+        let invalid = LexErrorKind::InvalidNumber;
+        assert_eq!(error("1."), invalid("expected digits after '.'"));
+        assert_eq!(error("0x"), invalid("expected digits after base prefix"));
+        assert_eq!(error("1__0"), invalid("unexpected character in number"));
+        assert_eq!(error("1_"), invalid("unexpected character in number"));
+        assert_eq!(error("0b102"), invalid("digit out of range for base"));
+        assert_eq!(error("1e"), invalid("expected digits in exponent"));
+        assert_eq!(error("1e+"), invalid("expected digits in exponent"));
+        assert_eq!(error("3px"), invalid("unexpected character in number"));
+        assert_eq!(
+            error("0x1_0000_0000_0000_0000"),
+            invalid("number is too large")
+        );
+    }
+
+    #[test]
+    fn malformed_number_is_one_error() {
+        let (tokens, errors) = scan("0b102 + 1");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].span, Span::new(0, 5));
+        let types: Vec<_> = tokens.iter().map(|token| token.r#type).collect();
+        assert_eq!(types, [TokenType::Plus, TokenType::Number, TokenType::Eof]);
+    }
+
+    fn string(source: &str) -> &'static str {
+        let (tokens, errors) = scan(source);
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        match tokens[0].literal {
+            Literal::String(symbol) => symbol.as_str(),
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(string(r#""plain""#), "plain");
+        assert_eq!(string(r#""a\nb\tc\r\0""#), "a\nb\tc\r\0");
+        assert_eq!(string(r#""\\ \"""#), "\\ \"");
+        assert_eq!(string(r#""\u{41}\u{e9}""#), "Aé");
+        assert_eq!(string(r#""\u{1F600}!""#), "😀!");
+        assert_eq!(string(r#""\u{10FFFF}""#), "\u{10FFFF}");
+    }
+
+    #[test]
+    fn malformed_escapes() {
+        let unicode = LexErrorKind::InvalidUnicodeEscape;
+        assert_eq!(error(r#""\q""#), LexErrorKind::InvalidEscape('q'));
+        assert_eq!(error(r#""\u41""#), unicode("expected '{'"));
+        assert_eq!(error(r#""\u{41""#), unicode("expected '}'"));
+        assert_eq!(error(r#""\u{}""#), unicode("expected 1 to 6 hex digits"));
+        assert_eq!(
+            error(r#""\u{1234567}""#),
+            unicode("expected 1 to 6 hex digits")
+        );
+        assert_eq!(error(r#""\u{D800}""#), unicode("not a valid code point"));
+        assert_eq!(error(r#""\u{110000}""#), unicode("not a valid code point"));
+        assert_eq!(error(r#""abc"#), LexErrorKind::UnterminatedString);
+    }
+
+    #[test]
+    fn escape_errors_cover_the_bad_sequence() {
+        let (tokens, errors) = scan(r#""ok \q and \u{D800}" 1"#);
+        let spans: Vec<_> = errors.iter().map(|error| error.span).collect();
+        assert_eq!(spans, [Span::new(4, 6), Span::new(11, 19)]);
+        assert_eq!(errors[1].column, 12);
+        // The string with bad escapes is dropped, scanning carries on after it.
+        assert_eq!(tokens[0].r#type, TokenType::Number);
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        assert_eq!(
+            types("/* a /* b */ c */ 1"),
+            [TokenType::Number, TokenType::Eof]
+        );
+        assert_eq!(error("/* a /* b */ c"), LexErrorKind::UnterminatedComment);
+        assert_eq!(error("/*"), LexErrorKind::UnterminatedComment);
+    }
+
+    #[test]
+    fn block_comments_keep_line_numbers() {
+        let (tokens, _) = scan("/* one\ntwo\n/* three\n*/ */ x");
+        assert_eq!(tokens[0].line, 4);
+        assert_eq!(tokens[0].start, Position::new(4, 7));
+    }
+
+    #[test]
+    fn doc_comments() {
+        let (tokens, _) = scan("/// Adds.\r\n//// Not docs.\n// Nor this.\nfun");
+        let types: Vec<_> = tokens.iter().map(|token| token.r#type).collect();
+        assert_eq!(
+            types,
+            [TokenType::DocComment, TokenType::Fun, TokenType::Eof]
+        );
+        assert_eq!(tokens[0].doc_text(), Some("Adds."));
+        assert_eq!(tokens[1].doc_text(), None);
+    }
+
+    fn round_trip(source: &str) -> String {
+        let (tokens, _) = Scanner::new(source).preserve_trivia().scan_tokens();
+        tokens.iter().map(Token::full_text).collect()
+    }
+
+    #[test]
+    fn trivia_round_trips() {
+        let sources = [
+            "",
+            "var a = 1;",
+            "  var a = 1;  // trailing\n\n/* block\n comment */ print a;\n",
+            "var a = 1;\r\n// note\r\n\r\n\tprint a;\r\n",
+            "/// doc\r\nfun f() {}\r\n",
+            "print 1 @ 2; \"unterminated\r\n",
+            "/* never closed\r\n",
+        ];
+        for source in sources {
+            assert_eq!(round_trip(source), source);
+        }
+    }
+
+    #[test]
+    fn trivia_trails_to_the_end_of_the_line() {
+        let source = "a // one\n  /* two */ b";
+        let (tokens, _) = Scanner::new(source).preserve_trivia().scan_tokens();
+        let kinds = |trivia: &[Trivia]| -> Vec<TriviaKind> {
+            trivia.iter().map(|trivia| trivia.kind).collect()
+        };
+        assert_eq!(
+            kinds(&tokens[0].trailing_trivia),
+            [TriviaKind::Whitespace, TriviaKind::LineComment]
+        );
+        assert_eq!(
+            kinds(&tokens[1].leading_trivia),
+            [
+                TriviaKind::Newline,
+                TriviaKind::Whitespace,
+                TriviaKind::BlockComment,
+                TriviaKind::Whitespace
+            ]
+        );
+        assert_eq!(tokens[1].leading_trivia[2].text, "/* two */");
+    }
+
+    #[test]
+    fn trivia_is_dropped_by_default() {
+        let (tokens, _) = scan("a // one\n b");
+        assert!(tokens
+            .iter()
+            .all(|token| token.leading_trivia.is_empty() && token.trailing_trivia.is_empty()));
+    }
+
+    #[test]
+    fn scans_lazily() {
+        let source = "var a = 1;\n".repeat(1000);
+        let mut scanner = Scanner::new(&source);
+        assert_eq!(scanner.next().unwrap().unwrap().r#type, TokenType::Var);
+        assert_eq!(scanner.next().unwrap().unwrap().source, "a");
+        // Only as much source as the tokens handed out has been read.
+        assert_eq!(scanner.current, 5);
+    }
+
+    #[test]
+    fn stopping_early_never_reaches_later_errors() {
+        let mut source = String::from("print 1;");
+        source.push_str(&"@".repeat(10_000));
+        let tokens: Vec<_> = Scanner::new(&source).take(3).collect();
+        assert!(tokens.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn yields_errors_in_order_and_ends_after_eof() {
+        let mut scanner = Scanner::new("1 @ 2");
+        assert!(matches!(scanner.next(), Some(Ok(token)) if token.source == "1"));
+        assert!(matches!(scanner.next(), Some(Err(error)) if error.column == 3));
+        assert!(matches!(scanner.next(), Some(Ok(token)) if token.source == "2"));
+        assert!(matches!(scanner.next(), Some(Ok(token)) if token.r#type == TokenType::Eof));
+        assert!(scanner.next().is_none());
+        assert!(scanner.next().is_none());
+    }
+
+    #[test]
+    fn holds_tokens_back_until_their_trailing_trivia_is_complete() {
+        let mut scanner = Scanner::new("a /* x */ // y\nb").preserve_trivia();
+        let a = scanner.next().unwrap().unwrap();
+        assert_eq!(a.trailing_trivia.len(), 4);
+        // Handing out `a` needed to read up to the newline, but no further.
+        assert_eq!(scanner.current, 15);
+    }
+
+    #[test]
+    fn tokens_borrow_their_text_from_the_source() {
+        let source = String::from("name \"text\" name");
+        let (tokens, _) = scan(&source);
+        let range = source.as_bytes().as_ptr_range();
+        assert!(tokens
+            .iter()
+            .all(|token| range.contains(&token.source.as_ptr()) || token.source.is_empty()));
+
+        match (tokens[0].literal, tokens[1].literal, tokens[2].literal) {
+            (Literal::Identifier(a), Literal::String(text), Literal::Identifier(b)) => {
+                assert_eq!(a, b);
+                assert_eq!(text.as_str(), "text");
+            }
+            other => panic!("unexpected literals {:?}", other),
+        }
     }
 }
@@ -0,0 +1,194 @@
+/// A byte range into the source text. Unlike `Range<usize>` this is `Copy`,
+/// so it can be passed around freely alongside tokens.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+/// A 1-based line and column. Columns count characters, not bytes, so they
+/// match what an editor shows for non-ASCII text.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, column: usize) -> Self {
+        Position { line, column }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position::new(1, 1)
+    }
+}
+
+/// Converts byte offsets into a source string to line/column positions.
+pub struct SourceMap<'a> {
+    source: &'a str,
+    // Byte offset at which each line starts. The first entry is always 0.
+    line_starts: Vec<usize>,
+    // Whether each line is pure ASCII, where columns are just byte offsets.
+    ascii_lines: Vec<bool>,
+}
+
+impl<'a> SourceMap<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(i, _)| i + 1));
+        let ascii_lines = source.split('\n').map(str::is_ascii).collect();
+        SourceMap {
+            source,
+            line_starts,
+            ascii_lines,
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Position of the character at `offset`. Offsets past the end of the
+    /// source are clamped to it, and offsets inside a multi-byte character are
+    /// rounded down to its start.
+    pub fn position(&self, offset: usize) -> Position {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        let line_index = match self.line_starts.binary_search(&offset) {
+            Ok(index) => index,
+            Err(index) => index - 1,
+        };
+        let line_start = self.line_starts[line_index];
+        let column = if self.ascii_lines[line_index] {
+            offset - line_start + 1
+        } else {
+            self.source[line_start..offset].chars().count() + 1
+        };
+
+        Position::new(line_index + 1, column)
+    }
+
+    /// Byte offset of `position`, or `None` if it lies outside the source.
+    /// This is the inverse of `position`, so a line's `\r` and `\n` have
+    /// offsets too.
+    pub fn offset(&self, position: Position) -> Option<usize> {
+        let line = self.raw_line(position.line)?;
+        let line_start = self.line_starts[position.line - 1];
+        if position.column == 0 {
+            return None;
+        }
+
+        let mut chars = line.char_indices().map(|(i, _)| i).chain([line.len()]);
+        chars.nth(position.column - 1).map(|i| line_start + i)
+    }
+
+    /// Text of the given 1-based line, without its line terminator.
+    pub fn line_text(&self, line: usize) -> Option<&'a str> {
+        let text = self.raw_line(line)?;
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    /// Text of the given 1-based line without its `\n`, but keeping any `\r`.
+    fn raw_line(&self, line: usize) -> Option<&'a str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.source.len(), |next| next - 1);
+        Some(&self.source[start..end])
+    }
+
+    pub fn slice(&self, span: Span) -> &'a str {
+        &self.source[span.start..span.end]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_count_characters_not_bytes() {
+        let map = SourceMap::new("é = \"😀\";\nx");
+        assert_eq!(map.position(0), Position::new(1, 1));
+        assert_eq!(map.position(2), Position::new(1, 2));
+        // `😀` starts at byte 6 and is four bytes long.
+        assert_eq!(map.position(6), Position::new(1, 6));
+        assert_eq!(map.position(10), Position::new(1, 7));
+        assert_eq!(map.position(13), Position::new(2, 1));
+    }
+
+    #[test]
+    fn offsets_inside_a_character_round_down() {
+        let map = SourceMap::new("a😀b");
+        assert_eq!(map.position(2), Position::new(1, 2));
+        assert_eq!(map.position(4), Position::new(1, 2));
+        assert_eq!(map.position(5), Position::new(1, 3));
+    }
+
+    #[test]
+    fn carriage_returns_end_lines() {
+        let map = SourceMap::new("ab\r\ncd\r\n");
+        assert_eq!(map.line_count(), 3);
+        assert_eq!(map.position(2), Position::new(1, 3));
+        assert_eq!(map.position(4), Position::new(2, 1));
+        assert_eq!(map.position(7), Position::new(2, 4));
+        assert_eq!(map.line_text(1), Some("ab"));
+        assert_eq!(map.line_text(2), Some("cd"));
+        assert_eq!(map.line_text(3), Some(""));
+        assert_eq!(map.line_text(4), None);
+        assert_eq!(map.line_text(0), None);
+    }
+
+    #[test]
+    fn offsets_past_the_end_are_clamped() {
+        let map = SourceMap::new("ab\nc");
+        assert_eq!(map.position(4), Position::new(2, 2));
+        assert_eq!(map.position(100), Position::new(2, 2));
+        assert_eq!(SourceMap::new("").position(3), Position::new(1, 1));
+    }
+
+    #[test]
+    fn offset_and_position_round_trip() {
+        let source = "var é = 1;\r\n\tprint \"😀\";\n\nend";
+        let map = SourceMap::new(source);
+        for (offset, _) in source.char_indices().chain([(source.len(), ' ')]) {
+            assert_eq!(map.offset(map.position(offset)), Some(offset));
+        }
+    }
+
+    #[test]
+    fn positions_outside_the_source_have_no_offset() {
+        let map = SourceMap::new("ab\nc");
+        assert_eq!(map.offset(Position::new(1, 3)), Some(2));
+        assert_eq!(map.offset(Position::new(1, 4)), None);
+        assert_eq!(map.offset(Position::new(1, 0)), None);
+        assert_eq!(map.offset(Position::new(3, 1)), None);
+        assert_eq!(map.slice(Span::new(3, 4)), "c");
+    }
+}
@@ -0,0 +1,229 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use crate::interpreter::RuntimeError;
+use crate::stdlib::{arity_message, integer_arg, Primitive};
+
+// The parts of lists both backends share: which methods there are, how
+// indexes resolve and how sorting compares. Each backend stores its own
+// lists and runs the methods itself, since `map` and friends call back
+// into Lox code.
+
+/// A method every list has, called like `list.push(value)`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ListMethod {
+    Push,
+    Pop,
+    Insert,
+    Remove,
+    Len,
+    Slice,
+    Sort,
+    Map,
+    Filter,
+    Reduce,
+}
+
+impl ListMethod {
+    pub fn from_name(name: &str) -> Option<ListMethod> {
+        let method = match name {
+            "push" => ListMethod::Push,
+            "pop" => ListMethod::Pop,
+            "insert" => ListMethod::Insert,
+            "remove" => ListMethod::Remove,
+            "len" => ListMethod::Len,
+            "slice" => ListMethod::Slice,
+            "sort" => ListMethod::Sort,
+            "map" => ListMethod::Map,
+            "filter" => ListMethod::Filter,
+            "reduce" => ListMethod::Reduce,
+            _ => return None,
+        };
+        Some(method)
+    }
+
+    /// The fewest and most arguments the method takes. Only `sort` has an
+    /// optional one, its comparator.
+    pub fn arity(self) -> (usize, usize) {
+        match self {
+            ListMethod::Pop | ListMethod::Len => (0, 0),
+            ListMethod::Sort => (0, 1),
+            ListMethod::Push | ListMethod::Remove | ListMethod::Map | ListMethod::Filter => (1, 1),
+            ListMethod::Insert | ListMethod::Slice | ListMethod::Reduce => (2, 2),
+        }
+    }
+
+    pub fn check_arity(self, count: usize) -> Result<(), RuntimeError> {
+        let (min, max) = self.arity();
+        if (min..=max).contains(&count) {
+            Ok(())
+        } else {
+            Err(RuntimeError::native(arity_message(min, max, count)))
+        }
+    }
+}
+
+/// Resolves an index into a list of length `len`. Negative indexes count
+/// back from the end, so -1 is the last element.
+pub fn index(index: &Primitive, len: usize) -> Result<usize, RuntimeError> {
+    let index = integer_index(index)?;
+    resolve(index, len)
+        .filter(|&resolved| resolved < len)
+        .ok_or_else(|| out_of_range(index, len))
+}
+
+/// Like `index`, but `len` itself is allowed too, for inserting at the end.
+pub fn insertion_index(index: &Primitive, len: usize) -> Result<usize, RuntimeError> {
+    let index = integer_index(index)?;
+    resolve(index, len)
+        .filter(|&resolved| resolved <= len)
+        .ok_or_else(|| out_of_range(index, len))
+}
+
+/// The elements `slice(start, end)` takes, given its arguments: from
+/// `start` up to but not including `end`. Either may be negative, and both
+/// are clamped to the list rather than failing, so `slice(0, 3)` of a
+/// shorter list is all of it.
+pub fn slice(args: &[Primitive], len: usize) -> Result<Range<usize>, RuntimeError> {
+    let clamp = |index: i64| {
+        let index = if index < 0 { index + len as i64 } else { index };
+        index.clamp(0, len as i64) as usize
+    };
+    let start = clamp(integer_arg("slice", args, 0)?);
+    let end = clamp(integer_arg("slice", args, 1)?);
+    Ok(start..end.max(start))
+}
+
+/// How `sort()` orders elements without a comparator: numbers or strings,
+/// but not a mix.
+pub fn compare(a: &Primitive, b: &Primitive) -> Result<Ordering, RuntimeError> {
+    match (a, b) {
+        (Primitive::Number(a), Primitive::Number(b)) => {
+            Ok(a.partial_cmp(b).unwrap_or(Ordering::Equal))
+        }
+        (Primitive::String(a), Primitive::String(b)) => Ok(a.cmp(b)),
+        _ => Err(RuntimeError::native(
+            "sort() can only compare two numbers or two strings without a comparator.",
+        )),
+    }
+}
+
+/// Reads what a comparator returned: a negative number if its first
+/// argument goes first, positive if the second does, and zero if either
+/// order will do.
+pub fn comparator_ordering(result: &Primitive) -> Result<Ordering, RuntimeError> {
+    match result {
+        Primitive::Number(number) => Ok(number.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+        _ => Err(RuntimeError::native("Comparator must return a number.")),
+    }
+}
+
+/// A stable merge sort with a comparison that can fail, which stops the
+/// sort. `slice::sort_by` can't be used since it may panic if a Lox
+/// comparator isn't a consistent ordering.
+pub fn sort<T: Clone, E>(
+    items: &[T],
+    compare: &mut impl FnMut(&T, &T) -> Result<Ordering, E>,
+) -> Result<Vec<T>, E> {
+    if items.len() <= 1 {
+        return Ok(items.to_vec());
+    }
+    let (left, right) = items.split_at(items.len() / 2);
+    let left = sort(left, compare)?;
+    let right = sort(right, compare)?;
+
+    let mut merged = Vec::with_capacity(items.len());
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        // Taking from the left on ties keeps equal elements in order.
+        if compare(&left[i], &right[j])? == Ordering::Greater {
+            merged.push(right[j].clone());
+            j += 1;
+        } else {
+            merged.push(left[i].clone());
+            i += 1;
+        }
+    }
+    merged.extend_from_slice(&left[i..]);
+    merged.extend_from_slice(&right[j..]);
+    Ok(merged)
+}
+
+fn integer_index(index: &Primitive) -> Result<i64, RuntimeError> {
+    match index {
+        Primitive::Number(number) if number.fract() == 0.0 && number.is_finite() => {
+            Ok(*number as i64)
+        }
+        _ => Err(RuntimeError::native("List index must be an integer.")),
+    }
+}
+
+fn resolve(index: i64, len: usize) -> Option<usize> {
+    let resolved = if index < 0 { index + len as i64 } else { index };
+    usize::try_from(resolved).ok()
+}
+
+fn out_of_range(index: i64, len: usize) -> RuntimeError {
+    RuntimeError::native(format!(
+        "List index {} is out of range for a list of length {}.",
+        index, len
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(n: f64) -> Primitive {
+        Primitive::Number(n)
+    }
+
+    #[test]
+    fn indexes_resolve_from_either_end() {
+        assert_eq!(index(&number(0.0), 3).unwrap(), 0);
+        assert_eq!(index(&number(-1.0), 3).unwrap(), 2);
+        assert_eq!(index(&number(-3.0), 3).unwrap(), 0);
+        assert!(index(&number(3.0), 3).is_err());
+        assert!(index(&number(-4.0), 3).is_err());
+        assert!(index(&number(0.0), 0).is_err());
+        assert!(index(&number(f64::INFINITY), 3).is_err());
+
+        // Inserting may also go just past the end.
+        assert_eq!(insertion_index(&number(3.0), 3).unwrap(), 3);
+        assert_eq!(insertion_index(&number(0.0), 0).unwrap(), 0);
+        assert!(insertion_index(&number(4.0), 3).is_err());
+    }
+
+    #[test]
+    fn slices_clamp_instead_of_failing() {
+        let bounds = |start, end| [number(start), number(end)];
+        assert_eq!(slice(&bounds(1.0, 3.0), 4).unwrap(), 1..3);
+        assert_eq!(slice(&bounds(-2.0, 100.0), 4).unwrap(), 2..4);
+        assert_eq!(slice(&bounds(3.0, 1.0), 4).unwrap(), 3..3);
+        assert_eq!(slice(&bounds(-100.0, -100.0), 4).unwrap(), 0..0);
+    }
+
+    #[test]
+    fn sort_is_stable_and_stops_at_the_first_error() {
+        let items = [(1, 'a'), (0, 'b'), (1, 'c'), (0, 'd')];
+        let sorted = sort(&items, &mut |a, b| Ok::<_, ()>(a.0.cmp(&b.0))).unwrap();
+        assert_eq!(sorted, [(0, 'b'), (0, 'd'), (1, 'a'), (1, 'c')]);
+
+        let mut calls = 0;
+        let failed = sort(&[3, 2, 1], &mut |_, _| {
+            calls += 1;
+            Err("stop")
+        });
+        assert_eq!(failed, Err("stop"));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn only_sort_takes_an_optional_argument() {
+        assert!(ListMethod::Sort.check_arity(0).is_ok());
+        assert!(ListMethod::Sort.check_arity(1).is_ok());
+        assert!(ListMethod::Pop.check_arity(1).is_err());
+        assert_eq!(ListMethod::from_name("reduce"), Some(ListMethod::Reduce));
+        assert_eq!(ListMethod::from_name("length"), None);
+    }
+}
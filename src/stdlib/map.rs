@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::interpreter::RuntimeError;
+use crate::stdlib::arity_message;
+
+// The parts of maps both backends share: which methods there are, what can
+// be a key, and the insertion-ordered table itself. Each backend brings its
+// own string type for keys.
+
+/// A method every map has, called like `map.keys()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MapMethod {
+    Keys,
+    Values,
+    Has,
+    Delete,
+    Len,
+}
+
+impl MapMethod {
+    pub fn from_name(name: &str) -> Option<MapMethod> {
+        let method = match name {
+            "keys" => MapMethod::Keys,
+            "values" => MapMethod::Values,
+            "has" => MapMethod::Has,
+            "delete" => MapMethod::Delete,
+            "len" => MapMethod::Len,
+            _ => return None,
+        };
+        Some(method)
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            MapMethod::Keys | MapMethod::Values | MapMethod::Len => 0,
+            MapMethod::Has | MapMethod::Delete => 1,
+        }
+    }
+
+    pub fn check_arity(self, count: usize) -> Result<(), RuntimeError> {
+        if count == self.arity() {
+            Ok(())
+        } else {
+            Err(RuntimeError::native(arity_message(
+                self.arity(),
+                self.arity(),
+                count,
+            )))
+        }
+    }
+}
+
+/// A map key. Only values compared by contents can be keys, with `S` being
+/// how the backend holds a string.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey<S> {
+    Nil,
+    Bool(bool),
+    /// The number's bits, so it can be hashed.
+    Number(u64),
+    String(S),
+}
+
+impl<S> MapKey<S> {
+    /// Fails for NaN, which isn't equal to itself in Lox, so an entry under
+    /// it could never be found again.
+    pub fn number(number: f64) -> Result<Self, RuntimeError> {
+        if number.is_nan() {
+            return Err(RuntimeError::native("Map keys can't be NaN."));
+        }
+        // 0 and -0 are equal in Lox, so they must be the same key.
+        let number = if number == 0.0 { 0.0 } else { number };
+        Ok(MapKey::Number(number.to_bits()))
+    }
+}
+
+pub fn key_error() -> RuntimeError {
+    RuntimeError::native("Map keys must be strings, numbers, booleans or nil.")
+}
+
+/// The error for reading a key that isn't there, given how `print` shows
+/// the key.
+pub fn undefined_key(key: &str) -> RuntimeError {
+    RuntimeError::native(format!("Undefined key '{}'.", key))
+}
+
+/// A hash map that remembers the order keys were first inserted in, which
+/// is the order `keys()`, `values()` and printing use.
+#[derive(Debug, Clone)]
+pub struct OrderedMap<K, V> {
+    entries: Vec<(K, V)>,
+    // Where each key's entry is.
+    positions: HashMap<K, usize>,
+}
+
+impl<K: Clone + Eq + Hash, V> OrderedMap<K, V> {
+    pub fn new() -> Self {
+        OrderedMap {
+            entries: vec![],
+            positions: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let position = *self.positions.get(key)?;
+        Some(&self.entries[position].1)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    /// Sets the value for `key`. A key that's already there keeps its place
+    /// in the order.
+    pub fn insert(&mut self, key: K, value: V) {
+        match self.positions.get(&key) {
+            Some(&position) => self.entries[position].1 = value,
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    /// Removes `key`, returning its value if it was there. This shifts the
+    /// entries after it, so it takes time proportional to the map's size.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let position = self.positions.remove(key)?;
+        let (_, value) = self.entries.remove(position);
+        for (key, _) in &self.entries[position..] {
+            *self
+                .positions
+                .get_mut(key)
+                .expect("every entry has a position") -= 1;
+        }
+        Some(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+impl<K: Clone + Eq + Hash, V> Default for OrderedMap<K, V> {
+    fn default() -> Self {
+        OrderedMap::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Key = MapKey<&'static str>;
+
+    #[test]
+    fn number_keys_treat_zeroes_alike_and_reject_nan() {
+        assert_eq!(Key::number(0.0).unwrap(), Key::number(-0.0).unwrap());
+        assert_ne!(Key::number(1.0).unwrap(), Key::number(2.0).unwrap());
+        assert!(Key::number(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn removing_keeps_the_rest_in_order() {
+        let mut map = OrderedMap::new();
+        for (key, value) in [("a", 1), ("b", 2), ("c", 3), ("d", 4)] {
+            map.insert(key, value);
+        }
+        assert_eq!(map.remove(&"b"), Some(2));
+        assert_eq!(map.remove(&"b"), None);
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), ["a", "c", "d"]);
+        // Positions after the removed entry were shifted down.
+        assert_eq!(map.get(&"d"), Some(&4));
+        assert_eq!(map.len(), 3);
+    }
+
+    #[test]
+    fn reinserting_a_key_keeps_its_place() {
+        let mut map = OrderedMap::new();
+        map.insert("a", 1);
+        map.insert("b", 2);
+        map.insert("a", 3);
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(&"a", &3), (&"b", &2)]);
+        assert!(map.contains_key(&"b"));
+        assert!(!map.contains_key(&"c"));
+    }
+}
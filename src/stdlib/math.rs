@@ -0,0 +1,297 @@
+use std::cell::Cell;
+use std::f64::consts;
+
+use crate::interpreter::RuntimeError;
+use crate::stdlib::{number_arg, LibraryFn, Primitive};
+
+pub static CONSTANTS: &[(&str, f64)] = &[("PI", consts::PI), ("E", consts::E)];
+
+pub static FUNCTIONS: &[LibraryFn] = &[
+    LibraryFn {
+        name: "floor",
+        arity: 1,
+        function: floor,
+    },
+    LibraryFn {
+        name: "ceil",
+        arity: 1,
+        function: ceil,
+    },
+    LibraryFn {
+        name: "round",
+        arity: 1,
+        function: round,
+    },
+    LibraryFn {
+        name: "abs",
+        arity: 1,
+        function: abs,
+    },
+    LibraryFn {
+        name: "sqrt",
+        arity: 1,
+        function: sqrt,
+    },
+    LibraryFn {
+        name: "pow",
+        arity: 2,
+        function: pow,
+    },
+    LibraryFn {
+        name: "min",
+        arity: 2,
+        function: min,
+    },
+    LibraryFn {
+        name: "max",
+        arity: 2,
+        function: max,
+    },
+    LibraryFn {
+        name: "sin",
+        arity: 1,
+        function: sin,
+    },
+    LibraryFn {
+        name: "cos",
+        arity: 1,
+        function: cos,
+    },
+    LibraryFn {
+        name: "tan",
+        arity: 1,
+        function: tan,
+    },
+    LibraryFn {
+        name: "asin",
+        arity: 1,
+        function: asin,
+    },
+    LibraryFn {
+        name: "acos",
+        arity: 1,
+        function: acos,
+    },
+    LibraryFn {
+        name: "atan",
+        arity: 1,
+        function: atan,
+    },
+    LibraryFn {
+        name: "atan2",
+        arity: 2,
+        function: atan2,
+    },
+    LibraryFn {
+        name: "log",
+        arity: 1,
+        function: log,
+    },
+    LibraryFn {
+        name: "exp",
+        arity: 1,
+        function: exp,
+    },
+    LibraryFn {
+        name: "div",
+        arity: 2,
+        function: div,
+    },
+    LibraryFn {
+        name: "random",
+        arity: 0,
+        function: random,
+    },
+    LibraryFn {
+        name: "seed",
+        arity: 1,
+        function: seed,
+    },
+];
+
+/// Raised by both `div` and `%` for a zero divisor, unlike `/`, which gives
+/// an infinity.
+const DIVISION_BY_ZERO: &str = "Division by zero.";
+
+/// The `%` operator. The result has the sign of the divisor, so together with
+/// `div` it satisfies `a == div(a, b) * b + a % b`. Like `div`, it fails for
+/// a zero divisor rather than giving NaN. Returns the error message, which
+/// the backends report at the operator.
+pub fn modulo(a: f64, b: f64) -> Result<f64, &'static str> {
+    if b == 0.0 {
+        return Err(DIVISION_BY_ZERO);
+    }
+    let remainder = a % b;
+    if remainder != 0.0 && (remainder < 0.0) != (b < 0.0) {
+        Ok(remainder + b)
+    } else {
+        Ok(remainder)
+    }
+}
+
+/// Applies a one-argument `f64` method.
+fn unary(name: &str, args: &[Primitive], op: fn(f64) -> f64) -> Result<Primitive, RuntimeError> {
+    Ok(Primitive::Number(op(number_arg(name, args, 0)?)))
+}
+
+fn binary(
+    name: &str,
+    args: &[Primitive],
+    op: fn(f64, f64) -> f64,
+) -> Result<Primitive, RuntimeError> {
+    let a = number_arg(name, args, 0)?;
+    let b = number_arg(name, args, 1)?;
+    Ok(Primitive::Number(op(a, b)))
+}
+
+fn floor(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    unary("floor", args, f64::floor)
+}
+
+fn ceil(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    unary("ceil", args, f64::ceil)
+}
+
+/// Rounds half-way cases away from zero.
+fn round(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    unary("round", args, f64::round)
+}
+
+fn abs(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    unary("abs", args, f64::abs)
+}
+
+fn sqrt(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    unary("sqrt", args, f64::sqrt)
+}
+
+fn pow(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    binary("pow", args, f64::powf)
+}
+
+fn min(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    binary("min", args, f64::min)
+}
+
+fn max(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    binary("max", args, f64::max)
+}
+
+fn sin(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    unary("sin", args, f64::sin)
+}
+
+fn cos(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    unary("cos", args, f64::cos)
+}
+
+fn tan(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    unary("tan", args, f64::tan)
+}
+
+fn asin(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    unary("asin", args, f64::asin)
+}
+
+fn acos(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    unary("acos", args, f64::acos)
+}
+
+fn atan(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    unary("atan", args, f64::atan)
+}
+
+/// `atan2(y, x)`.
+fn atan2(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    binary("atan2", args, f64::atan2)
+}
+
+/// The natural logarithm.
+fn log(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    unary("log", args, f64::ln)
+}
+
+fn exp(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    unary("exp", args, f64::exp)
+}
+
+/// Integer division, rounding the quotient down.
+fn div(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let a = number_arg("div", args, 0)?;
+    let b = number_arg("div", args, 1)?;
+    if b == 0.0 {
+        return Err(RuntimeError::native(DIVISION_BY_ZERO));
+    }
+    Ok(Primitive::Number((a / b).floor()))
+}
+
+// `random()` is deterministic: every run starts from the same seed unless a
+// script calls `seed(n)`, so runs can be reproduced exactly.
+
+const DEFAULT_SEED: u64 = 0x5eed;
+
+thread_local! {
+    static RANDOM_STATE: Cell<u64> = const { Cell::new(DEFAULT_SEED) };
+}
+
+/// A number in `[0, 1)` from a SplitMix64 generator.
+fn random(_args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let bits = RANDOM_STATE.with(|state| {
+        let next = state.get().wrapping_add(0x9e3779b97f4a7c15);
+        state.set(next);
+        let mut z = next;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    });
+    // The top 53 bits fill an f64's mantissa exactly.
+    Ok(Primitive::Number((bits >> 11) as f64 / (1u64 << 53) as f64))
+}
+
+/// Restarts the `random()` sequence from `seed`.
+fn seed(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let seed = number_arg("seed", args, 0)?;
+    RANDOM_STATE.with(|state| state.set(seed.to_bits()));
+    Ok(Primitive::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(result: Result<Primitive, RuntimeError>) -> f64 {
+        match result {
+            Ok(Primitive::Number(number)) => number,
+            other => panic!("expected a number, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn modulo_matches_floored_division() {
+        for a in [7.0, -7.0, 5.5, -0.5, 0.0] {
+            for b in [3.0, -3.0, 2.0, 0.25] {
+                let quotient = number(div(&[Primitive::Number(a), Primitive::Number(b)]));
+                let remainder = modulo(a, b).unwrap();
+                assert_eq!(quotient * b + remainder, a, "{} % {}", a, b);
+                assert!(remainder == 0.0 || (remainder < 0.0) == (b < 0.0));
+            }
+        }
+        assert_eq!(modulo(1.0, 0.0), Err(DIVISION_BY_ZERO));
+    }
+
+    #[test]
+    fn seeding_restarts_the_sequence() {
+        // Tests may share a thread, so start from the default explicitly.
+        RANDOM_STATE.with(|state| state.set(DEFAULT_SEED));
+        let first: Vec<f64> = (0..3).map(|_| number(random(&[]))).collect();
+        assert_eq!(first[0], 0.038848734697185194, "default seed changed");
+
+        seed(&[Primitive::Number(1.0)]).unwrap();
+        let seeded: Vec<f64> = (0..3).map(|_| number(random(&[]))).collect();
+        seed(&[Primitive::Number(1.0)]).unwrap();
+        let again: Vec<f64> = (0..3).map(|_| number(random(&[]))).collect();
+        assert_eq!(seeded, again);
+        assert_ne!(seeded, first);
+        assert!(seeded.iter().all(|r| (0.0..1.0).contains(r)));
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::interpreter::RuntimeError;
+
+pub mod list;
+pub mod map;
+mod math;
+mod string;
+
+pub use math::modulo;
+
+/// A value passed into or out of a library function. Both backends convert
+/// their own values to and from this, so the library is written once.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Primitive {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    /// A list's elements. A list nested inside it arrives as `Other`, which
+    /// keeps the conversion finite for lists that contain themselves.
+    List(Vec<Primitive>),
+    /// Any other value, carrying how `print` would show it. Library
+    /// functions can only convert it to a string or reject it.
+    Other(Rc<str>),
+}
+
+/// Shows the value the way `print` does.
+impl fmt::Display for Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Primitive::Nil => write!(f, "nil"),
+            Primitive::Bool(value) => write!(f, "{}", value),
+            Primitive::Number(value) => write!(f, "{}", value),
+            Primitive::String(value) | Primitive::Other(value) => write!(f, "{}", value),
+            Primitive::List(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+pub type LibraryBody = fn(&[Primitive]) -> Result<Primitive, RuntimeError>;
+
+/// A function of the standard library, defined as a native by each backend.
+pub struct LibraryFn {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: LibraryBody,
+}
+
+/// Every function in the standard library.
+pub fn functions() -> impl Iterator<Item = &'static LibraryFn> {
+    string::FUNCTIONS.iter().chain(math::FUNCTIONS)
+}
+
+/// Global number constants, defined alongside the functions.
+pub fn constants() -> impl Iterator<Item = &'static (&'static str, f64)> {
+    math::CONSTANTS.iter()
+}
+
+/// The error for a call with the wrong number of arguments, for callables
+/// that may take a range of them.
+pub fn arity_message(min: usize, max: usize, count: usize) -> String {
+    if min == max {
+        format!("Expected {} arguments but got {}.", min, count)
+    } else {
+        format!("Expected {} to {} arguments but got {}.", min, max, count)
+    }
+}
+
+// Argument checks. Errors name the function and the argument's position,
+// counting from one.
+
+fn string_arg<'a>(
+    name: &str,
+    args: &'a [Primitive],
+    index: usize,
+) -> Result<&'a str, RuntimeError> {
+    match &args[index] {
+        Primitive::String(string) => Ok(string),
+        _ => Err(argument_error(name, index, "a string")),
+    }
+}
+
+fn list_arg<'a>(
+    name: &str,
+    args: &'a [Primitive],
+    index: usize,
+) -> Result<&'a [Primitive], RuntimeError> {
+    match &args[index] {
+        Primitive::List(elements) => Ok(elements),
+        _ => Err(argument_error(name, index, "a list")),
+    }
+}
+
+fn number_arg(name: &str, args: &[Primitive], index: usize) -> Result<f64, RuntimeError> {
+    match args[index] {
+        Primitive::Number(number) => Ok(number),
+        _ => Err(argument_error(name, index, "a number")),
+    }
+}
+
+/// A number argument that must be a whole number, as indexes and counts are.
+fn integer_arg(name: &str, args: &[Primitive], index: usize) -> Result<i64, RuntimeError> {
+    let number = number_arg(name, args, index)?;
+    if number.fract() != 0.0 || !number.is_finite() {
+        return Err(argument_error(name, index, "an integer"));
+    }
+    Ok(number as i64)
+}
+
+fn argument_error(name: &str, index: usize, expected: &str) -> RuntimeError {
+    RuntimeError::native(format!(
+        "{}() expects {} as argument {}.",
+        name,
+        expected,
+        index + 1
+    ))
+}
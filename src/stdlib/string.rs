@@ -0,0 +1,234 @@
+use std::rc::Rc;
+
+use crate::interpreter::RuntimeError;
+use crate::stdlib::{argument_error, integer_arg, list_arg, string_arg, LibraryFn, Primitive};
+
+// Indexes and lengths count characters (Unicode scalar values), not bytes,
+// so they never split a character.
+
+pub static FUNCTIONS: &[LibraryFn] = &[
+    LibraryFn {
+        name: "len",
+        arity: 1,
+        function: len,
+    },
+    LibraryFn {
+        name: "substr",
+        arity: 3,
+        function: substr,
+    },
+    LibraryFn {
+        name: "index_of",
+        arity: 2,
+        function: index_of,
+    },
+    LibraryFn {
+        name: "trim",
+        arity: 1,
+        function: trim,
+    },
+    LibraryFn {
+        name: "upper",
+        arity: 1,
+        function: upper,
+    },
+    LibraryFn {
+        name: "lower",
+        arity: 1,
+        function: lower,
+    },
+    LibraryFn {
+        name: "replace",
+        arity: 3,
+        function: replace,
+    },
+    LibraryFn {
+        name: "starts_with",
+        arity: 2,
+        function: starts_with,
+    },
+    LibraryFn {
+        name: "ends_with",
+        arity: 2,
+        function: ends_with,
+    },
+    LibraryFn {
+        name: "char_at",
+        arity: 2,
+        function: char_at,
+    },
+    LibraryFn {
+        name: "split",
+        arity: 2,
+        function: split,
+    },
+    LibraryFn {
+        name: "join",
+        arity: 2,
+        function: join,
+    },
+    LibraryFn {
+        name: "to_number",
+        arity: 1,
+        function: to_number,
+    },
+    LibraryFn {
+        name: "to_string",
+        arity: 1,
+        function: to_string,
+    },
+];
+
+fn string_result(value: impl Into<Rc<str>>) -> Result<Primitive, RuntimeError> {
+    Ok(Primitive::String(value.into()))
+}
+
+fn len(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let string = string_arg("len", args, 0)?;
+    Ok(Primitive::Number(string.chars().count() as f64))
+}
+
+/// `substr(string, start, length)`.
+fn substr(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let string = string_arg("substr", args, 0)?;
+    let start = integer_arg("substr", args, 1)?;
+    let length = integer_arg("substr", args, 2)?;
+
+    let count = string.chars().count() as i64;
+    let end = start.saturating_add(length);
+    if start < 0 || length < 0 || end > count {
+        return Err(RuntimeError::native(format!(
+            "Substring {}..{} is out of range for a string of length {}.",
+            start, end, count
+        )));
+    }
+    string_result(
+        string
+            .chars()
+            .skip(start as usize)
+            .take(length as usize)
+            .collect::<String>(),
+    )
+}
+
+/// The character index of the first occurrence of `needle`, or -1.
+fn index_of(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let string = string_arg("index_of", args, 0)?;
+    let needle = string_arg("index_of", args, 1)?;
+    let index = match string.find(needle) {
+        Some(byte_index) => string[..byte_index].chars().count() as f64,
+        None => -1.0,
+    };
+    Ok(Primitive::Number(index))
+}
+
+fn trim(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    string_result(string_arg("trim", args, 0)?.trim())
+}
+
+fn upper(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    string_result(string_arg("upper", args, 0)?.to_uppercase())
+}
+
+fn lower(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    string_result(string_arg("lower", args, 0)?.to_lowercase())
+}
+
+/// `replace(string, from, to)` replaces every occurrence of `from`.
+fn replace(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let string = string_arg("replace", args, 0)?;
+    let from = string_arg("replace", args, 1)?;
+    let to = string_arg("replace", args, 2)?;
+    if from.is_empty() {
+        return Err(RuntimeError::native("Can't replace an empty string."));
+    }
+    string_result(string.replace(from, to))
+}
+
+fn starts_with(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let string = string_arg("starts_with", args, 0)?;
+    let prefix = string_arg("starts_with", args, 1)?;
+    Ok(Primitive::Bool(string.starts_with(prefix)))
+}
+
+fn ends_with(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let string = string_arg("ends_with", args, 0)?;
+    let suffix = string_arg("ends_with", args, 1)?;
+    Ok(Primitive::Bool(string.ends_with(suffix)))
+}
+
+fn char_at(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let string = string_arg("char_at", args, 0)?;
+    let index = integer_arg("char_at", args, 1)?;
+    let found = usize::try_from(index)
+        .ok()
+        .and_then(|index| string.chars().nth(index));
+    match found {
+        Some(c) => string_result(c.to_string()),
+        None => Err(RuntimeError::native(format!(
+            "String index {} is out of range for a string of length {}.",
+            index,
+            string.chars().count()
+        ))),
+    }
+}
+
+/// `split(string, separator)` returns a list of the pieces between each
+/// occurrence of `separator`. An empty separator splits out every
+/// character.
+fn split(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let string = string_arg("split", args, 0)?;
+    let separator = string_arg("split", args, 1)?;
+    let pieces = if separator.is_empty() {
+        string
+            .chars()
+            .map(|c| Primitive::String(Rc::from(c.to_string())))
+            .collect()
+    } else {
+        string
+            .split(separator)
+            .map(|piece| Primitive::String(Rc::from(piece)))
+            .collect()
+    };
+    Ok(Primitive::List(pieces))
+}
+
+/// `join(list, separator)` concatenates a list of strings, putting
+/// `separator` between each.
+fn join(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let elements = list_arg("join", args, 0)?;
+    let separator = string_arg("join", args, 1)?;
+    let pieces = elements
+        .iter()
+        .map(|element| match element {
+            Primitive::String(piece) => Ok(&**piece),
+            _ => Err(argument_error("join", 0, "a list of strings")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    string_result(pieces.join(separator))
+}
+
+/// Parses a decimal number, ignoring surrounding whitespace. Text that
+/// isn't one is a runtime error, like any other bad argument.
+fn to_number(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    let text = string_arg("to_number", args, 0)?.trim();
+    // Rust also parses words like "inf" and "NaN", which Lox doesn't have.
+    let numeric = text
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'));
+    match text.parse::<f64>() {
+        Ok(number) if numeric => Ok(Primitive::Number(number)),
+        _ => Err(RuntimeError::native(format!(
+            "to_number() can't parse '{}' as a number.",
+            text
+        ))),
+    }
+}
+
+/// Converts any value to a string the way `print` shows it.
+fn to_string(args: &[Primitive]) -> Result<Primitive, RuntimeError> {
+    match &args[0] {
+        Primitive::String(value) => Ok(Primitive::String(Rc::clone(value))),
+        other => string_result(other.to_string()),
+    }
+}
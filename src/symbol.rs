@@ -0,0 +1,88 @@
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref INTERNER: Mutex<Interner> = Mutex::new(Interner::default());
+
+    // Names the interpreter looks up on its own, interned once up front
+    // rather than every time a method is bound or a class instantiated.
+    pub static ref THIS: Symbol = Symbol::intern("this");
+    pub static ref SUPER: Symbol = Symbol::intern("super");
+    pub static ref INIT: Symbol = Symbol::intern("init");
+    pub static ref ITER: Symbol = Symbol::intern("iter");
+    pub static ref NEXT: Symbol = Symbol::intern("next");
+    pub static ref DONE: Symbol = Symbol::intern("done");
+}
+
+/// An interned string. Two symbols are equal exactly when their strings are,
+/// so comparing and hashing them is O(1).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn intern(string: &str) -> Symbol {
+        INTERNER.lock().unwrap().intern(string)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.lock().unwrap().strings[self.0 as usize]
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The symbol table behind `Symbol`. Interned strings are never freed, which
+/// lets `Symbol::as_str` hand out `'static` references.
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    strings: Vec<&'static str>,
+}
+
+impl Interner {
+    fn intern(&mut self, string: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(string) {
+            return *symbol;
+        }
+
+        let symbol = Symbol(self.strings.len() as u32);
+        let string: &'static str = Box::leak(Box::from(string));
+        self.symbols.insert(string, symbol);
+        self.strings.push(string);
+        symbol
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_strings_intern_to_the_same_symbol() {
+        let a = Symbol::intern("symbol_test_name");
+        let b = Symbol::intern(&String::from("symbol_test_name"));
+        assert_eq!(a, b);
+        assert_ne!(a, Symbol::intern("symbol_test_other"));
+        assert_eq!(a.as_str(), "symbol_test_name");
+    }
+
+    #[test]
+    fn well_known_names_are_ordinary_symbols() {
+        assert_eq!(*THIS, Symbol::intern("this"));
+        assert_eq!(*INIT, Symbol::intern("init"));
+        assert_eq!(SUPER.as_str(), "super");
+        assert_ne!(*NEXT, *DONE);
+    }
+}
@@ -0,0 +1,147 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::class::{Class, Instance};
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::list::{self, List};
+use crate::map::{self, Map};
+use crate::stdlib::Primitive;
+
+/// A value at runtime in the tree-walking interpreter. Objects are
+/// reference counted, so a cycle of them leaks; only the `--vm` backend
+/// collects garbage.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    Callable(Rc<dyn Callable>),
+    Class(Rc<Class>),
+    Instance(Rc<RefCell<Instance>>),
+    List(List),
+    Map(Map),
+}
+
+impl Value {
+    /// `nil` and `false` are falsey, everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    /// Converts the value to pass it to the standard library.
+    pub fn to_primitive(&self) -> Primitive {
+        match self {
+            Value::Nil => Primitive::Nil,
+            Value::Bool(value) => Primitive::Bool(*value),
+            Value::Number(value) => Primitive::Number(*value),
+            Value::String(value) => Primitive::String(Rc::clone(value)),
+            Value::List(value) => list::to_primitive(value),
+            other => Primitive::Other(Rc::from(other.to_string())),
+        }
+    }
+}
+
+impl From<Primitive> for Value {
+    fn from(primitive: Primitive) -> Self {
+        match primitive {
+            Primitive::Nil => Value::Nil,
+            Primitive::Bool(value) => Value::Bool(value),
+            Primitive::Number(value) => Value::Number(value),
+            Primitive::String(value) | Primitive::Other(value) => Value::String(value),
+            Primitive::List(elements) => list::new(elements.into_iter().map(Value::from).collect()),
+        }
+    }
+}
+
+/// Values of different types are never equal. Functions, classes,
+/// instances, lists and maps are compared by identity.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Callable(a), Value::Callable(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
+            (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Callable(callable) => write!(f, "{}", callable),
+            Value::Class(class) => write!(f, "{}", class),
+            Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
+            Value::List(_) | Value::Map(_) => self.fmt_nested(f, &mut vec![]),
+        }
+    }
+}
+
+impl Value {
+    /// Writes a value that may contain others. `open` holds the lists and
+    /// maps being written further out, so one that contains itself is shown
+    /// as `[...]` or `{...}` where it recurs, rather than forever.
+    fn fmt_nested(&self, f: &mut fmt::Formatter<'_>, open: &mut Vec<Value>) -> fmt::Result {
+        match self {
+            Value::List(_) | Value::Map(_) if open.contains(self) => match self {
+                Value::List(_) => write!(f, "[...]"),
+                _ => write!(f, "{{...}}"),
+            },
+            Value::List(list) => {
+                open.push(self.clone());
+                write!(f, "[")?;
+                for (i, element) in list.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.fmt_nested(f, open)?;
+                }
+                open.pop();
+                write!(f, "]")
+            }
+            Value::Map(entries) => {
+                open.push(self.clone());
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", map::key_value(key))?;
+                    value.fmt_nested(f, open)?;
+                }
+                open.pop();
+                write!(f, "}}")
+            }
+            other => write!(f, "{}", other),
+        }
+    }
+}
+
+/// Anything that can be called from Lox code.
+pub trait Callable: fmt::Debug + fmt::Display {
+    fn arity(&self) -> usize;
+
+    /// The most arguments the callable takes, if some are optional.
+    fn max_arity(&self) -> usize {
+        self.arity()
+    }
+
+    /// Called with between `arity()` and `max_arity()` arguments.
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError>;
+}
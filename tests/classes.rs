@@ -0,0 +1,156 @@
+mod common;
+
+use common::{compile_errors, output, runtime_error};
+
+#[test]
+fn classes_and_instances_print_their_name() {
+    assert_eq!(
+        output("class Point {} print Point; print Point();"),
+        "Point\nPoint instance\n"
+    );
+}
+
+#[test]
+fn fields_can_be_added_and_read() {
+    let source = "
+        class Box {}
+        var box = Box();
+        box.value = 1;
+        box.value = box.value + 1;
+        print box.value;
+    ";
+    assert_eq!(output(source), "2\n");
+}
+
+#[test]
+fn methods_see_this() {
+    let source = "
+        class Counter {
+            add(n) {
+                this.total = this.total + n;
+                return this;
+            }
+        }
+        var counter = Counter();
+        counter.total = 0;
+        print counter.add(2).add(3).total;
+    ";
+    assert_eq!(output(source), "5\n");
+}
+
+#[test]
+fn bound_methods_remember_their_instance() {
+    let source = "
+        class Person {
+            init(name) { this.name = name; }
+            greet() { return \"hi \" + this.name; }
+        }
+        var greet = Person(\"ada\").greet;
+        print greet;
+        print greet();
+    ";
+    assert_eq!(output(source), "<fn greet>\nhi ada\n");
+}
+
+#[test]
+fn fields_shadow_methods() {
+    let source = "
+        class A { m() { return \"method\"; } }
+        var a = A();
+        fun f() { return \"field\"; }
+        a.m = f;
+        print a.m();
+    ";
+    assert_eq!(output(source), "field\n");
+}
+
+#[test]
+fn init_always_returns_this() {
+    let source = "
+        class A {
+            init(x) {
+                this.x = x;
+                if (x > 1) return;
+                this.x = -x;
+            }
+        }
+        var a = A(1);
+        print a.x;
+        print a.init(5);
+        print a.x;
+    ";
+    assert_eq!(output(source), "-1\nA instance\n5\n");
+    assert_eq!(
+        runtime_error("class A { init(a, b) {} }\nA(1);"),
+        "Expected 2 arguments but got 1.\n[line 2]\n"
+    );
+}
+
+#[test]
+fn subclasses_inherit_and_call_super() {
+    let source = "
+        class Shape {
+            init(name) { this.name = name; }
+            describe() { return \"a \" + this.name; }
+            kind() { return \"shape\"; }
+        }
+        class Square < Shape {
+            init() { super.init(\"square\"); }
+            describe() { return super.describe() + \" (\" + this.kind() + \")\"; }
+        }
+        class Tile < Square {}
+        print Tile().describe();
+    ";
+    assert_eq!(output(source), "a square (shape)\n");
+}
+
+#[test]
+fn super_is_bound_to_the_defining_class() {
+    let source = "
+        class A { m() { return \"A\"; } }
+        class B < A { m() { return \"B\" + super.m(); } }
+        class C < B { m() { return \"C\" + super.m(); } }
+        print C().m();
+    ";
+    assert_eq!(output(source), "CBA\n");
+}
+
+#[test]
+fn property_errors() {
+    assert_eq!(
+        runtime_error("var x = 1;\nprint x.y;"),
+        "Only instances have properties.\n[line 2]\n"
+    );
+    assert_eq!(
+        runtime_error("var x = 1;\nx.y = 2;"),
+        "Only instances have fields.\n[line 2]\n"
+    );
+    assert_eq!(
+        runtime_error("class A {}\nprint A().missing;"),
+        "Undefined property 'missing'.\n[line 2]\n"
+    );
+    assert_eq!(
+        runtime_error("class A {}\nclass B < A { m() { return super.missing(); } }\nB().m();"),
+        "Undefined property 'missing'.\n[line 2]\n"
+    );
+}
+
+#[test]
+fn superclass_must_be_a_class() {
+    assert_eq!(
+        runtime_error("var NotClass = 1;\nclass B < NotClass {}"),
+        "Superclass must be a class.\n[line 2]\n"
+    );
+}
+
+#[test]
+fn this_and_super_outside_a_class() {
+    assert_eq!(
+        compile_errors("print this;"),
+        "[line 1:7] Error at 'this': Can't use 'this' outside of a class.\n"
+    );
+    assert_eq!(
+        compile_errors("class A { m() { super.m(); } }"),
+        "[line 1:17] Error at 'super': Can't use 'super' in a class with no superclass.\n"
+    );
+}
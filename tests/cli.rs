@@ -0,0 +1,94 @@
+mod common;
+
+use std::process::Command;
+
+use common::{compile_errors, output, run, run_with, runtime_error};
+
+#[test]
+fn clean_script_exits_with_zero() {
+    assert_eq!(output("print 1 + 2;"), "3\n");
+}
+
+#[test]
+fn lexical_errors_are_all_reported_with_exit_code_65() {
+    let errors = compile_errors("var a = @1;\nvar b = 2 # ;\nprint \"open");
+    assert_eq!(
+        errors,
+        "[line 1:9] Error: Unexpected character '@'.\n\
+         [line 2:11] Error: Unexpected character '#'.\n\
+         [line 3:7] Error: Unterminated string.\n"
+    );
+}
+
+#[test]
+fn nothing_runs_after_a_lexical_error() {
+    let run = run("print 1;\nprint @;");
+    assert_eq!(run.code, Some(65));
+    assert_eq!(run.stdout, "");
+}
+
+#[test]
+fn runtime_errors_exit_with_code_70() {
+    let run = run("print 1;\nprint -\"a\";\nprint 2;");
+    assert_eq!(run.code, Some(70));
+    assert_eq!(run.stdout, "1\n");
+    assert_eq!(run.stderr, "Operand must be a number.\n[line 2]\n");
+}
+
+#[test]
+fn bad_usage_exits_with_code_64() {
+    let run = run_with(&["--no-such-flag"], "print 1;");
+    assert_eq!(run.code, Some(64));
+    assert!(run.stdout.starts_with("Usage: lox"));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(["one.lox", "two.lox"])
+        .output()
+        .expect("Failed to run lox");
+    assert_eq!(status.status.code(), Some(64));
+}
+
+#[test]
+fn runtime_error_reports_the_line() {
+    assert_eq!(
+        runtime_error("var a = 1;\n\nprint a + nil;"),
+        "Operands must be two numbers or two strings.\n[line 3]\n"
+    );
+}
+
+#[test]
+fn syntax_errors_are_all_reported_with_exit_code_65() {
+    let errors = compile_errors("var = 1;\nprint 2;\nvar b = ;\nif (true print 3;\nprint 4;");
+    assert_eq!(
+        errors,
+        "[line 1:5] Error at '=': Expect variable name.\n\
+         [line 3:9] Error at ';': Expect expression.\n\
+         [line 4:10] Error at 'print': Expect ')' after if condition.\n"
+    );
+}
+
+#[test]
+fn stress_gc_leaves_output_unchanged() {
+    let source = "
+class Node { init(value) { this.value = value; this.next = this; } }
+fun counter() { var n = 0; fun count() { n = n + 1; return n; } return count; }
+var count = counter();
+var nodes = [];
+for (i in [1, 2, 3]) nodes.push(Node(i + count()));
+var names = {\"a\": nodes[0], \"b\": nodes[2].next};
+print names[\"b\"].value + len(upper(\"xy\"));
+";
+    let normal = run_with(&["--vm"], source);
+    let stressed = run_with(&["--stress-gc"], source);
+    assert_eq!(normal.code, Some(0), "{}", normal.stderr);
+    assert_eq!(normal.stdout, "8\n");
+    assert_eq!(stressed.stdout, normal.stdout);
+    assert_eq!(stressed.code, Some(0));
+}
+
+#[test]
+fn usage_states_that_the_tree_walker_keeps_cycles() {
+    let run = run_with(&["--no-such-flag"], "");
+    assert!(run.stdout.contains("never collects cycles"));
+    assert!(run.stdout.contains("Use --vm for long-running sessions."));
+}
@@ -0,0 +1,85 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What running the `lox` binary on a script produced.
+#[derive(Debug)]
+pub struct Run {
+    pub stdout: String,
+    pub stderr: String,
+    pub code: Option<i32>,
+}
+
+/// Runs `source` as a script file with the given command-line flags. The
+/// "Running file" banner is left out of `stdout`.
+pub fn run_with(flags: &[&str], source: &str) -> Run {
+    static SCRIPTS: AtomicUsize = AtomicUsize::new(0);
+    let number = SCRIPTS.fetch_add(1, Ordering::Relaxed);
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!(
+        "{}-{}-{}.lox",
+        env!("CARGO_CRATE_NAME"),
+        std::process::id(),
+        number
+    ));
+    fs::write(&path, source).expect("Failed to write script");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(flags)
+        .arg(&path)
+        .output()
+        .expect("Failed to run lox");
+    fs::remove_file(&path).expect("Failed to remove script");
+
+    let stdout = String::from_utf8(output.stdout).expect("stdout isn't UTF-8");
+    let stdout = match stdout.split_once('\n') {
+        Some((banner, rest)) if banner.starts_with("Running file: ") => rest.to_string(),
+        _ => stdout,
+    };
+    Run {
+        stdout,
+        stderr: String::from_utf8(output.stderr).expect("stderr isn't UTF-8"),
+        code: output.status.code(),
+    }
+}
+
+/// Runs `source` on both backends, checking that they agree on everything
+/// they print and on the exit code.
+pub fn run(source: &str) -> Run {
+    let tree_walker = run_with(&[], source);
+    let vm = run_with(&["--vm"], source);
+    assert_eq!(
+        tree_walker.stdout, vm.stdout,
+        "backends printed different output"
+    );
+    assert_eq!(
+        tree_walker.stderr, vm.stderr,
+        "backends reported different errors"
+    );
+    assert_eq!(tree_walker.code, vm.code, "backends exited differently");
+    tree_walker
+}
+
+/// Runs `source` on both backends and returns what it printed, failing if
+/// the script didn't run cleanly.
+pub fn output(source: &str) -> String {
+    let run = run(source);
+    assert_eq!(run.code, Some(0), "script failed: {}", run.stderr);
+    run.stdout
+}
+
+/// Runs `source` on both backends and returns the runtime error it reported.
+pub fn runtime_error(source: &str) -> String {
+    let run = run(source);
+    assert_eq!(run.code, Some(70), "expected a runtime error: {:?}", run);
+    run.stderr
+}
+
+/// Runs `source` on both backends and returns the compile errors it reported.
+pub fn compile_errors(source: &str) -> String {
+    let run = run(source);
+    assert_eq!(run.code, Some(65), "expected compile errors: {:?}", run);
+    run.stderr
+}
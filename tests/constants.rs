@@ -0,0 +1,119 @@
+mod common;
+
+use common::{output, run_with};
+
+/// `count` global declarations, each initialized to its own constant.
+fn many_globals(count: usize) -> String {
+    (0..count)
+        .map(|n| format!("var v{} = {}.5;\n", n, n))
+        .collect()
+}
+
+#[test]
+fn more_than_256_constants() {
+    let mut source = many_globals(130);
+    source.push_str("print v0 + v129;\n");
+    assert_eq!(output(&source), "130\n");
+
+    let mut source = String::from("var s = 0;\n");
+    for n in 0..260 {
+        source.push_str(&format!("s = s + {}.25;\n", n));
+    }
+    source.push_str("print s;\n");
+    assert_eq!(output(&source), "33735\n");
+}
+
+#[test]
+fn wide_global_reads_writes_and_definitions() {
+    let mut source = many_globals(400);
+    source.push_str("v399 = v398 + v300;\nprint v399;\nvar v0 = \"redefined\";\nprint v0;\n");
+    source.push_str("fun f() { v350 = -1; return v350 + v399; }\nprint f();\n");
+    assert_eq!(output(&source), "699\nredefined\n698\n");
+
+    let mut source = many_globals(300);
+    source.push_str("print missing;\n");
+    let run = common::run(&source);
+    assert_eq!(run.stderr, "Undefined variable 'missing'.\n[line 301]\n");
+}
+
+#[test]
+fn classes_methods_and_closures_past_256_constants() {
+    let mut source = many_globals(300);
+    source.push_str(
+        "
+        class Base {
+            init(start) { this.total = start; }
+            add(n) { this.total = this.total + n; return this; }
+        }
+        class Counter < Base {
+            add(n) { return super.add(n * 2); }
+            get() { var f = super.add; return f(0).total; }
+        }
+        fun adder(n) {
+            fun add(m) { return n + m; }
+            return add;
+        }
+        var counter = Counter(v1);
+        counter.add(1).add(2);
+        print counter.get();
+        counter.extra = v3;
+        print counter.extra;
+        print adder(v2)(3);
+        for (x in [1]) print x;
+    ",
+    );
+    assert_eq!(output(&source), "7.5\n3.5\n5.5\n1\n");
+}
+
+#[test]
+fn super_calls_past_256_constants() {
+    // The constants pile up in the method's own chunk this time.
+    let sum: String = (0..260).map(|n| format!(" + {}.5", n)).collect();
+    let source = format!(
+        "
+        class Base {{ add(n) {{ return n + 1; }} }}
+        class Derived < Base {{
+            sum() {{
+                var s = 0{};
+                var add = super.add;
+                return add(super.add(s));
+            }}
+        }}
+        print Derived().sum();
+        ",
+        sum
+    );
+    assert_eq!(output(&source), "33802\n");
+}
+
+#[test]
+fn repeated_constants_share_a_slot() {
+    // Without reuse, each line would add a constant for `a` and for `1`.
+    let mut source = String::from("var a = 0;\n");
+    source.push_str(&"a = a + 1;\n".repeat(1000));
+    source.push_str("print a;\n");
+    assert_eq!(output(&source), "1000\n");
+}
+
+#[test]
+fn wide_instructions_are_disassembled() {
+    let mut source = many_globals(200);
+    source.push_str("print v199;\n");
+    let run = run_with(&["--disassemble"], &source);
+    assert_eq!(run.stdout, "199.5\n");
+    assert!(
+        run.stderr.contains("DefineGlobalLong  398 'v199'"),
+        "{}",
+        run.stderr
+    );
+    assert!(
+        run.stderr.contains("ConstantLong      399 '199.5'"),
+        "{}",
+        run.stderr
+    );
+    assert!(
+        run.stderr.contains("GetGlobalLong     398 'v199'"),
+        "{}",
+        run.stderr
+    );
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::run_with;
+
+#[test]
+fn disassembles_every_function_in_source_order() {
+    let result = run_with(
+        &["--disassemble"],
+        "
+fun outer() {
+  var x = 1;
+  fun inner() { return x; }
+  return inner;
+}
+print outer()();
+",
+    );
+    assert_eq!(result.code, Some(0));
+    assert_eq!(result.stdout, "1\n");
+
+    let script = result.stderr.find("== <script> ==").expect("no script");
+    let outer = result.stderr.find("== outer ==").expect("no outer");
+    let inner = result.stderr.find("== inner ==").expect("no inner");
+    assert!(script < outer && outer < inner);
+    assert!(result
+        .stderr
+        .contains("0000    6 Closure             1 <fn outer>\n"));
+    assert!(result.stderr.contains("|                     local 1\n"));
+    assert!(result
+        .stderr
+        .contains("== inner ==\n0000    4 GetUpvalue          0\n"));
+}
+
+#[test]
+fn disassembly_takes_lines_from_the_source() {
+    let result = run_with(&["--disassemble"], "var a = 1;\n\nprint a;\n");
+    assert_eq!(
+        result.stderr,
+        "== <script> ==\n\
+         0000    1 Constant            1 '1'\n\
+         0002    | DefineGlobal        0 'a'\n\
+         0004    3 GetGlobal           0 'a'\n\
+         0006    | Print\n\
+         0007    4 Nil\n\
+         0008    | Return\n"
+    );
+}
+
+#[test]
+fn trace_shows_the_stack_before_each_instruction() {
+    let result = run_with(&["--trace"], "print 1 + 2;");
+    assert_eq!(result.code, Some(0));
+    assert_eq!(result.stdout, "3\n");
+    assert_eq!(
+        result.stderr,
+        "          [ <script> ]\n\
+         0000    1 Constant            0 '1'\n\
+         \x20         [ <script> ][ 1 ]\n\
+         0002    | Constant            1 '2'\n\
+         \x20         [ <script> ][ 1 ][ 2 ]\n\
+         0004    | Add\n\
+         \x20         [ <script> ][ 3 ]\n\
+         0005    | Print\n\
+         \x20         [ <script> ]\n\
+         0006    | Nil\n\
+         \x20         [ <script> ][ nil ]\n\
+         0007    | Return\n"
+    );
+}
+
+#[test]
+fn trace_stops_at_a_runtime_error() {
+    let result = run_with(&["--trace"], "print -\"a\";\nprint 1;");
+    assert_eq!(result.code, Some(70));
+    assert_eq!(result.stdout, "");
+    assert!(result.stderr.contains("0002    | Negate\n"));
+    assert!(result
+        .stderr
+        .ends_with("Operand must be a number.\n[line 1]\n"));
+    assert!(!result.stderr.contains("Print"));
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::{output, runtime_error};
+
+#[test]
+fn arithmetic_and_number_formatting() {
+    assert_eq!(
+        output("print 1 + 2 * 3; print (1 + 2) * 3; print 7 / 2; print -0; print 1e21;"),
+        "7\n9\n3.5\n-0\n1000000000000000000000\n"
+    );
+    assert_eq!(
+        output("print 1 / 0; print -1 / 0; print 0 / 0;"),
+        "inf\n-inf\nNaN\n"
+    );
+}
+
+#[test]
+fn strings_concatenate() {
+    assert_eq!(output("print \"lox\" + \"\" + \"!\";"), "lox!\n");
+}
+
+#[test]
+fn strings_built_at_runtime_equal_their_literals() {
+    let source = "
+        var built = \"na\" + \"me\";
+        print built == \"name\";
+        print built != \"nam\" + \"e!\";
+        var fields = {};
+        fields[built] = 2;
+        print fields[\"name\"];
+    ";
+    assert_eq!(output(source), "true\ntrue\n2\n");
+}
+
+#[test]
+fn only_nil_and_false_are_falsey() {
+    assert_eq!(
+        output("print !nil; print !false; print !0; print !\"\"; print !true;"),
+        "true\ntrue\nfalse\nfalse\nfalse\n"
+    );
+}
+
+#[test]
+fn equality_never_converts_types() {
+    assert_eq!(
+        output(
+            "print 1 == 1; print \"a\" == \"a\"; print nil == nil; \
+             print 1 == \"1\"; print nil == false; print 0 == false;"
+        ),
+        "true\ntrue\ntrue\nfalse\nfalse\nfalse\n"
+    );
+    assert_eq!(output("print (0 / 0) == (0 / 0);"), "false\n");
+}
+
+#[test]
+fn comparisons() {
+    assert_eq!(
+        output("print 1 < 2; print 2 <= 2; print 1 > 2; print 3 >= 4; print 2 > 1 == true;"),
+        "true\ntrue\nfalse\nfalse\ntrue\n"
+    );
+}
+
+#[test]
+fn logical_operators_return_an_operand_and_short_circuit() {
+    assert_eq!(
+        output("print nil or \"default\"; print 1 and 2; print false and undefined;"),
+        "default\n2\nfalse\n"
+    );
+}
+
+#[test]
+fn type_errors() {
+    assert_eq!(
+        runtime_error("print -\"a\";"),
+        "Operand must be a number.\n[line 1]\n"
+    );
+    assert_eq!(
+        runtime_error("print 1 + \"a\";"),
+        "Operands must be two numbers or two strings.\n[line 1]\n"
+    );
+    assert_eq!(
+        runtime_error("print\n\n\"a\" < \"b\";"),
+        "Operands must be numbers.\n[line 3]\n"
+    );
+}
+
+#[test]
+fn runs_the_example_script() {
+    let source = include_str!("../test.lox");
+    assert_eq!(output(source), "true\n125\n");
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::{output, runtime_error};
+
+/// A function `f(n)` that recurses `n` times, each call nested inside every
+/// kind of block and loop.
+const DEEPLY_NESTED: &str = "
+    fun f(n) {
+        if (n == 0) return 0;
+        for (x in [1]) { while (true) { { if (true) { return 1 + f(n - 1); } } } }
+    }
+";
+
+#[test]
+fn functions_are_values() {
+    assert_eq!(
+        output("fun f() {} print f; print clock; var g = f; print g();"),
+        "<fn f>\n<native fn>\nnil\n"
+    );
+    assert_eq!(
+        output(
+            "fun twice(f, x) { return f(f(x)); } fun inc(x) { return x + 1; } print twice(inc, 1);"
+        ),
+        "3\n"
+    );
+}
+
+#[test]
+fn closures_keep_their_own_counters() {
+    let source = "
+        fun counter() {
+            var count = 0;
+            fun increment() {
+                count = count + 1;
+                return count;
+            }
+            return increment;
+        }
+        var a = counter();
+        var b = counter();
+        print a();
+        print a();
+        print b();
+    ";
+    assert_eq!(output(source), "1\n2\n1\n");
+}
+
+#[test]
+fn closures_capture_variables_not_values() {
+    let source = "
+        var get;
+        var set;
+        {
+            var shared = 1;
+            fun g() { return shared; }
+            fun s(value) { shared = value; }
+            get = g;
+            set = s;
+        }
+        set(2);
+        print get();
+    ";
+    assert_eq!(output(source), "2\n");
+}
+
+#[test]
+fn return_unwinds_out_of_nested_blocks_and_loops() {
+    let source = "
+        fun find(limit) {
+            for (var i = 0; i < 10; i = i + 1) {
+                while (true) {
+                    { if (i == limit) return i; }
+                    break;
+                }
+            }
+            return \"none\";
+        }
+        print find(3);
+        print find(20);
+    ";
+    assert_eq!(output(source), "3\nnone\n");
+}
+
+#[test]
+fn recursion() {
+    assert_eq!(
+        output(
+            "fun fib(n) { if (n < 2) return n; return fib(n - 2) + fib(n - 1); } print fib(15);"
+        ),
+        "610\n"
+    );
+}
+
+#[test]
+fn calls_check_arity() {
+    assert_eq!(
+        runtime_error("fun f(a, b) {}\nf(1);"),
+        "Expected 2 arguments but got 1.\n[line 2]\n"
+    );
+    assert_eq!(
+        runtime_error("clock(1);"),
+        "Expected 0 arguments but got 1.\n[line 1]\n"
+    );
+    assert_eq!(
+        runtime_error("var x = 1;\nx();"),
+        "Can only call functions and classes.\n[line 2]\n"
+    );
+}
+
+#[test]
+fn deep_recursion_runs_up_to_the_frame_limit() {
+    // 255 nested calls, plus the script itself, fill every frame.
+    assert_eq!(
+        output(&format!("{} print f(200); print f(254);", DEEPLY_NESTED)),
+        "200\n254\n"
+    );
+}
+
+#[test]
+fn recursing_past_the_frame_limit_is_a_stack_overflow() {
+    let error = runtime_error(&format!("{} print f(255);", DEEPLY_NESTED));
+    assert!(error.starts_with("Stack overflow.\n"), "{}", error);
+    assert!(runtime_error("fun f() { f(); } f();").starts_with("Stack overflow.\n"));
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::run_with;
+
+/// Compiles `source` on the VM, expecting it to be rejected, and returns
+/// the first error.
+fn vm_compile_error(source: &str) -> String {
+    let result = run_with(&["--vm"], source);
+    assert_eq!(result.code, Some(65), "{}", result.stderr);
+    result.stderr.lines().next().unwrap_or_default().to_string()
+}
+
+#[test]
+fn at_most_256_locals_per_function() {
+    // Slot zero holds the function, which leaves room for 255 more.
+    let declare =
+        |count: usize| -> String { (0..count).map(|i| format!("var l{};\n", i)).collect() };
+    let fits = format!("{{\n{}}}\nprint \"ok\";", declare(255));
+    assert_eq!(run_with(&["--vm"], &fits).stdout, "ok\n");
+
+    let source = format!("{{\n{}}}", declare(256));
+    assert_eq!(
+        vm_compile_error(&source),
+        "[line 257:5] Error at 'l255': Too many local variables in function."
+    );
+}
+
+#[test]
+fn at_most_256_closure_variables_per_function() {
+    let mut source = String::from("fun outer() {\n");
+    for i in 0..200 {
+        source += &format!("var a{};\n", i);
+    }
+    source += "fun middle() {\n";
+    for i in 0..100 {
+        source += &format!("var b{};\n", i);
+    }
+    source += "fun inner() {\n";
+    for i in 0..200 {
+        source += &format!("a{};\n", i);
+    }
+    for i in 0..100 {
+        source += &format!("b{};\n", i);
+    }
+    source += "}\n}\n}\n";
+    assert_eq!(
+        vm_compile_error(&source),
+        "[line 560:1] Error at 'b56': Too many closure variables in function."
+    );
+}
+
+#[test]
+fn jumps_span_at_most_65535_bytes() {
+    // Each statement compiles to 8 bytes, so this is well past the limit.
+    let body = "x = x + 1;\n".repeat(10_000);
+    assert_eq!(
+        vm_compile_error(&format!("var x = 1;\nif (true) {{\n{}}}", body)),
+        "[line 10003:1] Error at '}': Too much code to jump over."
+    );
+    assert_eq!(
+        vm_compile_error(&format!("var x = 1;\nwhile (false) {{\n{}}}", body)),
+        "[line 10003:1] Error at '}': Loop body too large."
+    );
+}
@@ -0,0 +1,211 @@
+mod common;
+
+use common::{output, runtime_error};
+
+#[test]
+fn literals_print_their_elements() {
+    assert_eq!(
+        output("print []; print [1, [2, \"a\"], nil, true];"),
+        "[]\n[1, [2, a], nil, true]\n"
+    );
+}
+
+#[test]
+fn subscripts_count_back_from_the_end() {
+    let source = "
+        var xs = [1, 2, 3];
+        print xs[0]; print xs[-1]; print xs[-3];
+        xs[1] = 20;
+        xs[-1] = 30;
+        print xs;
+        print xs[0] = 10;
+    ";
+    assert_eq!(output(source), "1\n3\n1\n[1, 20, 30]\n10\n");
+}
+
+#[test]
+fn lists_are_shared_not_copied() {
+    let source = "
+        var xs = [1];
+        var ys = xs;
+        ys.push(2);
+        print xs;
+        print xs == ys;
+        print [1] == [1];
+    ";
+    assert_eq!(output(source), "[1, 2]\ntrue\nfalse\n");
+}
+
+#[test]
+fn adding_and_removing_elements() {
+    let source = "
+        var xs = [3, 1];
+        print xs.push(4);
+        print xs.pop();
+        print xs.len();
+        xs.insert(0, 9);
+        xs.insert(-1, 8);
+        xs.insert(4, 7);
+        print xs;
+        print xs.remove(1);
+        print xs.remove(-1);
+        print xs;
+    ";
+    assert_eq!(
+        output(source),
+        "nil\n4\n2\n[9, 3, 8, 1, 7]\n3\n7\n[9, 8, 1]\n"
+    );
+}
+
+#[test]
+fn slices_clamp_to_the_list() {
+    let source = "
+        var xs = [1, 2, 3, 4];
+        print xs.slice(1, 3);
+        print xs.slice(-2, 100);
+        print xs.slice(3, 1);
+        print xs.slice(-100, 1);
+        var copy = xs.slice(0, xs.len());
+        copy.push(5);
+        print xs.len();
+    ";
+    assert_eq!(output(source), "[2, 3]\n[3, 4]\n[]\n[1]\n4\n");
+}
+
+#[test]
+fn sort_orders_in_place() {
+    let source = "
+        var xs = [3, 1, 2];
+        print xs.sort();
+        print xs;
+        var words = [\"pear\", \"apple\", \"fig\"];
+        words.sort();
+        print words;
+        fun descending(a, b) { return b - a; }
+        xs.sort(descending);
+        print xs;
+        // Equal elements keep their order.
+        var pairs = [[1, \"a\"], [0, \"b\"], [1, \"c\"], [0, \"d\"]];
+        fun byFirst(a, b) { return a[0] - b[0]; }
+        pairs.sort(byFirst);
+        print pairs;
+    ";
+    assert_eq!(
+        output(source),
+        "nil\n[1, 2, 3]\n[apple, fig, pear]\n[3, 2, 1]\n[[0, b], [0, d], [1, a], [1, c]]\n"
+    );
+}
+
+#[test]
+fn map_filter_and_reduce() {
+    let source = "
+        fun double(x) { return x * 2; }
+        fun odd(x) { return x % 2 == 1; }
+        fun add(total, x) { return total + x; }
+        var xs = [1, 2, 3, 4];
+        print xs.map(double);
+        print xs.filter(odd);
+        print xs.reduce(add, 10);
+        print [].reduce(add, \"empty\");
+        print xs;
+    ";
+    assert_eq!(
+        output(source),
+        "[2, 4, 6, 8]\n[1, 3]\n20\nempty\n[1, 2, 3, 4]\n"
+    );
+}
+
+#[test]
+fn callbacks_that_change_the_list_still_finish() {
+    let source = "
+        var xs = [1, 2, 3];
+        fun grow(x) { xs.push(x); return x; }
+        print xs.map(grow);
+        print xs;
+        fun shrink(x) { xs.pop(); return true; }
+        print xs.filter(shrink);
+        fun count(total, x) { xs.push(x); return total + 1; }
+        print xs.reduce(count, 0);
+    ";
+    assert_eq!(
+        output(source),
+        "[1, 2, 3]\n[1, 2, 3, 1, 2, 3]\n[1, 2, 3]\n3\n"
+    );
+}
+
+#[test]
+fn index_errors() {
+    let cases = [
+        (
+            "[1][1];",
+            "List index 1 is out of range for a list of length 1.",
+        ),
+        (
+            "[1][-2];",
+            "List index -2 is out of range for a list of length 1.",
+        ),
+        (
+            "var xs = [1]; xs[5] = 1;",
+            "List index 5 is out of range for a list of length 1.",
+        ),
+        ("[1][0.5];", "List index must be an integer."),
+        ("[1][\"a\"];", "List index must be an integer."),
+        ("1[0];", "Only lists and maps can be indexed."),
+        (
+            "var n = nil; n[0] = 1;",
+            "Only lists and maps can be indexed.",
+        ),
+    ];
+    for (source, message) in cases {
+        assert_eq!(
+            runtime_error(&format!("\n{}", source)),
+            format!("{}\n[line 2]\n", message),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn method_errors() {
+    let cases = [
+        ("[].pop();", "Can't pop from an empty list."),
+        (
+            "[1].insert(3, 1);",
+            "List index 3 is out of range for a list of length 1.",
+        ),
+        (
+            "[1].remove(2);",
+            "List index 2 is out of range for a list of length 1.",
+        ),
+        (
+            "[1].slice(0.5, 1);",
+            "slice() expects an integer as argument 1.",
+        ),
+        (
+            "[1, \"a\"].sort();",
+            "sort() can only compare two numbers or two strings without a comparator.",
+        ),
+        (
+            "fun f(a, b) { return \"x\"; } [2, 1].sort(f);",
+            "Comparator must return a number.",
+        ),
+        ("[1].map(1);", "Can only call functions and classes."),
+        (
+            "fun f(a) { return a; } [1].reduce(f, 0);",
+            "Expected 1 arguments but got 2.",
+        ),
+        ("[1].len(1);", "Expected 0 arguments but got 1."),
+        ("[1].push();", "Expected 1 arguments but got 0."),
+        ("[1].sort(1, 2);", "Expected 0 to 1 arguments but got 2."),
+        ("[1].nope;", "Undefined property 'nope'."),
+    ];
+    for (source, message) in cases {
+        assert_eq!(
+            runtime_error(&format!("\n{}", source)),
+            format!("{}\n[line 2]\n", message),
+            "{}",
+            source
+        );
+    }
+}
@@ -0,0 +1,196 @@
+mod common;
+
+use common::{compile_errors, output, run_with, runtime_error};
+
+#[test]
+fn iterates_lists_maps_and_strings() {
+    let source = "
+        for (x in [1, 2]) print x;
+        for (k in {\"a\": 1, 2: \"b\"}) print k;
+        for (c in \"hé\") print c;
+        for (x in []) print \"never\";
+    ";
+    assert_eq!(output(source), "1\n2\na\n2\nh\né\n");
+}
+
+#[test]
+fn iterates_instances_through_iter_done_and_next() {
+    let source = "
+        class Range {
+            init(n) { this.n = n; }
+            iter() { return RangeIter(this.n); }
+        }
+        class RangeIter {
+            init(n) { this.i = 0; this.n = n; }
+            done() { return this.i >= this.n; }
+            next() { this.i = this.i + 1; return this.i; }
+        }
+        for (x in Range(3)) print x;
+    ";
+    assert_eq!(output(source), "1\n2\n3\n");
+}
+
+#[test]
+fn each_iteration_gets_its_own_variable() {
+    let source = "
+        var getters = [];
+        for (x in [1, 2]) {
+            fun get() { return x; }
+            getters.push(get);
+        }
+        print getters[0]();
+        print getters[1]();
+    ";
+    assert_eq!(output(source), "1\n2\n");
+}
+
+#[test]
+fn lists_are_read_afresh_at_each_step() {
+    let source = "
+        var xs = [1, 2, 3];
+        for (x in xs) {
+            if (x == 1) xs.push(4);
+            if (x == 2) xs.pop();
+            print x;
+        }
+    ";
+    assert_eq!(output(source), "1\n2\n3\n");
+}
+
+#[test]
+fn map_keys_are_taken_when_the_loop_starts() {
+    // Deleting the current key doesn't skip the next one.
+    let source = "
+        var m = {\"a\": 1, \"b\": 2, \"c\": 3};
+        for (k in m) { print k; m.delete(k); }
+        print m;
+    ";
+    assert_eq!(output(source), "a\nb\nc\n{}\n");
+
+    // Keys added during the loop aren't visited, and keys deleted before
+    // the loop reaches them are passed over.
+    let source = "
+        var m = {\"a\": 1, \"b\": 2, \"c\": 3};
+        for (k in m) {
+            print k;
+            m.delete(\"b\");
+            m[k + \"!\"] = 0;
+        }
+        print m;
+    ";
+    assert_eq!(output(source), "a\nc\n{a: 1, c: 3, a!: 0, c!: 0}\n");
+}
+
+#[test]
+fn map_iteration_survives_collection() {
+    let source = "
+        var m = {\"one\": 1, \"two\": 2};
+        for (k in m) {
+            m.delete(\"two\");
+            m[\"t\" + \"wo\"] = 2;
+            print k;
+        }
+    ";
+    // A key deleted and added back before the loop reaches it is visited.
+    assert_eq!(output(source), "one\ntwo\n");
+    assert_eq!(run_with(&["--stress-gc"], source).stdout, "one\ntwo\n");
+}
+
+#[test]
+fn break_and_continue_in_every_loop() {
+    let source = "
+        var i = 0;
+        while (true) {
+            i = i + 1;
+            if (i < 3) continue;
+            break;
+        }
+        print i;
+        for (var j = 0; j < 5; j = j + 1) {
+            if (j == 1) continue;
+            if (j == 3) break;
+            print j;
+        }
+        var s = \"\";
+        for (c in \"lox!\") {
+            if (c == \"o\") continue;
+            if (c == \"!\") break;
+            s = s + c;
+        }
+        print s;
+    ";
+    assert_eq!(output(source), "3\n0\n2\nlx\n");
+}
+
+#[test]
+fn break_leaves_only_the_innermost_loop() {
+    let source = "
+        for (a in [1, 2]) {
+            for (b in [1, 2, 3]) {
+                if (b == 2) break;
+                print a * 10 + b;
+            }
+        }
+    ";
+    assert_eq!(output(source), "11\n21\n");
+}
+
+#[test]
+fn continue_in_a_for_loop_still_runs_the_increment() {
+    let source = "
+        var n = 0;
+        for (var i = 0; i < 3; i = i + 1) {
+            n = n + 1;
+            {
+                var shadow = i;
+                continue;
+            }
+            print \"never\";
+        }
+        print n;
+    ";
+    assert_eq!(output(source), "3\n");
+}
+
+#[test]
+fn iteration_errors() {
+    let cases = [
+        (
+            "for (x in 1) print x;",
+            "Can only iterate over lists, maps, strings and instances.",
+        ),
+        (
+            "class A {} for (x in A()) print x;",
+            "Undefined property 'iter'.",
+        ),
+        (
+            "class A { iter() { return 1; } } for (x in A()) print x;",
+            "Only instances have properties.",
+        ),
+        (
+            "class I { done() { return false; } } \
+             class A { iter() { return I(); } } for (x in A()) print x;",
+            "Undefined property 'next'.",
+        ),
+    ];
+    for (source, message) in cases {
+        assert_eq!(
+            runtime_error(&format!("\n{}", source)),
+            format!("{}\n[line 2]\n", message),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn break_and_continue_need_a_loop() {
+    assert_eq!(
+        compile_errors("print 1;\nbreak;"),
+        "[line 2:1] Error at 'break': Can't use 'break' outside of a loop.\n"
+    );
+    assert_eq!(
+        compile_errors("while (true) { fun f() { continue; } }"),
+        "[line 1:26] Error at 'continue': Can't use 'continue' outside of a loop.\n"
+    );
+}
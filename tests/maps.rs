@@ -0,0 +1,126 @@
+mod common;
+
+use common::{output, runtime_error};
+
+#[test]
+fn literals_take_any_hashable_key() {
+    let source = "
+        var m = {\"a\": 1, 2: \"two\", true: \"yes\", nil: \"none\"};
+        print m;
+        print m[\"a\"]; print m[2]; print m[true]; print m[nil];
+        print {};
+        print {\"x\": {\"y\": [1]}}[\"x\"][\"y\"][0];
+    ";
+    assert_eq!(
+        output(source),
+        "{a: 1, 2: two, true: yes, nil: none}\n1\ntwo\nyes\nnone\n{}\n1\n"
+    );
+}
+
+#[test]
+fn entries_keep_insertion_order() {
+    let source = "
+        var m = {\"b\": 1, \"a\": 2};
+        m[\"c\"] = 3;
+        m[\"b\"] = 10;
+        print m;
+        m.delete(\"b\");
+        m[\"b\"] = 4;
+        print m.keys();
+        print m.values();
+        print {1: 1, 1: 2};
+    ";
+    assert_eq!(
+        output(source),
+        "{b: 10, a: 2, c: 3}\n[a, c, b]\n[2, 3, 4]\n{1: 2}\n"
+    );
+}
+
+#[test]
+fn equal_numbers_are_the_same_key() {
+    assert_eq!(
+        output("var m = {0: \"zero\", 1: \"one\"}; print m[-0]; print m[2 / 2];"),
+        "zero\none\n"
+    );
+}
+
+#[test]
+fn methods() {
+    let source = "
+        var m = {\"a\": 1, \"b\": 2};
+        print m.len();
+        print m.has(\"a\"); print m.has(\"z\");
+        print m.delete(\"a\"); print m.delete(\"a\");
+        print m.len();
+        print m;
+    ";
+    assert_eq!(output(source), "2\ntrue\nfalse\ntrue\nfalse\n1\n{b: 2}\n");
+}
+
+#[test]
+fn maps_are_shared_not_copied() {
+    let source = "
+        var m = {};
+        var n = m;
+        n[\"k\"] = 1;
+        print m.has(\"k\");
+        print m == n;
+        print {} == {};
+    ";
+    assert_eq!(output(source), "true\ntrue\nfalse\n");
+}
+
+#[test]
+fn key_errors() {
+    let cases = [
+        ("m[\"a\"];", "Undefined key 'a'."),
+        ("m[1];", "Undefined key '1'."),
+        (
+            "m[[1]] = 1;",
+            "Map keys must be strings, numbers, booleans or nil.",
+        ),
+        (
+            "m[m];",
+            "Map keys must be strings, numbers, booleans or nil.",
+        ),
+        (
+            "var n = {[1]: 1};",
+            "Map keys must be strings, numbers, booleans or nil.",
+        ),
+        ("m[0/0];", "Map keys can't be NaN."),
+        ("m[0/0] = 1;", "Map keys can't be NaN."),
+        ("var n = {0/0: 1};", "Map keys can't be NaN."),
+    ];
+    for (source, message) in cases {
+        assert_eq!(
+            runtime_error(&format!("var m = {{\"k\": 1}};\n{}", source)),
+            format!("{}\n[line 2]\n", message),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn method_errors() {
+    let cases = [
+        (
+            "m.has([]);",
+            "Map keys must be strings, numbers, booleans or nil.",
+        ),
+        ("m.has(0/0);", "Map keys can't be NaN."),
+        ("m.delete(0/0);", "Map keys can't be NaN."),
+        ("m.keys(1);", "Expected 0 arguments but got 1."),
+        ("m.has();", "Expected 1 arguments but got 0."),
+        ("m.nope;", "Undefined property 'nope'."),
+        ("m.len = 1;", "Only instances have fields."),
+    ];
+    for (source, message) in cases {
+        assert_eq!(
+            runtime_error(&format!("var m = {{\"k\": 1}};\n{}", source)),
+            format!("{}\n[line 2]\n", message),
+            "{}",
+            source
+        );
+    }
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use common::{output, runtime_error};
+
+#[test]
+fn modulo_takes_the_sign_of_the_divisor() {
+    assert_eq!(
+        output("print 7 % 3; print -7 % 3; print 7 % -3; print -7 % -3; print 5.5 % 2;"),
+        "1\n2\n-2\n-1\n1.5\n"
+    );
+}
+
+#[test]
+fn modulo_binds_like_multiplication() {
+    assert_eq!(output("print 1 + 7 % 4 * 2; print 10 - 9 % 4;"), "7\n9\n");
+}
+
+#[test]
+fn div_rounds_down_and_agrees_with_modulo() {
+    assert_eq!(
+        output("print div(7, 2); print div(-7, 2); print div(7, -2); print div(6, 3);"),
+        "3\n-4\n-4\n2\n"
+    );
+    let source = "
+        for (a in [7, -7, 5.5, -0.5]) {
+            for (b in [3, -3, 2, 0.25]) {
+                if (div(a, b) * b + a % b != a) print [a, b];
+            }
+        }
+        print \"ok\";
+    ";
+    assert_eq!(output(source), "ok\n");
+}
+
+#[test]
+fn only_slash_divides_by_zero() {
+    assert_eq!(output("print 1 / 0; print -1 / 0;"), "inf\n-inf\n");
+    assert_eq!(
+        runtime_error("var a = 1;\nprint a % 0;"),
+        "Division by zero.\n[line 2]\n"
+    );
+    assert_eq!(
+        runtime_error("var a = 1;\nprint div(a, 0);"),
+        "Division by zero.\n[line 2]\n"
+    );
+}
+
+#[test]
+fn rounding_and_arithmetic_functions() {
+    let source = "
+        print floor(-1.5); print ceil(-1.5);
+        print round(2.5); print round(-2.5); print round(2.4);
+        print abs(-3); print sqrt(16); print sqrt(-1);
+        print pow(2, 10); print min(1, 2); print max(1, 2);
+        print log(E); print exp(0);
+    ";
+    assert_eq!(
+        output(source),
+        "-2\n-1\n3\n-3\n2\n3\n4\nNaN\n1024\n1\n2\n1\n1\n"
+    );
+}
+
+#[test]
+fn trigonometry_and_constants() {
+    let source = "
+        print PI; print E;
+        print sin(0); print cos(0); print tan(0);
+        print asin(1) * 2 == PI; print acos(1); print atan(1) * 4 == PI;
+        print atan2(1, -1) == 3 * PI / 4;
+    ";
+    assert_eq!(
+        output(source),
+        "3.141592653589793\n2.718281828459045\n0\n1\n0\ntrue\n0\ntrue\ntrue\n"
+    );
+}
+
+#[test]
+fn random_is_reproducible() {
+    // `output` runs each backend in its own process, and checks they agree.
+    let first = output("print random(); print random();");
+    assert_eq!(first, "0.038848734697185194\n0.3328011087394298\n");
+    assert_eq!(output("print random(); print random();"), first);
+
+    let source = "
+        seed(42);
+        var a = [random(), random()];
+        seed(42);
+        var b = [random(), random()];
+        print a[0] == b[0] and a[1] == b[1];
+        print a[0] != a[1];
+        seed(43);
+        print random() != a[0];
+    ";
+    assert_eq!(output(source), "true\ntrue\ntrue\n");
+}
+
+#[test]
+fn random_stays_in_the_unit_interval() {
+    let source = "
+        seed(7);
+        var low = 1;
+        var high = 0;
+        for (i in [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]) {
+            for (j in [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]) {
+                var r = random();
+                low = min(low, r);
+                high = max(high, r);
+            }
+        }
+        print low >= 0 and high < 1;
+        print high - low > 0.5;
+    ";
+    assert_eq!(output(source), "true\ntrue\n");
+}
+
+#[test]
+fn bad_arguments_are_runtime_errors() {
+    let cases = [
+        ("floor(\"a\");", "floor() expects a number as argument 1."),
+        ("pow(2, nil);", "pow() expects a number as argument 2."),
+        ("div(1, \"2\");", "div() expects a number as argument 2."),
+        ("seed(true);", "seed() expects a number as argument 1."),
+        ("print 1 % \"a\";", "Operands must be numbers."),
+        ("sqrt();", "Expected 1 arguments but got 0."),
+    ];
+    for (source, message) in cases {
+        assert_eq!(
+            runtime_error(&format!("\n{}", source)),
+            format!("{}\n[line 2]\n", message),
+            "{}",
+            source
+        );
+    }
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::{output, runtime_error};
+
+#[test]
+fn lengths_and_indexes_count_characters() {
+    let source = "
+        print len(\"\");
+        print len(\"héllo\");
+        print len(\"日本語\");
+        print index_of(\"日本語\", \"語\");
+        print index_of(\"abcabc\", \"c\");
+        print index_of(\"abc\", \"z\");
+        print char_at(\"héllo\", 1);
+        print substr(\"héllo wörld\", 6, 5);
+        print substr(\"abc\", 3, 0) == \"\";
+    ";
+    assert_eq!(output(source), "0\n5\n3\n2\n2\n-1\né\nwörld\ntrue\n");
+}
+
+#[test]
+fn case_trimming_and_affixes() {
+    let source = "
+        print upper(\"straße\");
+        print lower(\"ÀB\");
+        print \"[\" + trim(\" \\t padded \\n \") + \"]\";
+        print starts_with(\"lox\", \"lo\");
+        print starts_with(\"lox\", \"ox\");
+        print ends_with(\"lox\", \"ox\");
+        print ends_with(\"lox\", \"\");
+    ";
+    assert_eq!(
+        output(source),
+        "STRASSE\nàb\n[padded]\ntrue\nfalse\ntrue\ntrue\n"
+    );
+}
+
+#[test]
+fn replace_split_and_join() {
+    let source = "
+        print replace(\"a-b-c\", \"-\", \"+\");
+        print split(\"a,b,,c\", \",\");
+        print split(\"añb\", \"\");
+        print join(split(\"a b c\", \" \"), \"/\");
+        print join([], \", \") == \"\";
+    ";
+    assert_eq!(
+        output(source),
+        "a+b+c\n[a, b, , c]\n[a, ñ, b]\na/b/c\ntrue\n"
+    );
+}
+
+#[test]
+fn conversions() {
+    let source = "
+        print to_number(\" 42 \") + 1;
+        print to_number(\"-1.5e2\");
+        print to_string(12.5) + \"!\";
+        print to_string(nil);
+        print to_string([1, \"a\"]);
+        print to_string(\"same\");
+    ";
+    assert_eq!(output(source), "43\n-150\n12.5!\nnil\n[1, a]\nsame\n");
+}
+
+#[test]
+fn to_number_rejects_text_that_isnt_a_number() {
+    assert_eq!(
+        runtime_error("print 1;\nprint to_number(\"abc\");"),
+        "to_number() can't parse 'abc' as a number.\n[line 2]\n"
+    );
+    // Rust's parser accepts these, but they aren't Lox numbers.
+    for text in ["inf", "NaN", ""] {
+        assert_eq!(
+            runtime_error(&format!("to_number(\"{}\");", text)),
+            format!(
+                "to_number() can't parse '{}' as a number.\n[line 1]\n",
+                text
+            )
+        );
+    }
+}
+
+#[test]
+fn bad_arguments_are_runtime_errors() {
+    let cases = [
+        ("len(1);", "len() expects a string as argument 1."),
+        ("upper(nil);", "upper() expects a string as argument 1."),
+        (
+            "index_of(\"a\", 1);",
+            "index_of() expects a string as argument 2.",
+        ),
+        (
+            "substr(\"abc\", 1.5, 1);",
+            "substr() expects an integer as argument 2.",
+        ),
+        (
+            "substr(\"abc\", 2, 2);",
+            "Substring 2..4 is out of range for a string of length 3.",
+        ),
+        (
+            "substr(\"abc\", -1, 1);",
+            "Substring -1..0 is out of range for a string of length 3.",
+        ),
+        (
+            "char_at(\"héllo\", 5);",
+            "String index 5 is out of range for a string of length 5.",
+        ),
+        (
+            "replace(\"abc\", \"\", \"x\");",
+            "Can't replace an empty string.",
+        ),
+        (
+            "join([\"a\", 1], \",\");",
+            "join() expects a list of strings as argument 1.",
+        ),
+        ("split(\"a\");", "Expected 2 arguments but got 1."),
+    ];
+    for (source, message) in cases {
+        assert_eq!(
+            runtime_error(&format!("\n{}", source)),
+            format!("{}\n[line 2]\n", message),
+            "{}",
+            source
+        );
+    }
+}
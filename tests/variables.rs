@@ -0,0 +1,84 @@
+mod common;
+
+use common::{output, run, runtime_error};
+
+#[test]
+fn inner_blocks_shadow_outer_ones() {
+    let source = "
+        var a = \"global\";
+        {
+            var a = \"outer\";
+            {
+                var a = \"inner\";
+                print a;
+            }
+            print a;
+        }
+        print a;
+    ";
+    assert_eq!(output(source), "inner\nouter\nglobal\n");
+}
+
+#[test]
+fn assignment_reaches_the_nearest_declaration() {
+    let source = "
+        var a = 1;
+        {
+            var b = 2;
+            {
+                a = 10;
+                b = 20;
+            }
+            print b;
+        }
+        print a;
+    ";
+    assert_eq!(output(source), "20\n10\n");
+}
+
+#[test]
+fn assignment_is_an_expression() {
+    assert_eq!(
+        output("var a; var b; a = b = 3; print a; print b;"),
+        "3\n3\n"
+    );
+}
+
+#[test]
+fn uninitialized_variables_are_nil() {
+    assert_eq!(output("var a; print a; { var b; print b; }"), "nil\nnil\n");
+}
+
+#[test]
+fn globals_can_be_redeclared() {
+    assert_eq!(output("var a = 1; var a = a + 1; print a;"), "2\n");
+}
+
+#[test]
+fn undeclared_variables_are_runtime_errors() {
+    assert_eq!(
+        runtime_error("print 1;\nprint missing;"),
+        "Undefined variable 'missing'.\n[line 2]\n"
+    );
+    assert_eq!(
+        runtime_error("missing = 1;"),
+        "Undefined variable 'missing'.\n[line 1]\n"
+    );
+    // A block's variables are gone once it ends.
+    assert_eq!(
+        runtime_error("{ var inner = 1; }\nprint inner;"),
+        "Undefined variable 'inner'.\n[line 2]\n"
+    );
+}
+
+#[test]
+fn static_errors_stop_the_script_before_it_runs() {
+    let run = run("print 1;\n{ var a = 1; var a = 2; }\nreturn;");
+    assert_eq!(run.code, Some(65));
+    assert_eq!(run.stdout, "");
+    assert_eq!(
+        run.stderr,
+        "[line 2:18] Error at 'a': Already a variable with this name in this scope.\n\
+         [line 3:1] Error at 'return': Can't return from top-level code.\n"
+    );
+}